@@ -1,6 +1,14 @@
 // (c) Cartesi and individual authors (see AUTHORS)
 // SPDX-License-Identifier: Apache-2.0 (see LICENSE)
 
+use sha3::{Digest, Sha3_256};
+
+const PROTOS: [&str; 3] = [
+    "./grpc-interfaces/versioning.proto",
+    "./grpc-interfaces/cartesi-machine.proto",
+    "./grpc-interfaces/server-manager.proto",
+];
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     tonic_build::configure()
         .protoc_arg("--experimental_allow_proto3_optional")
@@ -11,8 +19,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             ],
             &["./grpc-interfaces"],
         )?;
-    println!("cargo:rerun-if-changed=./grpc-interfaces/versioning.proto");
-    println!("cargo:rerun-if-changed=./grpc-interfaces/server-manager.proto");
+    // Hash of the proto files, so a mismatched server manager can be told apart at runtime
+    let mut hasher = Sha3_256::new();
+    for proto in PROTOS {
+        hasher.update(std::fs::read(proto)?);
+        println!("cargo:rerun-if-changed={}", proto);
+    }
+    println!(
+        "cargo:rustc-env=GRPC_INTERFACES_SCHEMA_HASH={}",
+        hex::encode(hasher.finalize())
+    );
     println!("cargo:rerun-if-changed=build.rs");
     Ok(())
 }
@@ -1,6 +1,9 @@
 // (c) Cartesi and individual authors (see AUTHORS)
 // SPDX-License-Identifier: Apache-2.0 (see LICENSE)
 
+/// SHA3-256 of the proto files the interfaces were generated from
+pub const SCHEMA_HASH: &str = env!("GRPC_INTERFACES_SCHEMA_HASH");
+
 pub mod versioning {
     tonic::include_proto!("versioning");
 }
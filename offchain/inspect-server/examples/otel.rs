@@ -0,0 +1,63 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+//! Export the trace of an inspect request to an OpenTelemetry collector.
+//! The trace has the span of the HTTP request, the inspect span of the client, and the
+//! inspect_state span of the gRPC call, whose context is sent to the server manager in the
+//! traceparent metadata; a mock server manager answers the call.
+//!
+//! Start a collector with an OTLP endpoint, such as Jaeger, and run the example:
+//!
+//! ```sh
+//! docker run --rm -p 4317:4317 -p 16686:16686 jaegertracing/all-in-one
+//! OTEL_EXPORTER_OTLP_ENDPOINT=http://localhost:4317 OTEL_SERVICE_NAME=inspect-server \
+//!     cargo run -p inspect-server --example otel --features otel,test-utils
+//! ```
+//!
+//! The trace is then shown by the Jaeger UI at http://localhost:16686.
+
+use inspect_server::config::InspectServerConfig;
+use inspect_server::inspect::InspectClient;
+use inspect_server::otel;
+use inspect_server::testing::{MockServerManager, MOCK_SESSION_ID};
+
+const INSPECT_SERVER_ADDRESS: &str = "127.0.0.1:5005";
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let layer = otel::layer()?.ok_or_else(|| {
+        format!(
+            "{} must be set to export the spans",
+            otel::OTLP_ENDPOINT_ENV
+        )
+    })?;
+    log::configure_with_layers(&Default::default(), vec![layer]);
+
+    let server_manager = MockServerManager::new().start().await;
+    let config = InspectServerConfig::builder()
+        .inspect_server_address(INSPECT_SERVER_ADDRESS)
+        .server_manager_address(server_manager.address().to_string())
+        .session_id(MOCK_SESSION_ID)
+        .build()?;
+    let inspect_client = InspectClient::new(&config);
+    let server = inspect_server::server::create(&config, inspect_client)?;
+    let server_handle = server.handle();
+    tokio::spawn(server);
+
+    // The caller continues its own trace, so the inspect spans are part of it
+    let response = reqwest::Client::new()
+        .get(format!("http://{}/inspect/hello", INSPECT_SERVER_ADDRESS))
+        .header(
+            "traceparent",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        )
+        .send()
+        .await?;
+    tracing::info!(status = %response.status(), "inspect finished");
+    tracing::info!("exporting trace 4bf92f3577b34da6a3ce929d0e0e4736");
+
+    server_handle.stop(true).await;
+    server_manager.stop().await;
+    otel::shutdown();
+    Ok(())
+}
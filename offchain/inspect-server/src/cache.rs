@@ -0,0 +1,165 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+use sha3::{Digest, Sha3_256};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::inspect::InspectStateResponse;
+
+pub type CacheKey = [u8; 32];
+
+/// Least recently used cache of inspect responses.
+/// Inspects don't change the machine state, so the same payload has the same response until the
+/// server manager processes a new input. The whole cache is invalidated when a response from the
+/// server manager has a different processed_input_count, and each entry expires after the TTL, so
+/// a response is never served for longer than the TTL after a new input was processed.
+pub struct InspectCache {
+    capacity: usize,
+    ttl: Duration,
+    processed_input_count: Option<u64>,
+    clock: u64,
+    entries: HashMap<CacheKey, CacheEntry>,
+}
+
+struct CacheEntry {
+    response: InspectStateResponse,
+    inserted_at: Instant,
+    last_used: u64,
+}
+
+impl InspectCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            processed_input_count: None,
+            clock: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Key of the payload in the cache.
+    pub fn key(payload: &[u8]) -> CacheKey {
+        Sha3_256::digest(payload).into()
+    }
+
+    /// Return the cached response of the payload if it didn't expire.
+    pub fn get(&mut self, key: &CacheKey) -> Option<InspectStateResponse> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        if entry.inserted_at.elapsed() >= self.ttl {
+            self.entries.remove(key);
+            return None;
+        }
+        entry.last_used = self.clock;
+        Some(entry.response.clone())
+    }
+
+    /// Remove all the responses, e.g. when they come from a session that is no longer used.
+    /// Return the number of responses removed.
+    pub fn clear(&mut self) -> usize {
+        let count = self.entries.len();
+        self.entries.clear();
+        self.processed_input_count = None;
+        count
+    }
+
+    /// Add a response that came from the server manager to the cache.
+    pub fn insert(&mut self, key: CacheKey, response: &InspectStateResponse) {
+        if self.processed_input_count != Some(response.processed_input_count) {
+            if self.processed_input_count.is_some() {
+                tracing::debug!(
+                    "invalidating inspect cache processed_input_count={}",
+                    response.processed_input_count
+                );
+            }
+            self.entries.clear();
+            self.processed_input_count = Some(response.processed_input_count);
+        }
+        if !self.entries.contains_key(&key)
+            && self.entries.len() >= self.capacity
+        {
+            self.evict();
+        }
+        self.clock += 1;
+        self.entries.insert(
+            key,
+            CacheEntry {
+                response: response.clone(),
+                inserted_at: Instant::now(),
+                last_used: self.clock,
+            },
+        );
+    }
+
+    /// Remove the least recently used entry.
+    fn evict(&mut self) {
+        let key = self
+            .entries
+            .iter()
+            .min_by_key(|(_, entry)| entry.last_used)
+            .map(|(key, _)| *key);
+        if let Some(key) = key {
+            self.entries.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    fn response(processed_input_count: u64) -> InspectStateResponse {
+        InspectStateResponse {
+            processed_input_count,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn it_returns_cached_responses() {
+        let mut cache = InspectCache::new(2, TTL);
+        let key = InspectCache::key(b"hello");
+        assert_eq!(cache.get(&key), None);
+        cache.insert(key, &response(1));
+        assert_eq!(cache.get(&key), Some(response(1)));
+        assert_eq!(cache.get(&InspectCache::key(b"world")), None);
+    }
+
+    #[test]
+    fn it_evicts_least_recently_used_response() {
+        let mut cache = InspectCache::new(2, TTL);
+        let first = InspectCache::key(b"first");
+        let second = InspectCache::key(b"second");
+        let third = InspectCache::key(b"third");
+        cache.insert(first, &response(1));
+        cache.insert(second, &response(1));
+        cache.get(&first);
+        cache.insert(third, &response(1));
+        assert!(cache.get(&first).is_some());
+        assert!(cache.get(&second).is_none());
+        assert!(cache.get(&third).is_some());
+    }
+
+    #[test]
+    fn it_invalidates_when_processed_input_count_changes() {
+        let mut cache = InspectCache::new(2, TTL);
+        let first = InspectCache::key(b"first");
+        let second = InspectCache::key(b"second");
+        cache.insert(first, &response(1));
+        cache.insert(second, &response(2));
+        assert!(cache.get(&first).is_none());
+        assert_eq!(cache.get(&second), Some(response(2)));
+    }
+
+    #[test]
+    fn it_expires_responses_after_ttl() {
+        let mut cache = InspectCache::new(2, Duration::ZERO);
+        let key = InspectCache::key(b"hello");
+        cache.insert(key, &response(1));
+        assert_eq!(cache.get(&key), None);
+    }
+}
@@ -0,0 +1,158 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Circuit breaker that stops the inspect requests from trying to reach the server manager while
+/// it is unreachable.
+/// After `threshold` consecutive connection failures, the circuit opens and rejects the requests
+/// until the cooldown expires. Then, it lets a single probe request through: if it succeeds the
+/// circuit closes, otherwise it opens again. A threshold of zero disables the circuit breaker.
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Closed {
+        failures: u32,
+    },
+    Open {
+        until: Instant,
+    },
+    /// A probe is in flight; another one is allowed if it doesn't finish until the deadline
+    HalfOpen {
+        until: Instant,
+    },
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            state: Mutex::new(State::Closed { failures: 0 }),
+        }
+    }
+
+    /// Check whether a request may try to reach the server manager.
+    pub fn try_acquire(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        match *state {
+            State::Closed { .. } => true,
+            State::Open { until } | State::HalfOpen { until }
+                if now >= until =>
+            {
+                tracing::info!(
+                    "circuit breaker is half-open, probing server manager"
+                );
+                *state = State::HalfOpen {
+                    until: now + self.cooldown,
+                };
+                true
+            }
+            State::Open { .. } | State::HalfOpen { .. } => false,
+        }
+    }
+
+    /// Record that a request reached the server manager.
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        if !matches!(*state, State::Closed { .. }) {
+            tracing::info!("circuit breaker is closed");
+        }
+        *state = State::Closed { failures: 0 };
+    }
+
+    /// Record that a request failed to reach the server manager.
+    pub fn record_failure(&self) {
+        if self.threshold == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        let failures = match *state {
+            State::Closed { failures } => failures + 1,
+            State::HalfOpen { .. } => self.threshold,
+            State::Open { .. } => return,
+        };
+        *state = if failures >= self.threshold {
+            tracing::warn!(
+                "circuit breaker is open after {} connection failures",
+                failures
+            );
+            State::Open {
+                until: Instant::now() + self.cooldown,
+            }
+        } else {
+            State::Closed { failures }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COOLDOWN: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn it_opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new(2, COOLDOWN);
+        breaker.record_failure();
+        assert!(breaker.try_acquire());
+        breaker.record_success();
+        breaker.record_failure();
+        assert!(breaker.try_acquire());
+        breaker.record_failure();
+        assert!(!breaker.try_acquire());
+    }
+
+    #[test]
+    fn it_goes_through_closed_open_half_open_closed() {
+        let breaker = CircuitBreaker::new(1, COOLDOWN);
+        breaker.record_failure();
+        assert!(!breaker.try_acquire());
+        std::thread::sleep(COOLDOWN);
+        // Only a single probe is allowed
+        assert!(breaker.try_acquire());
+        assert!(!breaker.try_acquire());
+        breaker.record_success();
+        assert!(breaker.try_acquire());
+        assert!(breaker.try_acquire());
+    }
+
+    #[test]
+    fn it_opens_again_when_probe_fails() {
+        let breaker = CircuitBreaker::new(3, COOLDOWN);
+        for _ in 0..3 {
+            breaker.record_failure();
+        }
+        std::thread::sleep(COOLDOWN);
+        assert!(breaker.try_acquire());
+        breaker.record_failure();
+        assert!(!breaker.try_acquire());
+    }
+
+    #[test]
+    fn it_allows_new_probe_when_previous_one_is_lost() {
+        let breaker = CircuitBreaker::new(1, COOLDOWN);
+        breaker.record_failure();
+        std::thread::sleep(COOLDOWN);
+        assert!(breaker.try_acquire());
+        std::thread::sleep(COOLDOWN);
+        assert!(breaker.try_acquire());
+    }
+
+    #[test]
+    fn it_never_opens_when_disabled() {
+        let breaker = CircuitBreaker::new(0, COOLDOWN);
+        for _ in 0..10 {
+            breaker.record_failure();
+        }
+        assert!(breaker.try_acquire());
+    }
+}
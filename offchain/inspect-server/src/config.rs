@@ -5,10 +5,23 @@
 /// configuration file.
 /// Command-line parameters take precedence over environment variables and environment variables
 /// take precedence over same parameter from file configuration.
-use clap::Parser;
-use log::{LogConfig, LogEnvCliConfig};
+use clap::{CommandFactory, FromArgMatches, Parser};
+use log::{ConfigSources, LogConfig, LogEnvCliConfig, Redacted};
 use serde::Deserialize;
-use snafu::{ResultExt, Snafu};
+use snafu::{OptionExt, ResultExt, Snafu};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt::Debug;
+use std::time::Duration;
+use tonic::metadata::AsciiMetadataValue;
+
+use crate::server::CARTESI_MACHINE_RX_BUFFER_LIMIT;
+
+/// Default maximum size of the gRPC responses of the server manager, far above the 4MB of tonic
+pub const DEFAULT_MAX_RESPONSE_BYTES: usize = 64 * 1024 * 1024;
+
+/// Default time after which a queued inspect is promoted to the next priority
+pub const DEFAULT_PRIORITY_AGING: Duration = Duration::from_secs(1);
 
 #[derive(Debug, Snafu)]
 pub enum ConfigError {
@@ -18,6 +31,33 @@ pub enum ConfigError {
     #[snafu(display("parse configuration file error"))]
     ParseError { source: toml::de::Error },
 
+    #[snafu(display("{} must be greater than zero", name))]
+    ZeroValue { name: &'static str },
+
+    #[snafu(display("{} must be at least 0 and less than 1", name))]
+    InvalidFraction { name: &'static str },
+
+    #[snafu(display("{} is required", name))]
+    MissingValue { name: &'static str },
+
+    #[snafu(display(
+        "{} must be finite, greater than zero, and large enough to refill the burst in a valid duration",
+        name
+    ))]
+    InvalidRate { name: &'static str },
+
+    #[snafu(display("invalid server manager address {:?}", address))]
+    InvalidServerManagerAddress {
+        address: String,
+        source: tonic::transport::Error,
+    },
+
+    #[snafu(display("invalid node id {:?}", node_id))]
+    InvalidNodeId { node_id: String },
+
+    #[snafu(display("TLS client cert and key must be provided together"))]
+    IncompleteTlsClientIdentity,
+
     #[snafu(whatever, display("{message}"))]
     Whatever {
         message: String,
@@ -25,14 +65,219 @@ pub enum ConfigError {
         source: Option<Box<dyn std::error::Error>>,
     },
 }
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct InspectServerConfig {
     pub log_config: LogConfig,
     pub inspect_server_address: String,
+    /// Server manager address, with or without the http:// or https:// scheme, or the path of a
+    /// Unix domain socket with the unix: scheme.
+    /// Several replicas can be given as a comma-separated list, in order of preference; the
+    /// requests fail over to the next one when an endpoint is unreachable.
     pub server_manager_address: String,
     pub session_id: String,
+    /// Maximum number of inspect requests waiting for the server manager.
+    /// Each queued request holds its whole payload, so the queue can use up to
+    /// queue_size * max_payload_size bytes of memory.
     pub queue_size: usize,
+    /// Fraction of the queue capacity above which the requests are rejected with a probability
+    /// that grows to 1 at the full queue; zero disables the load shedding
+    pub load_shedding_threshold: f64,
+    /// Time an HTTP inspect request waits for space in the queue when it is full; zero fails
+    /// immediately
+    pub enqueue_timeout: Duration,
+    /// Time after which an inspect waiting in the queue is promoted to the next priority, so the
+    /// low priority inspects are not starved; zero disables the aging
+    pub priority_aging: Duration,
+    /// Maximum size of the inspect payloads sent to the server manager (in bytes)
+    pub max_payload_size: usize,
+    /// Maximum size of the body of the HTTP inspect requests (in bytes); larger bodies are
+    /// rejected with 413 before they are read
+    pub max_http_body_bytes: usize,
+    /// Maximum number of reports of an inspect response; zero disables the limit
+    pub max_reports: usize,
+    /// Maximum total size of the report payloads of an inspect response (in bytes); zero
+    /// disables the limit
+    pub max_total_report_bytes: usize,
+    /// Maximum size of the gRPC responses of the server manager (in bytes); larger responses are
+    /// rejected before they are decoded
+    pub max_response_bytes: usize,
+    /// Decode the payloads of the machine exceptions as UTF-8 error messages, when they are valid
+    /// UTF-8
+    pub decode_exception_messages: bool,
+    /// Timeout for each inspect request sent to the server manager; zero disables the timeout
+    pub request_timeout: Duration,
+    /// Timeout for establishing a connection to the server manager; zero disables the timeout
+    pub connect_timeout: Duration,
+    /// Time after which an inspect is logged as slow, at most once every few seconds; zero
+    /// disables the warning
+    pub slow_inspect_threshold: Duration,
+    /// Time a pooled server manager connection may stay unused before it is dropped, so the next
+    /// request connects again instead of using a connection an intermediary may have closed; zero
+    /// disables it
+    pub idle_timeout: Duration,
+    /// Interval of the HTTP/2 keepalive pings sent over the server manager connections, including
+    /// the idle ones; zero disables them
+    pub keepalive_interval: Duration,
+    /// Maximum number of inspect requests processed by the server manager at the same time
+    pub concurrency: usize,
+    /// Maximum number of inspect requests of a session processed at the same time, so one session
+    /// doesn't take the capacity of the others; zero doesn't limit the sessions
+    pub session_concurrency: usize,
+    /// Maximum number of inspect requests processed at the same time for specific sessions, which
+    /// overrides session_concurrency
+    pub session_concurrency_overrides: HashMap<String, usize>,
+    /// Number of connections to the server manager the inspect requests are distributed over
+    pub connection_pool_size: usize,
+    /// Number of times an inspect is retried when the server manager is unreachable
+    pub max_retries: u32,
+    /// Initial interval of the exponential backoff between retries
+    pub initial_backoff: Duration,
+    /// Maximum number of retries of all the inspects in a burst, so an outage doesn't turn into a
+    /// retry storm against the recovering server manager; zero doesn't limit the retries
+    pub retry_budget: u32,
+    /// Number of retries per second returned to the retry budget
+    pub retry_budget_refill: f64,
+    /// Consecutive connection failures that open the circuit breaker; zero disables it
+    pub circuit_breaker_threshold: u32,
+    /// Time the circuit breaker stays open before probing the server manager again
+    pub circuit_breaker_cooldown: Duration,
+    /// Time an unreachable server manager endpoint is avoided while other endpoints are available
+    pub failover_cooldown: Duration,
+    /// Interval between the probes that check whether the server manager is ready
+    pub readiness_interval: Duration,
+    /// Maximum time to wait for the server manager before starting the HTTP server
+    pub readiness_timeout: Duration,
+    /// Check at startup whether the session exists in the server manager
+    pub verify_session: bool,
+    /// Attach the requests with the same client supplied request id to the one in flight instead
+    /// of sending another inspect
+    pub dedup_request_ids: bool,
+    /// Send an inspect before starting the HTTP server, so the connection and the machine are warm
+    /// for the first requests
+    pub warm_up: bool,
+    /// Payload of the warm-up inspect
+    pub warm_up_payload: Vec<u8>,
+    /// Fail at startup when the warm-up inspect fails instead of only logging it
+    pub warm_up_required: bool,
+    /// Time given to the queued inspect requests to finish when shutting down
+    pub shutdown_timeout: Duration,
+    /// Maximum number of inspect responses kept in the cache; zero disables the cache
+    pub cache_size: usize,
+    /// Time an inspect response is kept in the cache
+    pub cache_ttl: Duration,
+    /// Time the reports of a paged inspect are kept to be read in pages
+    pub reports_ttl: Duration,
+    /// File where the inspects sent to the server manager are recorded as JSON lines; recording is
+    /// disabled when it is not set
+    pub record_path: Option<String>,
+    /// Identifier of this node sent to the server manager in the x-node-id metadata, so its
+    /// requests can be told apart in the server manager logs
+    pub node_id: Option<String>,
+    /// Rate limit of the inspect requests of each client; rate limiting is disabled when it is not
+    /// set
+    pub rate_limit_config: Option<RateLimitConfig>,
+    /// TLS settings for the server manager connection; plaintext is used when it is not set
+    pub tls_config: Option<TlsConfig>,
+    /// Compression of the gRPC messages exchanged with the server manager
+    pub compression: GrpcCompression,
+    /// Encoding of the payloads in the path of the GET inspect requests
+    pub get_payload_encoding: GetPayloadEncoding,
+    /// Token of the admin endpoints, which are disabled when it is not set
+    pub admin_token: Option<AdminToken>,
     pub healthcheck_port: u16,
+    /// Where the value of each setting came from, by the id of its command-line argument
+    pub sources: ConfigSources,
+}
+
+/// Compression of the gRPC messages exchanged with the server manager.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum GrpcCompression {
+    #[default]
+    None,
+    /// Compress the requests with gzip and accept gzip compressed responses; the server manager
+    /// must support it
+    Gzip,
+}
+
+/// Encoding of the payloads in the path of the GET inspect requests, after the percent-decoding.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum GetPayloadEncoding {
+    /// Send the bytes of the path and query as the payload
+    #[default]
+    Utf8,
+    /// Decode the path as hex, with or without the 0x prefix; a query string is rejected
+    Hex,
+}
+
+#[derive(Clone)]
+pub struct TlsConfig {
+    /// Path to the PEM encoded CA certificate used to verify the server manager
+    pub ca_cert_path: String,
+    /// Paths to the PEM encoded client certificate and key used for mTLS
+    pub client_identity: Option<(String, String)>,
+    /// Domain name used to verify the server manager certificate instead of the address host
+    pub domain_name: Option<String>,
+}
+
+impl Debug for TlsConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsConfig")
+            .field("ca_cert_path", &self.ca_cert_path)
+            .field(
+                "client_identity",
+                &self
+                    .client_identity
+                    .as_ref()
+                    .map(|(cert_path, _)| (cert_path, Redacted)),
+            )
+            .field("domain_name", &self.domain_name)
+            .finish()
+    }
+}
+
+/// Token the admin requests must send in the Authorization header as a bearer token.
+/// Its Debug doesn't show it, so it is not logged with the config.
+#[derive(Clone)]
+pub struct AdminToken(String);
+
+impl AdminToken {
+    pub fn new(token: impl Into<String>) -> Self {
+        Self(token.into())
+    }
+
+    /// Compare the token in constant time, so the time of a rejected request doesn't reveal how
+    /// much of the token it guessed.
+    pub fn matches(&self, token: &str) -> bool {
+        let (expected, token) = (self.0.as_bytes(), token.as_bytes());
+        expected.len() == token.len()
+            && expected
+                .iter()
+                .zip(token)
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+impl Debug for AdminToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("AdminToken").field(&Redacted).finish()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    /// Inspect requests per second allowed for each client
+    pub rate: f64,
+    /// Inspect requests a client can send at once
+    pub burst: u32,
+    /// Header that identifies the client; the client IP is used when it is not set
+    pub header: Option<String>,
 }
 
 #[derive(Parser)]
@@ -46,7 +291,9 @@ pub struct CLIConfig {
     #[arg(long, env)]
     inspect_server_address: Option<String>,
 
-    /// Server manager gRPC address
+    /// Server manager gRPC address; http:// is assumed when there is no scheme, or https:// when
+    /// TLS is enabled. Use unix:/path/to/socket to connect over a Unix domain socket.
+    /// Separate the addresses of several replicas with commas to fail over between them
     #[arg(long, env)]
     server_manager_address: Option<String>,
 
@@ -54,10 +301,209 @@ pub struct CLIConfig {
     #[arg(long, env)]
     session_id: Option<String>,
 
-    /// Queue size for concurrent inspect requests
+    /// Queue size for concurrent inspect requests; each queued request holds its whole payload
     #[arg(long, env)]
     queue_size: Option<usize>,
 
+    /// Fraction of the queue capacity above which inspect requests are rejected with a growing
+    /// probability; 0 disables it
+    #[arg(long, env)]
+    inspect_load_shedding_threshold: Option<f64>,
+
+    /// Time to wait for space in the inspect queue when it is full (in millis); 0 fails immediately
+    #[arg(long, env)]
+    inspect_enqueue_timeout: Option<u64>,
+
+    /// Time after which a queued inspect is promoted to the next priority (in millis); 1000 by
+    /// default, 0 disables the aging
+    #[arg(long, env)]
+    inspect_priority_aging: Option<u64>,
+
+    /// Maximum size of inspect payloads (in bytes); defaults to the machine RX buffer limit
+    #[arg(long, env)]
+    inspect_max_payload_size: Option<usize>,
+
+    /// Maximum size of the body of HTTP inspect requests (in bytes); defaults to the maximum
+    /// payload size
+    #[arg(long, env)]
+    inspect_max_http_body_bytes: Option<usize>,
+
+    /// Maximum number of reports of an inspect response; 0 disables it
+    #[arg(long, env)]
+    inspect_max_reports: Option<usize>,
+
+    /// Maximum total size of the reports of an inspect response (in bytes); 0 disables it
+    #[arg(long, env)]
+    inspect_max_total_report_bytes: Option<usize>,
+
+    /// Maximum size of the gRPC responses of the server manager (in bytes)
+    #[arg(long, env)]
+    inspect_max_response_bytes: Option<usize>,
+
+    /// Decode the machine exception payloads as UTF-8 error messages; enabled by default
+    #[arg(long, env)]
+    inspect_decode_exception_messages: Option<bool>,
+
+    /// Timeout for inspect requests sent to the server manager (in millis); 0 disables it
+    #[arg(long, env)]
+    inspect_request_timeout: Option<u64>,
+
+    /// Timeout for connecting to the server manager (in millis); 0 disables it
+    #[arg(long, env)]
+    inspect_connect_timeout: Option<u64>,
+
+    /// Time after which an inspect is logged as slow (in millis); 0 disables it
+    #[arg(long, env)]
+    inspect_slow_threshold: Option<u64>,
+
+    /// Time a server manager connection may stay unused before being dropped (in millis); 0
+    /// disables it
+    #[arg(long, env)]
+    inspect_idle_timeout: Option<u64>,
+
+    /// Interval of the keepalive pings sent to the server manager (in millis); 0 disables them
+    #[arg(long, env)]
+    inspect_keepalive_interval: Option<u64>,
+
+    /// Maximum number of inspect requests sent to the server manager concurrently
+    #[arg(long, env)]
+    inspect_concurrency: Option<usize>,
+
+    /// Maximum number of inspect requests of each session sent concurrently; 0 doesn't limit the
+    /// sessions
+    #[arg(long, env)]
+    inspect_session_concurrency: Option<usize>,
+
+    /// Maximum number of inspect requests of specific sessions sent concurrently, as a
+    /// comma-separated list of session=limit
+    #[arg(long, env, value_delimiter = ',', value_parser = parse_session_limit)]
+    inspect_session_concurrency_overrides: Vec<(String, usize)>,
+
+    /// Number of connections to the server manager
+    #[arg(long, env)]
+    inspect_connection_pool_size: Option<usize>,
+
+    /// Number of times an inspect is retried when the server manager is unreachable
+    #[arg(long, env)]
+    inspect_max_retries: Option<u32>,
+
+    /// Initial backoff between inspect retries (in millis)
+    #[arg(long, env)]
+    inspect_initial_backoff: Option<u64>,
+
+    /// Maximum number of retries of all the inspects in a burst; 0 doesn't limit the retries
+    #[arg(long, env)]
+    inspect_retry_budget: Option<u32>,
+
+    /// Number of retries per second returned to the retry budget
+    #[arg(long, env)]
+    inspect_retry_budget_refill: Option<f64>,
+
+    /// Consecutive connection failures that open the circuit breaker; 0 disables it
+    #[arg(long, env)]
+    inspect_circuit_breaker_threshold: Option<u32>,
+
+    /// Time the circuit breaker stays open before probing the server manager (in millis)
+    #[arg(long, env)]
+    inspect_circuit_breaker_cooldown: Option<u64>,
+
+    /// Time an unreachable server manager endpoint is avoided in favor of the others (in millis)
+    #[arg(long, env)]
+    inspect_failover_cooldown: Option<u64>,
+
+    /// Interval between the server manager readiness probes at startup (in millis)
+    #[arg(long, env)]
+    inspect_readiness_interval: Option<u64>,
+
+    /// Maximum time to wait for the server manager at startup (in millis)
+    #[arg(long, env)]
+    inspect_readiness_timeout: Option<u64>,
+
+    /// Check at startup whether the session exists in the server manager; enabled by default
+    #[arg(long, env)]
+    inspect_verify_session: Option<bool>,
+
+    /// Attach repeated request ids to the inspect in flight instead of sending another one
+    #[arg(long, env)]
+    inspect_dedup_request_ids: Option<bool>,
+
+    /// Send an inspect before starting the HTTP server to warm up the server manager
+    #[arg(long, env)]
+    inspect_warm_up: Option<bool>,
+
+    /// Payload of the warm-up inspect; empty by default
+    #[arg(long, env)]
+    inspect_warm_up_payload: Option<String>,
+
+    /// Fail at startup when the warm-up inspect fails
+    #[arg(long, env)]
+    inspect_warm_up_required: Option<bool>,
+
+    /// Time given to the queued inspect requests to finish when shutting down (in millis)
+    #[arg(long, env)]
+    inspect_shutdown_timeout: Option<u64>,
+
+    /// Maximum number of inspect responses kept in the cache; 0 disables it
+    #[arg(long, env)]
+    inspect_cache_size: Option<usize>,
+
+    /// Time an inspect response is kept in the cache (in millis)
+    #[arg(long, env)]
+    inspect_cache_ttl: Option<u64>,
+
+    /// Time the reports of a paged inspect are kept to be read in pages (in millis)
+    #[arg(long, env)]
+    inspect_reports_ttl: Option<u64>,
+
+    /// File where the inspects sent to the server manager are recorded as JSON lines
+    #[arg(long, env)]
+    inspect_record_path: Option<String>,
+
+    /// Identifier of this node sent to the server manager with each inspect
+    #[arg(long, env)]
+    inspect_node_id: Option<String>,
+
+    /// Inspect requests per second allowed for each client; 0 disables rate limiting
+    #[arg(long, env)]
+    inspect_rate_limit: Option<f64>,
+
+    /// Inspect requests a client can send at once before being rate limited
+    #[arg(long, env)]
+    inspect_rate_limit_burst: Option<u32>,
+
+    /// Header that identifies the client for rate limiting; the client IP is used when not set
+    #[arg(long, env)]
+    inspect_rate_limit_header: Option<String>,
+
+    /// Path to the CA certificate of the server manager; enables TLS when set
+    #[arg(long, env)]
+    inspect_tls_ca_cert: Option<String>,
+
+    /// Path to the client certificate used for mTLS with the server manager
+    #[arg(long, env, requires = "inspect_tls_client_key")]
+    inspect_tls_client_cert: Option<String>,
+
+    /// Path to the client key used for mTLS with the server manager
+    #[arg(long, env, requires = "inspect_tls_client_cert")]
+    inspect_tls_client_key: Option<String>,
+
+    /// Domain name used to verify the server manager certificate
+    #[arg(long, env)]
+    inspect_tls_domain_name: Option<String>,
+
+    /// Compression of the gRPC messages exchanged with the server manager; none by default
+    #[arg(long, env, value_enum)]
+    inspect_grpc_compression: Option<GrpcCompression>,
+
+    /// Encoding of the payloads in the path of GET inspect requests; utf8 by default
+    #[arg(long, env, value_enum)]
+    inspect_get_payload_encoding: Option<GetPayloadEncoding>,
+
+    /// Bearer token of the admin endpoints, such as POST /admin/cache/flush; they are disabled
+    /// when it is not set or empty
+    #[arg(long, env)]
+    inspect_admin_token: Option<String>,
+
     /// Path to the config file
     #[arg(long, env)]
     pub config_path: Option<String>,
@@ -69,40 +515,249 @@ pub struct CLIConfig {
         default_value_t = 8080
     )]
     pub healthcheck_port: u16,
+
+    #[arg(skip)]
+    sources: ConfigSources,
 }
 
-impl From<CLIConfig> for InspectServerConfig {
+impl CLIConfig {
+    /// Parse the command line and the environment, keeping where each setting came from.
+    pub fn parse_with_sources() -> Self {
+        Self::try_parse_with_sources_from(std::env::args_os())
+            .unwrap_or_else(|e| e.exit())
+    }
+
+    pub fn try_parse_with_sources_from<I, T>(
+        args: I,
+    ) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let matches = Self::command().try_get_matches_from(args)?;
+        let mut cli_config = Self::from_arg_matches(&matches)?;
+        cli_config.sources = ConfigSources::from_matches(&matches);
+        Ok(cli_config)
+    }
+}
+
+impl From<CLIConfig> for InspectServerConfigBuilder {
     fn from(cli_config: CLIConfig) -> Self {
-        let file_config: FileConfig = load_config_file(cli_config.config_path)
+        let file_table: toml::Table = load_config_file(cli_config.config_path)
+            .expect("couldn't read config file");
+        let sources = cli_config
+            .sources
+            .with_file(file_table.keys().map(String::as_str));
+        let file_config: FileConfig = toml::Value::Table(file_table)
+            .try_into()
+            .context(ParseSnafu)
             .expect("couldn't read config file");
 
-        let inspect_server_address: String = cli_config
-            .inspect_server_address
-            .or(file_config.inspect_server_address)
-            .expect("couldn't retrieve inspect server address");
+        let rate = cli_config
+            .inspect_rate_limit
+            .or(file_config.inspect_rate_limit)
+            .unwrap_or(0.0);
+        let rate_limit_config = (rate > 0.0).then(|| RateLimitConfig {
+            rate,
+            burst: cli_config
+                .inspect_rate_limit_burst
+                .or(file_config.inspect_rate_limit_burst)
+                .unwrap_or(rate.ceil() as u32),
+            header: cli_config
+                .inspect_rate_limit_header
+                .or(file_config.inspect_rate_limit_header),
+        });
 
-        let server_manager_address: String = cli_config
-            .server_manager_address
-            .or(file_config.server_manager_address)
-            .expect("couldn't retrieve server manager address");
+        let domain_name = cli_config
+            .inspect_tls_domain_name
+            .or(file_config.inspect_tls_domain_name);
 
-        let session_id: String = cli_config
-            .session_id
-            .or(file_config.session_id)
-            .expect("couldn't retrieve session id");
+        let tls_config = cli_config
+            .inspect_tls_ca_cert
+            .or(file_config.inspect_tls_ca_cert)
+            .map(|ca_cert_path| TlsConfig {
+                ca_cert_path,
+                client_identity: None,
+                domain_name,
+            });
 
-        let queue_size: usize = cli_config
-            .queue_size
-            .or(file_config.queue_size)
-            .unwrap_or(100);
+        let millis = |value: Option<u64>| value.map(Duration::from_millis);
 
         Self {
-            log_config: cli_config.log_config.into(),
-            inspect_server_address,
-            server_manager_address,
-            session_id,
-            queue_size,
-            healthcheck_port: cli_config.healthcheck_port,
+            log_config: Some(cli_config.log_config.into()),
+            inspect_server_address: cli_config
+                .inspect_server_address
+                .or(file_config.inspect_server_address),
+            server_manager_address: cli_config
+                .server_manager_address
+                .or(file_config.server_manager_address),
+            session_id: cli_config.session_id.or(file_config.session_id),
+            queue_size: cli_config.queue_size.or(file_config.queue_size),
+            load_shedding_threshold: cli_config
+                .inspect_load_shedding_threshold
+                .or(file_config.inspect_load_shedding_threshold),
+            enqueue_timeout: millis(
+                cli_config
+                    .inspect_enqueue_timeout
+                    .or(file_config.inspect_enqueue_timeout),
+            ),
+            priority_aging: millis(
+                cli_config
+                    .inspect_priority_aging
+                    .or(file_config.inspect_priority_aging),
+            ),
+            max_payload_size: cli_config
+                .inspect_max_payload_size
+                .or(file_config.inspect_max_payload_size),
+            max_http_body_bytes: cli_config
+                .inspect_max_http_body_bytes
+                .or(file_config.inspect_max_http_body_bytes),
+            max_reports: cli_config
+                .inspect_max_reports
+                .or(file_config.inspect_max_reports),
+            max_total_report_bytes: cli_config
+                .inspect_max_total_report_bytes
+                .or(file_config.inspect_max_total_report_bytes),
+            max_response_bytes: cli_config
+                .inspect_max_response_bytes
+                .or(file_config.inspect_max_response_bytes),
+            decode_exception_messages: cli_config
+                .inspect_decode_exception_messages
+                .or(file_config.inspect_decode_exception_messages),
+            request_timeout: millis(
+                cli_config
+                    .inspect_request_timeout
+                    .or(file_config.inspect_request_timeout),
+            ),
+            connect_timeout: millis(
+                cli_config
+                    .inspect_connect_timeout
+                    .or(file_config.inspect_connect_timeout),
+            ),
+            slow_inspect_threshold: millis(
+                cli_config
+                    .inspect_slow_threshold
+                    .or(file_config.inspect_slow_threshold),
+            ),
+            idle_timeout: millis(
+                cli_config
+                    .inspect_idle_timeout
+                    .or(file_config.inspect_idle_timeout),
+            ),
+            keepalive_interval: millis(
+                cli_config
+                    .inspect_keepalive_interval
+                    .or(file_config.inspect_keepalive_interval),
+            ),
+            concurrency: cli_config
+                .inspect_concurrency
+                .or(file_config.inspect_concurrency),
+            session_concurrency: cli_config
+                .inspect_session_concurrency
+                .or(file_config.inspect_session_concurrency),
+            session_concurrency_overrides: match cli_config
+                .inspect_session_concurrency_overrides
+            {
+                overrides if !overrides.is_empty() => {
+                    Some(overrides.into_iter().collect())
+                }
+                _ => file_config.inspect_session_concurrency_overrides,
+            },
+            connection_pool_size: cli_config
+                .inspect_connection_pool_size
+                .or(file_config.inspect_connection_pool_size),
+            max_retries: cli_config
+                .inspect_max_retries
+                .or(file_config.inspect_max_retries),
+            initial_backoff: millis(
+                cli_config
+                    .inspect_initial_backoff
+                    .or(file_config.inspect_initial_backoff),
+            ),
+            retry_budget: cli_config
+                .inspect_retry_budget
+                .or(file_config.inspect_retry_budget),
+            retry_budget_refill: cli_config
+                .inspect_retry_budget_refill
+                .or(file_config.inspect_retry_budget_refill),
+            circuit_breaker_threshold: cli_config
+                .inspect_circuit_breaker_threshold
+                .or(file_config.inspect_circuit_breaker_threshold),
+            circuit_breaker_cooldown: millis(
+                cli_config
+                    .inspect_circuit_breaker_cooldown
+                    .or(file_config.inspect_circuit_breaker_cooldown),
+            ),
+            failover_cooldown: millis(
+                cli_config
+                    .inspect_failover_cooldown
+                    .or(file_config.inspect_failover_cooldown),
+            ),
+            readiness_interval: millis(
+                cli_config
+                    .inspect_readiness_interval
+                    .or(file_config.inspect_readiness_interval),
+            ),
+            readiness_timeout: millis(
+                cli_config
+                    .inspect_readiness_timeout
+                    .or(file_config.inspect_readiness_timeout),
+            ),
+            verify_session: cli_config
+                .inspect_verify_session
+                .or(file_config.inspect_verify_session),
+            dedup_request_ids: cli_config
+                .inspect_dedup_request_ids
+                .or(file_config.inspect_dedup_request_ids),
+            warm_up: cli_config.inspect_warm_up.or(file_config.inspect_warm_up),
+            warm_up_payload: cli_config
+                .inspect_warm_up_payload
+                .or(file_config.inspect_warm_up_payload)
+                .map(String::into_bytes),
+            warm_up_required: cli_config
+                .inspect_warm_up_required
+                .or(file_config.inspect_warm_up_required),
+            shutdown_timeout: millis(
+                cli_config
+                    .inspect_shutdown_timeout
+                    .or(file_config.inspect_shutdown_timeout),
+            ),
+            cache_size: cli_config
+                .inspect_cache_size
+                .or(file_config.inspect_cache_size),
+            cache_ttl: millis(
+                cli_config
+                    .inspect_cache_ttl
+                    .or(file_config.inspect_cache_ttl),
+            ),
+            reports_ttl: millis(
+                cli_config
+                    .inspect_reports_ttl
+                    .or(file_config.inspect_reports_ttl),
+            ),
+            record_path: cli_config
+                .inspect_record_path
+                .or(file_config.inspect_record_path),
+            node_id: cli_config.inspect_node_id.or(file_config.inspect_node_id),
+            rate_limit_config,
+            tls_config,
+            tls_client_cert: cli_config
+                .inspect_tls_client_cert
+                .or(file_config.inspect_tls_client_cert),
+            tls_client_key: cli_config
+                .inspect_tls_client_key
+                .or(file_config.inspect_tls_client_key),
+            compression: cli_config
+                .inspect_grpc_compression
+                .or(file_config.inspect_grpc_compression),
+            get_payload_encoding: cli_config
+                .inspect_get_payload_encoding
+                .or(file_config.inspect_get_payload_encoding),
+            admin_token: cli_config
+                .inspect_admin_token
+                .or(file_config.inspect_admin_token),
+            healthcheck_port: Some(cli_config.healthcheck_port),
+            sources,
         }
     }
 }
@@ -113,6 +768,549 @@ struct FileConfig {
     server_manager_address: Option<String>,
     session_id: Option<String>,
     queue_size: Option<usize>,
+    inspect_load_shedding_threshold: Option<f64>,
+    inspect_enqueue_timeout: Option<u64>,
+    inspect_priority_aging: Option<u64>,
+    inspect_max_payload_size: Option<usize>,
+    inspect_max_http_body_bytes: Option<usize>,
+    inspect_max_reports: Option<usize>,
+    inspect_max_total_report_bytes: Option<usize>,
+    inspect_max_response_bytes: Option<usize>,
+    inspect_decode_exception_messages: Option<bool>,
+    inspect_request_timeout: Option<u64>,
+    inspect_connect_timeout: Option<u64>,
+    inspect_slow_threshold: Option<u64>,
+    inspect_idle_timeout: Option<u64>,
+    inspect_keepalive_interval: Option<u64>,
+    inspect_concurrency: Option<usize>,
+    inspect_session_concurrency: Option<usize>,
+    inspect_session_concurrency_overrides: Option<HashMap<String, usize>>,
+    inspect_connection_pool_size: Option<usize>,
+    inspect_max_retries: Option<u32>,
+    inspect_initial_backoff: Option<u64>,
+    inspect_retry_budget: Option<u32>,
+    inspect_retry_budget_refill: Option<f64>,
+    inspect_circuit_breaker_threshold: Option<u32>,
+    inspect_circuit_breaker_cooldown: Option<u64>,
+    inspect_failover_cooldown: Option<u64>,
+    inspect_readiness_interval: Option<u64>,
+    inspect_readiness_timeout: Option<u64>,
+    inspect_verify_session: Option<bool>,
+    inspect_dedup_request_ids: Option<bool>,
+    inspect_warm_up: Option<bool>,
+    inspect_warm_up_payload: Option<String>,
+    inspect_warm_up_required: Option<bool>,
+    inspect_shutdown_timeout: Option<u64>,
+    inspect_cache_size: Option<usize>,
+    inspect_cache_ttl: Option<u64>,
+    inspect_reports_ttl: Option<u64>,
+    inspect_record_path: Option<String>,
+    inspect_node_id: Option<String>,
+    inspect_rate_limit: Option<f64>,
+    inspect_rate_limit_burst: Option<u32>,
+    inspect_rate_limit_header: Option<String>,
+    inspect_tls_ca_cert: Option<String>,
+    inspect_tls_client_cert: Option<String>,
+    inspect_tls_client_key: Option<String>,
+    inspect_tls_domain_name: Option<String>,
+    inspect_grpc_compression: Option<GrpcCompression>,
+    inspect_get_payload_encoding: Option<GetPayloadEncoding>,
+    inspect_admin_token: Option<String>,
+}
+
+/// Builder of the inspect server config, for the services that embed the inspect client without
+/// parsing the CLI.
+/// The required fields are the server addresses and the session id; the others default to the
+/// same values as the CLI.
+#[derive(Debug, Clone, Default)]
+pub struct InspectServerConfigBuilder {
+    log_config: Option<LogConfig>,
+    inspect_server_address: Option<String>,
+    server_manager_address: Option<String>,
+    session_id: Option<String>,
+    queue_size: Option<usize>,
+    load_shedding_threshold: Option<f64>,
+    enqueue_timeout: Option<Duration>,
+    priority_aging: Option<Duration>,
+    max_payload_size: Option<usize>,
+    max_http_body_bytes: Option<usize>,
+    max_reports: Option<usize>,
+    max_total_report_bytes: Option<usize>,
+    max_response_bytes: Option<usize>,
+    decode_exception_messages: Option<bool>,
+    request_timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    slow_inspect_threshold: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    keepalive_interval: Option<Duration>,
+    concurrency: Option<usize>,
+    session_concurrency: Option<usize>,
+    session_concurrency_overrides: Option<HashMap<String, usize>>,
+    connection_pool_size: Option<usize>,
+    max_retries: Option<u32>,
+    initial_backoff: Option<Duration>,
+    retry_budget: Option<u32>,
+    retry_budget_refill: Option<f64>,
+    circuit_breaker_threshold: Option<u32>,
+    circuit_breaker_cooldown: Option<Duration>,
+    failover_cooldown: Option<Duration>,
+    readiness_interval: Option<Duration>,
+    readiness_timeout: Option<Duration>,
+    verify_session: Option<bool>,
+    dedup_request_ids: Option<bool>,
+    warm_up: Option<bool>,
+    warm_up_payload: Option<Vec<u8>>,
+    warm_up_required: Option<bool>,
+    shutdown_timeout: Option<Duration>,
+    cache_size: Option<usize>,
+    cache_ttl: Option<Duration>,
+    reports_ttl: Option<Duration>,
+    record_path: Option<String>,
+    node_id: Option<String>,
+    rate_limit_config: Option<RateLimitConfig>,
+    tls_config: Option<TlsConfig>,
+    /// Client identity given as separate settings, which is checked when building
+    tls_client_cert: Option<String>,
+    tls_client_key: Option<String>,
+    compression: Option<GrpcCompression>,
+    get_payload_encoding: Option<GetPayloadEncoding>,
+    admin_token: Option<String>,
+    healthcheck_port: Option<u16>,
+    sources: ConfigSources,
+}
+
+macro_rules! setters {
+    ($($name:ident: $ty:ty),* $(,)?) => {
+        $(
+            pub fn $name(mut self, $name: $ty) -> Self {
+                self.$name = Some($name);
+                self
+            }
+        )*
+    };
+}
+
+impl InspectServerConfigBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    setters! {
+        log_config: LogConfig,
+        queue_size: usize,
+        load_shedding_threshold: f64,
+        enqueue_timeout: Duration,
+        priority_aging: Duration,
+        max_payload_size: usize,
+        max_http_body_bytes: usize,
+        max_reports: usize,
+        max_total_report_bytes: usize,
+        max_response_bytes: usize,
+        decode_exception_messages: bool,
+        request_timeout: Duration,
+        connect_timeout: Duration,
+        slow_inspect_threshold: Duration,
+        idle_timeout: Duration,
+        keepalive_interval: Duration,
+        concurrency: usize,
+        session_concurrency: usize,
+        session_concurrency_overrides: HashMap<String, usize>,
+        connection_pool_size: usize,
+        max_retries: u32,
+        initial_backoff: Duration,
+        retry_budget: u32,
+        retry_budget_refill: f64,
+        circuit_breaker_threshold: u32,
+        circuit_breaker_cooldown: Duration,
+        failover_cooldown: Duration,
+        readiness_interval: Duration,
+        readiness_timeout: Duration,
+        verify_session: bool,
+        dedup_request_ids: bool,
+        warm_up: bool,
+        warm_up_payload: Vec<u8>,
+        warm_up_required: bool,
+        shutdown_timeout: Duration,
+        cache_size: usize,
+        cache_ttl: Duration,
+        reports_ttl: Duration,
+        record_path: String,
+        node_id: String,
+        rate_limit_config: RateLimitConfig,
+        tls_config: TlsConfig,
+        tls_client_cert: String,
+        tls_client_key: String,
+        compression: GrpcCompression,
+        get_payload_encoding: GetPayloadEncoding,
+        admin_token: String,
+        healthcheck_port: u16,
+    }
+
+    pub fn inspect_server_address(
+        mut self,
+        address: impl Into<String>,
+    ) -> Self {
+        self.inspect_server_address = Some(address.into());
+        self
+    }
+
+    pub fn server_manager_address(
+        mut self,
+        address: impl Into<String>,
+    ) -> Self {
+        self.server_manager_address = Some(address.into());
+        self
+    }
+
+    pub fn session_id(mut self, session_id: impl Into<String>) -> Self {
+        self.session_id = Some(session_id.into());
+        self
+    }
+
+    /// Build the config, failing when a required field is missing or a value is invalid.
+    pub fn build(self) -> Result<InspectServerConfig, ConfigError> {
+        let max_payload_size = self
+            .max_payload_size
+            .unwrap_or(CARTESI_MACHINE_RX_BUFFER_LIMIT);
+        let tls_config = match (self.tls_client_cert, self.tls_client_key) {
+            (Some(cert), Some(key)) => {
+                self.tls_config.map(|tls_config| TlsConfig {
+                    client_identity: Some((cert, key)),
+                    ..tls_config
+                })
+            }
+            (None, None) => self.tls_config,
+            _ => return IncompleteTlsClientIdentitySnafu.fail(),
+        };
+        let config = InspectServerConfig {
+            log_config: self.log_config.unwrap_or_default(),
+            inspect_server_address: self.inspect_server_address.context(
+                MissingValueSnafu {
+                    name: "inspect_server_address",
+                },
+            )?,
+            server_manager_address: self.server_manager_address.context(
+                MissingValueSnafu {
+                    name: "server_manager_address",
+                },
+            )?,
+            session_id: self
+                .session_id
+                .context(MissingValueSnafu { name: "session_id" })?,
+            queue_size: self.queue_size.unwrap_or(100),
+            load_shedding_threshold: self
+                .load_shedding_threshold
+                .unwrap_or(0.0),
+            enqueue_timeout: self.enqueue_timeout.unwrap_or(Duration::ZERO),
+            priority_aging: self
+                .priority_aging
+                .unwrap_or(DEFAULT_PRIORITY_AGING),
+            max_payload_size,
+            max_http_body_bytes: self
+                .max_http_body_bytes
+                .unwrap_or(max_payload_size),
+            max_reports: self.max_reports.unwrap_or(0),
+            max_total_report_bytes: self.max_total_report_bytes.unwrap_or(0),
+            max_response_bytes: self
+                .max_response_bytes
+                .unwrap_or(DEFAULT_MAX_RESPONSE_BYTES),
+            decode_exception_messages: self
+                .decode_exception_messages
+                .unwrap_or(true),
+            request_timeout: self.request_timeout.unwrap_or(Duration::ZERO),
+            connect_timeout: self.connect_timeout.unwrap_or(Duration::ZERO),
+            slow_inspect_threshold: self
+                .slow_inspect_threshold
+                .unwrap_or(Duration::ZERO),
+            idle_timeout: self.idle_timeout.unwrap_or(Duration::ZERO),
+            keepalive_interval: self
+                .keepalive_interval
+                .unwrap_or(Duration::ZERO),
+            concurrency: self.concurrency.unwrap_or(10),
+            session_concurrency: self.session_concurrency.unwrap_or(0),
+            session_concurrency_overrides: self
+                .session_concurrency_overrides
+                .unwrap_or_default(),
+            connection_pool_size: self.connection_pool_size.unwrap_or(1),
+            max_retries: self.max_retries.unwrap_or(3),
+            initial_backoff: self
+                .initial_backoff
+                .unwrap_or(Duration::from_millis(100)),
+            retry_budget: self.retry_budget.unwrap_or(0),
+            retry_budget_refill: self.retry_budget_refill.unwrap_or(10.0),
+            circuit_breaker_threshold: self
+                .circuit_breaker_threshold
+                .unwrap_or(5),
+            circuit_breaker_cooldown: self
+                .circuit_breaker_cooldown
+                .unwrap_or(Duration::from_secs(5)),
+            failover_cooldown: self
+                .failover_cooldown
+                .unwrap_or(Duration::from_secs(5)),
+            readiness_interval: self
+                .readiness_interval
+                .unwrap_or(Duration::from_secs(1)),
+            readiness_timeout: self
+                .readiness_timeout
+                .unwrap_or(Duration::from_secs(30)),
+            verify_session: self.verify_session.unwrap_or(true),
+            dedup_request_ids: self.dedup_request_ids.unwrap_or(false),
+            warm_up: self.warm_up.unwrap_or(false),
+            warm_up_payload: self.warm_up_payload.unwrap_or_default(),
+            warm_up_required: self.warm_up_required.unwrap_or(false),
+            shutdown_timeout: self
+                .shutdown_timeout
+                .unwrap_or(Duration::from_secs(30)),
+            cache_size: self.cache_size.unwrap_or(0),
+            cache_ttl: self.cache_ttl.unwrap_or(Duration::from_secs(1)),
+            reports_ttl: self.reports_ttl.unwrap_or(Duration::from_secs(60)),
+            record_path: self.record_path,
+            node_id: self.node_id,
+            rate_limit_config: self.rate_limit_config,
+            tls_config,
+            compression: self.compression.unwrap_or_default(),
+            get_payload_encoding: self.get_payload_encoding.unwrap_or_default(),
+            admin_token: self
+                .admin_token
+                .filter(|token| !token.is_empty())
+                .map(AdminToken),
+            healthcheck_port: self.healthcheck_port.unwrap_or(8080),
+            sources: self.sources,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+impl InspectServerConfig {
+    pub fn builder() -> InspectServerConfigBuilder {
+        InspectServerConfigBuilder::new()
+    }
+
+    /// Check the values that would make the inspect server fail at runtime.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        snafu::ensure!(
+            self.queue_size > 0,
+            ZeroValueSnafu { name: "queue_size" }
+        );
+        snafu::ensure!(
+            (0.0..1.0).contains(&self.load_shedding_threshold),
+            InvalidFractionSnafu {
+                name: "inspect_load_shedding_threshold"
+            }
+        );
+        snafu::ensure!(
+            self.max_http_body_bytes > 0,
+            ZeroValueSnafu {
+                name: "inspect_max_http_body_bytes"
+            }
+        );
+        snafu::ensure!(
+            self.concurrency > 0,
+            ZeroValueSnafu {
+                name: "inspect_concurrency"
+            }
+        );
+        snafu::ensure!(
+            self.retry_budget == 0 || self.retry_budget_refill > 0.0,
+            ZeroValueSnafu {
+                name: "inspect_retry_budget_refill"
+            }
+        );
+        snafu::ensure!(
+            self.connection_pool_size > 0,
+            ZeroValueSnafu {
+                name: "inspect_connection_pool_size"
+            }
+        );
+        if let Some(rate_limit) = &self.rate_limit_config {
+            // The rate limiter waits burst / rate seconds for an empty bucket to refill
+            let refill_secs = rate_limit.burst.max(1) as f64 / rate_limit.rate;
+            snafu::ensure!(
+                rate_limit.rate.is_finite()
+                    && rate_limit.rate > 0.0
+                    && Duration::try_from_secs_f64(refill_secs).is_ok(),
+                InvalidRateSnafu {
+                    name: "inspect_rate_limit"
+                }
+            );
+        }
+        for address in self.server_manager_addresses() {
+            tonic::transport::Endpoint::from_shared(
+                self.server_manager_url(address),
+            )
+            .context(InvalidServerManagerAddressSnafu { address })?;
+        }
+        if let Some(node_id) = &self.node_id {
+            snafu::ensure!(
+                node_id.parse::<AsciiMetadataValue>().is_ok(),
+                InvalidNodeIdSnafu { node_id }
+            );
+        }
+        Ok(())
+    }
+
+    /// Log each setting of the effective configuration and where its value came from.
+    /// The admin token and the path of the TLS client key are redacted.
+    pub fn log_effective(&self) {
+        self.for_each_setting(|id, value| {
+            log::log_setting(id, value, self.sources.get(id))
+        });
+    }
+
+    /// Call f with each setting, by the id of its command-line argument.
+    fn for_each_setting(&self, mut f: impl FnMut(&str, &dyn Debug)) {
+        let rate_limit = self.rate_limit_config.as_ref();
+        let tls = self.tls_config.as_ref();
+        let client_identity = tls.and_then(|tls| tls.client_identity.as_ref());
+        let settings: [(&str, &dyn Debug); 56] = [
+            ("log_enable_timestamp", &self.log_config.enable_timestamp),
+            ("log_enable_color", &self.log_config.enable_color),
+            ("log_format", &self.log_config.format),
+            ("inspect_server_address", &self.inspect_server_address),
+            ("server_manager_address", &self.server_manager_address),
+            ("session_id", &self.session_id),
+            ("queue_size", &self.queue_size),
+            (
+                "inspect_load_shedding_threshold",
+                &self.load_shedding_threshold,
+            ),
+            ("inspect_enqueue_timeout", &self.enqueue_timeout),
+            ("inspect_priority_aging", &self.priority_aging),
+            ("inspect_max_payload_size", &self.max_payload_size),
+            ("inspect_max_http_body_bytes", &self.max_http_body_bytes),
+            ("inspect_max_reports", &self.max_reports),
+            (
+                "inspect_max_total_report_bytes",
+                &self.max_total_report_bytes,
+            ),
+            ("inspect_max_response_bytes", &self.max_response_bytes),
+            (
+                "inspect_decode_exception_messages",
+                &self.decode_exception_messages,
+            ),
+            ("inspect_request_timeout", &self.request_timeout),
+            ("inspect_connect_timeout", &self.connect_timeout),
+            ("inspect_slow_threshold", &self.slow_inspect_threshold),
+            ("inspect_idle_timeout", &self.idle_timeout),
+            ("inspect_keepalive_interval", &self.keepalive_interval),
+            ("inspect_concurrency", &self.concurrency),
+            ("inspect_session_concurrency", &self.session_concurrency),
+            (
+                "inspect_session_concurrency_overrides",
+                &self.session_concurrency_overrides,
+            ),
+            ("inspect_connection_pool_size", &self.connection_pool_size),
+            ("inspect_max_retries", &self.max_retries),
+            ("inspect_initial_backoff", &self.initial_backoff),
+            ("inspect_retry_budget", &self.retry_budget),
+            ("inspect_retry_budget_refill", &self.retry_budget_refill),
+            (
+                "inspect_circuit_breaker_threshold",
+                &self.circuit_breaker_threshold,
+            ),
+            (
+                "inspect_circuit_breaker_cooldown",
+                &self.circuit_breaker_cooldown,
+            ),
+            ("inspect_failover_cooldown", &self.failover_cooldown),
+            ("inspect_readiness_interval", &self.readiness_interval),
+            ("inspect_readiness_timeout", &self.readiness_timeout),
+            ("inspect_verify_session", &self.verify_session),
+            ("inspect_dedup_request_ids", &self.dedup_request_ids),
+            ("inspect_warm_up", &self.warm_up),
+            (
+                "inspect_warm_up_payload",
+                &String::from_utf8_lossy(&self.warm_up_payload),
+            ),
+            ("inspect_warm_up_required", &self.warm_up_required),
+            ("inspect_shutdown_timeout", &self.shutdown_timeout),
+            ("inspect_cache_size", &self.cache_size),
+            ("inspect_cache_ttl", &self.cache_ttl),
+            ("inspect_reports_ttl", &self.reports_ttl),
+            ("inspect_record_path", &self.record_path),
+            ("inspect_node_id", &self.node_id),
+            ("inspect_rate_limit", &rate_limit.map(|config| config.rate)),
+            (
+                "inspect_rate_limit_burst",
+                &rate_limit.map(|config| config.burst),
+            ),
+            (
+                "inspect_rate_limit_header",
+                &rate_limit.and_then(|config| config.header.as_ref()),
+            ),
+            ("inspect_tls_ca_cert", &tls.map(|tls| &tls.ca_cert_path)),
+            (
+                "inspect_tls_client_cert",
+                &client_identity.map(|(cert_path, _)| cert_path),
+            ),
+            ("inspect_tls_client_key", &client_identity.map(|_| Redacted)),
+            (
+                "inspect_tls_domain_name",
+                &tls.and_then(|tls| tls.domain_name.as_ref()),
+            ),
+            ("inspect_grpc_compression", &self.compression),
+            ("inspect_get_payload_encoding", &self.get_payload_encoding),
+            ("inspect_admin_token", &self.admin_token),
+            ("healthcheck_port", &self.healthcheck_port),
+        ];
+        for (id, value) in settings {
+            f(id, value);
+        }
+    }
+
+    /// Addresses of the server manager replicas, in order of preference.
+    /// An address without any replica is kept as is, so it is rejected by the validation.
+    pub fn server_manager_addresses(&self) -> Vec<&str> {
+        let addresses: Vec<&str> = self
+            .server_manager_address
+            .split(',')
+            .map(str::trim)
+            .filter(|address| !address.is_empty())
+            .collect();
+        if addresses.is_empty() {
+            return vec![self.server_manager_address.as_str()];
+        }
+        addresses
+    }
+
+    /// URL of a server manager address.
+    /// The scheme of the address is kept when present; otherwise http:// is prepended, or
+    /// https:// when TLS is enabled. The URL of a Unix domain socket address only sets the
+    /// authority of the requests, since the connection goes to the socket path.
+    pub fn server_manager_url(&self, address: &str) -> String {
+        if server_manager_socket_path(address).is_some() {
+            return String::from("http://localhost");
+        }
+        if address.contains("://") {
+            return address.to_string();
+        }
+        let scheme = match self.tls_config {
+            Some(_) => "https",
+            None => "http",
+        };
+        format!("{}://{}", scheme, address)
+    }
+}
+
+/// Path of the Unix domain socket of a server manager address, when it has the unix: scheme, as in
+/// unix:/path/to/socket or unix:///path/to/socket.
+pub fn server_manager_socket_path(address: &str) -> Option<&str> {
+    address
+        .strip_prefix("unix://")
+        .or_else(|| address.strip_prefix("unix:"))
+}
+
+/// Parse a session=limit pair of the session concurrency overrides.
+fn parse_session_limit(value: &str) -> Result<(String, usize), String> {
+    let (session_id, limit) = value
+        .rsplit_once('=')
+        .ok_or_else(|| format!("expected session=limit, got {:?}", value))?;
+    let limit = limit
+        .trim()
+        .parse()
+        .map_err(|e| format!("invalid limit {:?}: {}", limit, e))?;
+    Ok((session_id.trim().to_string(), limit))
 }
 
 fn load_config_file<T: Default + serde::de::DeserializeOwned>(
@@ -130,3 +1328,486 @@ fn load_config_file<T: Default + serde::de::DeserializeOwned>(
         None => Ok(T::default()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_builder(args: &[&str]) -> InspectServerConfigBuilder {
+        let required = [
+            "inspect-server",
+            "--inspect-server-address",
+            "127.0.0.1:5005",
+            "--server-manager-address",
+            "127.0.0.1:5001",
+            "--session-id",
+            "default",
+        ];
+        let args = required.iter().chain(args.iter());
+        CLIConfig::parse_from(args).into()
+    }
+
+    fn parse_config(args: &[&str]) -> InspectServerConfig {
+        parse_builder(args).build().expect("config should be valid")
+    }
+
+    #[test]
+    fn it_accepts_the_default_config() {
+        let config = parse_config(&[]);
+        assert_eq!(config.queue_size, 100);
+        config.validate().expect("default config should be valid");
+    }
+
+    #[test]
+    fn it_rejects_zero_queue_size() {
+        let err = parse_builder(&["--queue-size", "0"])
+            .build()
+            .expect_err("queue size should be invalid");
+        assert_eq!(err.to_string(), "queue_size must be greater than zero");
+    }
+
+    #[test]
+    fn it_rejects_invalid_load_shedding_threshold() {
+        for threshold in ["-0.5", "1", "1.5"] {
+            let arg =
+                format!("--inspect-load-shedding-threshold={}", threshold);
+            let err = parse_builder(&[&arg])
+                .build()
+                .expect_err("threshold should be invalid");
+            assert_eq!(
+                err.to_string(),
+                "inspect_load_shedding_threshold must be at least 0 and less than 1"
+            );
+        }
+        let config =
+            parse_config(&["--inspect-load-shedding-threshold", "0.8"]);
+        assert_eq!(config.load_shedding_threshold, 0.8);
+    }
+
+    #[test]
+    fn it_parses_the_grpc_compression() {
+        assert_eq!(parse_config(&[]).compression, GrpcCompression::None);
+        let config = parse_config(&["--inspect-grpc-compression", "gzip"]);
+        assert_eq!(config.compression, GrpcCompression::Gzip);
+    }
+
+    #[test]
+    fn it_parses_the_get_payload_encoding() {
+        assert_eq!(
+            parse_config(&[]).get_payload_encoding,
+            GetPayloadEncoding::Utf8
+        );
+        let config = parse_config(&["--inspect-get-payload-encoding", "hex"]);
+        assert_eq!(config.get_payload_encoding, GetPayloadEncoding::Hex);
+    }
+
+    #[test]
+    fn it_parses_the_session_concurrency_overrides() {
+        let config = parse_config(&[
+            "--inspect-session-concurrency",
+            "2",
+            "--inspect-session-concurrency-overrides",
+            "first=4, second=0",
+        ]);
+        assert_eq!(config.session_concurrency, 2);
+        assert_eq!(
+            config.session_concurrency_overrides,
+            HashMap::from([
+                (String::from("first"), 4),
+                (String::from("second"), 0),
+            ])
+        );
+        let err = CLIConfig::try_parse_from([
+            "inspect-server",
+            "--inspect-session-concurrency-overrides",
+            "first",
+        ])
+        .map(|_| ())
+        .expect_err("overrides should be invalid");
+        assert!(err.to_string().contains("expected session=limit"));
+    }
+
+    #[test]
+    fn it_defaults_the_http_body_limit_to_the_payload_limit() {
+        let config = parse_config(&[]);
+        assert_eq!(config.max_http_body_bytes, CARTESI_MACHINE_RX_BUFFER_LIMIT);
+        let config = parse_config(&["--inspect-max-payload-size", "100"]);
+        assert_eq!(config.max_http_body_bytes, 100);
+        let config = parse_config(&[
+            "--inspect-max-payload-size",
+            "100",
+            "--inspect-max-http-body-bytes",
+            "200",
+        ]);
+        assert_eq!(config.max_http_body_bytes, 200);
+        let err = parse_builder(&["--inspect-max-http-body-bytes", "0"])
+            .build()
+            .expect_err("body limit should be invalid");
+        assert_eq!(
+            err.to_string(),
+            "inspect_max_http_body_bytes must be greater than zero"
+        );
+    }
+
+    #[test]
+    fn it_rejects_invalid_rate_limits() {
+        for rate in [0.0, -1.0, f64::NAN, f64::INFINITY, 1e-20] {
+            let err = builder()
+                .rate_limit_config(RateLimitConfig {
+                    rate,
+                    burst: 1,
+                    header: None,
+                })
+                .build()
+                .expect_err("rate should be invalid");
+            assert!(
+                matches!(err, ConfigError::InvalidRate { .. }),
+                "rate {} gave {}",
+                rate,
+                err
+            );
+        }
+        let err = parse_builder(&["--inspect-rate-limit", "1e-20"])
+            .build()
+            .expect_err("rate should be invalid");
+        assert!(matches!(err, ConfigError::InvalidRate { .. }));
+        let config = parse_config(&["--inspect-rate-limit", "0.5"]);
+        assert_eq!(config.rate_limit_config.unwrap().burst, 1);
+    }
+
+    #[test]
+    fn it_rejects_zero_retry_budget_refill() {
+        let config = parse_config(&["--inspect-retry-budget-refill", "0"]);
+        assert_eq!(config.retry_budget, 0);
+        let err = parse_builder(&[
+            "--inspect-retry-budget",
+            "10",
+            "--inspect-retry-budget-refill",
+            "0",
+        ])
+        .build()
+        .expect_err("retry budget refill should be invalid");
+        assert_eq!(
+            err.to_string(),
+            "inspect_retry_budget_refill must be greater than zero"
+        );
+    }
+
+    #[test]
+    fn it_rejects_zero_concurrency() {
+        let err = parse_builder(&["--inspect-concurrency", "0"])
+            .build()
+            .expect_err("concurrency should be invalid");
+        assert_eq!(
+            err.to_string(),
+            "inspect_concurrency must be greater than zero"
+        );
+    }
+
+    #[test]
+    fn it_prepends_http_to_address_without_scheme() {
+        let config = parse_config(&[]);
+        assert_eq!(
+            config.server_manager_url("127.0.0.1:5001"),
+            "http://127.0.0.1:5001"
+        );
+    }
+
+    #[test]
+    fn it_prepends_https_to_address_without_scheme_when_tls_is_enabled() {
+        let config = parse_config(&["--inspect-tls-ca-cert", "ca.pem"]);
+        assert_eq!(
+            config.server_manager_url("127.0.0.1:5001"),
+            "https://127.0.0.1:5001"
+        );
+    }
+
+    #[test]
+    fn it_keeps_the_scheme_of_the_address() {
+        let mut config = parse_config(&[]);
+        for address in ["http://localhost:5001", "https://localhost:5001"] {
+            config.server_manager_address = address.to_string();
+            assert_eq!(config.server_manager_url(address), address);
+            config.validate().expect("address should be valid");
+        }
+    }
+
+    #[test]
+    fn it_extracts_the_unix_socket_path() {
+        let mut config = parse_config(&[]);
+        assert_eq!(server_manager_socket_path("127.0.0.1:5001"), None);
+        for address in [
+            "unix:/tmp/server-manager.sock",
+            "unix:///tmp/server-manager.sock",
+        ] {
+            config.server_manager_address = address.to_string();
+            assert_eq!(
+                server_manager_socket_path(address),
+                Some("/tmp/server-manager.sock")
+            );
+            assert_eq!(config.server_manager_url(address), "http://localhost");
+            config.validate().expect("address should be valid");
+        }
+    }
+
+    #[test]
+    fn it_rejects_invalid_server_manager_address() {
+        let mut config = parse_config(&[]);
+        config.server_manager_address = "localhost:50 01".to_string();
+        let err = config.validate().expect_err("address should be invalid");
+        assert_eq!(
+            err.to_string(),
+            "invalid server manager address \"localhost:50 01\""
+        );
+    }
+
+    #[test]
+    fn it_rejects_invalid_node_id() {
+        let config = parse_config(&["--inspect-node-id", "node-1"]);
+        assert_eq!(config.node_id.as_deref(), Some("node-1"));
+        let err = parse_builder(&["--inspect-node-id", "node\n1"])
+            .build()
+            .expect_err("node id should be invalid");
+        assert_eq!(err.to_string(), "invalid node id \"node\\n1\"");
+    }
+
+    #[test]
+    fn it_splits_the_server_manager_addresses() {
+        let mut config = parse_config(&[]);
+        assert_eq!(config.server_manager_addresses(), vec!["127.0.0.1:5001"]);
+        config.server_manager_address =
+            "127.0.0.1:5001, unix:/tmp/server-manager.sock,".to_string();
+        assert_eq!(
+            config.server_manager_addresses(),
+            vec!["127.0.0.1:5001", "unix:/tmp/server-manager.sock"]
+        );
+        config.validate().expect("addresses should be valid");
+        config.server_manager_address =
+            "127.0.0.1:5001,localhost:50 01".to_string();
+        let err = config.validate().expect_err("address should be invalid");
+        assert_eq!(
+            err.to_string(),
+            "invalid server manager address \"localhost:50 01\""
+        );
+    }
+
+    fn builder() -> InspectServerConfigBuilder {
+        InspectServerConfig::builder()
+            .inspect_server_address("127.0.0.1:5005")
+            .server_manager_address("127.0.0.1:5001")
+            .session_id("default")
+    }
+
+    #[test]
+    fn it_builds_with_the_cli_defaults() {
+        let config = builder().build().expect("config should be valid");
+        let cli_config = parse_config(&[]);
+        assert_eq!(config.queue_size, cli_config.queue_size);
+        assert_eq!(config.concurrency, cli_config.concurrency);
+        assert_eq!(config.max_payload_size, cli_config.max_payload_size);
+        assert_eq!(config.readiness_timeout, cli_config.readiness_timeout);
+        assert_eq!(config.shutdown_timeout, cli_config.shutdown_timeout);
+        assert_eq!(config.max_response_bytes, DEFAULT_MAX_RESPONSE_BYTES);
+        assert_eq!(config.max_response_bytes, cli_config.max_response_bytes);
+        assert!(config.tls_config.is_none());
+    }
+
+    #[test]
+    fn it_builds_with_the_given_values() {
+        let config = builder()
+            .queue_size(5)
+            .request_timeout(Duration::from_secs(2))
+            .build()
+            .expect("config should be valid");
+        assert_eq!(config.queue_size, 5);
+        assert_eq!(config.request_timeout, Duration::from_secs(2));
+    }
+
+    #[test]
+    fn it_requires_the_addresses_and_session_id() {
+        let err = InspectServerConfig::builder()
+            .server_manager_address("127.0.0.1:5001")
+            .session_id("default")
+            .build()
+            .expect_err("inspect server address should be required");
+        assert_eq!(err.to_string(), "inspect_server_address is required");
+        let err = InspectServerConfig::builder()
+            .inspect_server_address("127.0.0.1:5005")
+            .session_id("default")
+            .build()
+            .expect_err("server manager address should be required");
+        assert_eq!(err.to_string(), "server_manager_address is required");
+        let err = InspectServerConfig::builder()
+            .inspect_server_address("127.0.0.1:5005")
+            .server_manager_address("127.0.0.1:5001")
+            .build()
+            .expect_err("session id should be required");
+        assert_eq!(err.to_string(), "session_id is required");
+    }
+
+    #[test]
+    fn it_validates_the_built_config() {
+        let err = builder()
+            .concurrency(0)
+            .build()
+            .expect_err("concurrency should be invalid");
+        assert_eq!(
+            err.to_string(),
+            "inspect_concurrency must be greater than zero"
+        );
+    }
+
+    #[test]
+    fn it_keeps_the_source_of_the_settings() {
+        let path = std::env::temp_dir()
+            .join(format!("inspect-server-config-{}.toml", std::process::id()));
+        std::fs::write(&path, "queue_size = 7\ninspect_cache_size = 3\n")
+            .expect("config file should be written");
+        let cli_config = CLIConfig::try_parse_with_sources_from([
+            "inspect-server",
+            "--inspect-server-address",
+            "127.0.0.1:5005",
+            "--server-manager-address",
+            "127.0.0.1:5001",
+            "--session-id",
+            "default",
+            "--queue-size",
+            "5",
+            "--config-path",
+            path.to_str().unwrap(),
+        ])
+        .expect("args should be valid");
+        let config = InspectServerConfigBuilder::from(cli_config)
+            .build()
+            .expect("config should be valid");
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(config.queue_size, 5);
+        assert_eq!(config.cache_size, 3);
+        assert_eq!(
+            config.sources.get("queue_size"),
+            log::ConfigSource::CommandLine
+        );
+        assert_eq!(
+            config.sources.get("inspect_cache_size"),
+            log::ConfigSource::File
+        );
+        assert_eq!(
+            config.sources.get("inspect_concurrency"),
+            log::ConfigSource::Default
+        );
+    }
+
+    #[test]
+    fn it_logs_every_setting() {
+        let config = parse_config(&[]);
+        let mut logged = vec![];
+        config.for_each_setting(|id, _| logged.push(id.to_string()));
+        for arg in CLIConfig::command().get_arguments() {
+            let id = arg.get_id().as_str();
+            if id != "config_path" {
+                assert!(logged.iter().any(|name| name == id), "{}", id);
+            }
+        }
+    }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn it_redacts_the_tls_client_key() {
+        let config = builder()
+            .tls_config(TlsConfig {
+                ca_cert_path: String::from("ca.pem"),
+                client_identity: Some((
+                    String::from("client.pem"),
+                    String::from("secret-client.key"),
+                )),
+                domain_name: None,
+            })
+            .build()
+            .expect("config should be valid");
+        config.log_effective();
+        assert!(logs_contain("inspect_tls_client_key"));
+        assert!(logs_contain("client.pem"));
+        assert!(!logs_contain("secret-client.key"));
+        assert!(!format!("{:?}", config).contains("secret-client.key"));
+    }
+
+    #[test]
+    fn it_builds_the_tls_client_identity() {
+        let config = builder()
+            .tls_config(TlsConfig {
+                ca_cert_path: String::from("ca.pem"),
+                client_identity: None,
+                domain_name: None,
+            })
+            .tls_client_cert(String::from("client.pem"))
+            .tls_client_key(String::from("client.key"))
+            .build()
+            .expect("config should be valid");
+        assert_eq!(
+            config.tls_config.unwrap().client_identity,
+            Some((String::from("client.pem"), String::from("client.key")))
+        );
+    }
+
+    #[test]
+    fn it_rejects_a_tls_client_cert_without_key() {
+        let err = builder()
+            .tls_client_cert(String::from("client.pem"))
+            .build()
+            .expect_err("client identity should be incomplete");
+        assert!(matches!(err, ConfigError::IncompleteTlsClientIdentity));
+        let err = CLIConfig::try_parse_from([
+            "inspect-server",
+            "--inspect-server-address",
+            "127.0.0.1:5005",
+            "--server-manager-address",
+            "127.0.0.1:5001",
+            "--session-id",
+            "default",
+            "--inspect-tls-ca-cert",
+            "ca.pem",
+            "--inspect-tls-client-key",
+            "client.key",
+        ])
+        .map(|_| ())
+        .expect_err("client identity should be incomplete");
+        assert_eq!(err.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+        assert!(err.to_string().contains("--inspect-tls-client-cert"));
+    }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn it_redacts_the_admin_token() {
+        let config = builder()
+            .admin_token(String::from("secret-admin-token"))
+            .build()
+            .expect("config should be valid");
+        assert!(config
+            .admin_token
+            .as_ref()
+            .unwrap()
+            .matches("secret-admin-token"));
+        config.log_effective();
+        assert!(logs_contain("inspect_admin_token"));
+        assert!(!logs_contain("secret-admin-token"));
+        assert!(!format!("{:?}", config).contains("secret-admin-token"));
+    }
+
+    #[test]
+    fn it_disables_the_admin_endpoints_with_an_empty_token() {
+        let config = builder()
+            .admin_token(String::new())
+            .build()
+            .expect("config should be valid");
+        assert!(config.admin_token.is_none());
+    }
+
+    #[test]
+    fn it_matches_only_the_admin_token() {
+        let token = AdminToken::new("secret");
+        assert!(token.matches("secret"));
+        assert!(!token.matches("secreT"));
+        assert!(!token.matches("secret-but-longer"));
+        assert!(!token.matches(""));
+    }
+}
@@ -0,0 +1,293 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use tokio::sync::oneshot;
+
+use crate::cache::{CacheKey, InspectCache};
+use crate::error::InspectError;
+use crate::inspect::InspectStateResponse;
+
+type InspectResult = Result<InspectStateResponse, InspectError>;
+
+/// Request id, session id, and payload digest of an inspect request.
+/// A retry only attaches to the request in flight if it is the same inspect, not only the same id.
+type Key = (String, String, CacheKey);
+
+/// Inspect requests in flight, keyed by the request id supplied by the client.
+/// A request that arrives while another one with the same key is in flight waits for the response
+/// of the first one instead of sending another inspect to the server manager. The entry is removed
+/// when the first request finishes, so a later retry sends a new inspect.
+#[derive(Default)]
+pub struct InFlightRequests {
+    waiters: Mutex<HashMap<Key, Vec<oneshot::Sender<InspectResult>>>>,
+}
+
+enum Role {
+    Leader,
+    Follower(oneshot::Receiver<InspectResult>),
+}
+
+impl InFlightRequests {
+    /// Run the inspect, or wait for the one in flight with the same key.
+    pub async fn run<F, Fut>(
+        &self,
+        request_id: &str,
+        session_id: &str,
+        payload: Vec<u8>,
+        inspect: F,
+    ) -> InspectResult
+    where
+        F: FnOnce(Vec<u8>) -> Fut,
+        Fut: Future<Output = InspectResult>,
+    {
+        let key = (
+            request_id.to_string(),
+            session_id.to_string(),
+            InspectCache::key(&payload),
+        );
+        loop {
+            match self.join(&key) {
+                Role::Leader => break,
+                Role::Follower(response_rx) => match response_rx.await {
+                    Ok(result) => return result,
+                    // The first request was cancelled, so try to take its place
+                    Err(_) => continue,
+                },
+            }
+        }
+        let guard = LeaderGuard {
+            requests: self,
+            key: Some(key),
+        };
+        let result = inspect(payload).await;
+        for response_tx in guard.finish() {
+            let _ = response_tx.send(duplicate(&result));
+        }
+        result
+    }
+
+    fn join(&self, key: &Key) -> Role {
+        let mut waiters = self.waiters.lock().unwrap();
+        match waiters.get_mut(key) {
+            Some(followers) => {
+                let (response_tx, response_rx) = oneshot::channel();
+                followers.push(response_tx);
+                Role::Follower(response_rx)
+            }
+            None => {
+                waiters.insert(key.clone(), vec![]);
+                Role::Leader
+            }
+        }
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.waiters.lock().unwrap().len()
+    }
+}
+
+/// Remove the entry of the leader even if its future is dropped, which wakes up the followers.
+struct LeaderGuard<'a> {
+    requests: &'a InFlightRequests,
+    key: Option<Key>,
+}
+
+impl LeaderGuard<'_> {
+    fn finish(mut self) -> Vec<oneshot::Sender<InspectResult>> {
+        self.remove()
+    }
+
+    fn remove(&mut self) -> Vec<oneshot::Sender<InspectResult>> {
+        match self.key.take() {
+            Some(key) => self
+                .requests
+                .waiters
+                .lock()
+                .unwrap()
+                .remove(&key)
+                .unwrap_or_default(),
+            None => vec![],
+        }
+    }
+}
+
+impl Drop for LeaderGuard<'_> {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+/// Copy the result of the leader for a follower.
+/// InspectError is not Clone because of the sources of the server errors, which inspect requests
+/// never return; they become ServiceUnavailable.
+fn duplicate(result: &InspectResult) -> InspectResult {
+    let error = match result {
+        Ok(response) => return Ok(response.clone()),
+        Err(error) => error,
+    };
+    Err(match error {
+        InspectError::FailedToConnect { message } => {
+            InspectError::FailedToConnect {
+                message: message.clone(),
+            }
+        }
+        InspectError::InspectFailed {
+            message,
+            code,
+            details,
+        } => InspectError::InspectFailed {
+            message: message.clone(),
+            code: *code,
+            details: details.clone(),
+        },
+        InspectError::InvalidArgument { message } => {
+            InspectError::InvalidArgument {
+                message: message.clone(),
+            }
+        }
+        InspectError::MachineException {
+            status,
+            payload,
+            message,
+            reports,
+            processed_input_count,
+        } => InspectError::MachineException {
+            status: *status,
+            payload: payload.clone(),
+            message: message.clone(),
+            reports: reports.clone(),
+            processed_input_count: *processed_input_count,
+        },
+        InspectError::SessionNotFound { session_id } => {
+            InspectError::SessionNotFound {
+                session_id: session_id.clone(),
+            }
+        }
+        InspectError::SessionChanged { session_id } => {
+            InspectError::SessionChanged {
+                session_id: session_id.clone(),
+            }
+        }
+        InspectError::ServerManagerInternal { message } => {
+            InspectError::ServerManagerInternal {
+                message: message.clone(),
+            }
+        }
+        InspectError::PayloadTooLarge { size, limit } => {
+            InspectError::PayloadTooLarge {
+                size: *size,
+                limit: *limit,
+            }
+        }
+        InspectError::ResponseTooLarge { details } => {
+            InspectError::ResponseTooLarge {
+                details: details.clone(),
+            }
+        }
+        InspectError::ReportsNotFound { token } => {
+            InspectError::ReportsNotFound {
+                token: token.clone(),
+            }
+        }
+        InspectError::QueueFull { capacity } => InspectError::QueueFull {
+            capacity: *capacity,
+        },
+        InspectError::RateLimited { retry_after } => {
+            InspectError::RateLimited {
+                retry_after: *retry_after,
+            }
+        }
+        InspectError::Timeout { elapsed } => {
+            InspectError::Timeout { elapsed: *elapsed }
+        }
+        InspectError::ServiceUnavailable
+        | InspectError::HealthCheckError { .. }
+        | InspectError::ServerError { .. } => InspectError::ServiceUnavailable,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::sync::Notify;
+
+    fn response() -> InspectStateResponse {
+        InspectStateResponse {
+            session_id: String::from("default"),
+            active_epoch_index: 0,
+            processed_input_count: 0,
+            status: 0,
+            exception_data: None,
+            reports: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn it_shares_the_response_of_the_request_in_flight() {
+        let requests = Arc::new(InFlightRequests::default());
+        let calls = Arc::new(AtomicUsize::new(0));
+        let release = Arc::new(Notify::new());
+        let spawn = |payload: &[u8]| {
+            let payload = payload.to_vec();
+            let requests = requests.clone();
+            let calls = calls.clone();
+            let release = release.clone();
+            tokio::spawn(async move {
+                requests
+                    .run("id", "default", payload, |_| async move {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        release.notified().await;
+                        Ok(response())
+                    })
+                    .await
+            })
+        };
+        let first = spawn(b"hello");
+        tokio::task::yield_now().await;
+        let second = spawn(b"hello");
+        let other = spawn(b"other");
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        release.notify_waiters();
+        for handle in [first, second, other] {
+            handle.await.unwrap().expect("inspect should succeed");
+        }
+        assert_eq!(requests.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn it_replaces_a_cancelled_request() {
+        let requests = Arc::new(InFlightRequests::default());
+        let first = {
+            let requests = requests.clone();
+            tokio::spawn(async move {
+                requests
+                    .run("id", "default", b"hello".to_vec(), |_| {
+                        std::future::pending::<InspectResult>()
+                    })
+                    .await
+            })
+        };
+        tokio::task::yield_now().await;
+        let second = {
+            let requests = requests.clone();
+            tokio::spawn(async move {
+                requests
+                    .run("id", "default", b"hello".to_vec(), |_| async {
+                        Ok(response())
+                    })
+                    .await
+            })
+        };
+        tokio::task::yield_now().await;
+        first.abort();
+        second.await.unwrap().expect("inspect should succeed");
+        assert_eq!(requests.len(), 0);
+    }
+}
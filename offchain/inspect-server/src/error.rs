@@ -2,6 +2,11 @@
 // SPDX-License-Identifier: Apache-2.0 (see LICENSE)
 
 use snafu::Snafu;
+use std::time::Duration;
+
+use grpc_interfaces::cartesi_server_manager::{CompletionStatus, Report};
+
+use crate::inspect::http_status_for;
 
 #[derive(Debug, Snafu)]
 #[snafu(visibility(pub(crate)))]
@@ -17,6 +22,242 @@ pub enum InspectError {
     #[snafu(display("Failed to connect to server manager: {}", message))]
     FailedToConnect { message: String },
 
+    /// The server manager failed the inspect with a gRPC status that has no dedicated error
     #[snafu(display("Failed to inspect state: {}", message))]
-    InspectFailed { message: String },
+    InspectFailed {
+        message: String,
+        code: tonic::Code,
+        details: Vec<u8>,
+    },
+
+    #[snafu(display("Invalid argument: {}", message))]
+    InvalidArgument { message: String },
+
+    #[snafu(display(
+        "Inspect failed with status {:?}{}",
+        status,
+        message
+            .as_ref()
+            .map(|message| format!(": {}", message))
+            .unwrap_or_default()
+    ))]
+    MachineException {
+        status: CompletionStatus,
+        payload: Vec<u8>,
+        /// Payload decoded as UTF-8; None when it is not valid UTF-8 or the decoding is disabled
+        message: Option<String>,
+        reports: Vec<Report>,
+        processed_input_count: u64,
+    },
+
+    #[snafu(display("Session {:?} not found in server manager", session_id))]
+    SessionNotFound { session_id: String },
+
+    /// The default session was replaced while the inspect was in flight
+    #[snafu(display("Inspect session {:?} was replaced", session_id))]
+    SessionChanged { session_id: String },
+
+    #[snafu(display("Server manager internal error: {}", message))]
+    ServerManagerInternal { message: String },
+
+    #[snafu(display(
+        "Inspect payload is too large ({} bytes, limit: {} bytes)",
+        size,
+        limit
+    ))]
+    PayloadTooLarge { size: usize, limit: usize },
+
+    #[snafu(display("Inspect response is too large ({})", details))]
+    ResponseTooLarge { details: String },
+
+    #[snafu(display("Reports {:?} not found or expired", token))]
+    ReportsNotFound { token: String },
+
+    #[snafu(display("Inspect queue is full (capacity: {})", capacity))]
+    QueueFull { capacity: usize },
+
+    #[snafu(display(
+        "Too many inspect requests, retry after {}ms",
+        retry_after.as_millis()
+    ))]
+    RateLimited { retry_after: Duration },
+
+    #[snafu(display("Inspect service is unavailable"))]
+    ServiceUnavailable,
+
+    #[snafu(display("Inspect request timed out after {}ms", elapsed.as_millis()))]
+    Timeout { elapsed: Duration },
+}
+
+impl InspectError {
+    /// gRPC code of the status returned by the server manager, if the error comes from one.
+    pub fn grpc_code(&self) -> Option<tonic::Code> {
+        match self {
+            InspectError::InspectFailed { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+
+    /// Binary details of the status returned by the server manager, if the error comes from one.
+    /// They are empty when the server manager doesn't send details.
+    pub fn grpc_details(&self) -> Option<&[u8]> {
+        match self {
+            InspectError::InspectFailed { details, .. } => Some(details),
+            _ => None,
+        }
+    }
+
+    /// Stable code of the error, for the API clients to match on instead of the message.
+    pub fn code(&self) -> &'static str {
+        match self {
+            InspectError::HealthCheckError { .. } => "HEALTH_CHECK_ERROR",
+            InspectError::ServerError { .. } => "SERVER_ERROR",
+            InspectError::FailedToConnect { .. } => "FAILED_TO_CONNECT",
+            InspectError::InspectFailed { .. } => "INSPECT_FAILED",
+            InspectError::InvalidArgument { .. } => "INVALID_ARGUMENT",
+            InspectError::MachineException { .. } => "MACHINE_EXCEPTION",
+            InspectError::SessionNotFound { .. } => "SESSION_NOT_FOUND",
+            InspectError::SessionChanged { .. } => "SESSION_CHANGED",
+            InspectError::ServerManagerInternal { .. } => {
+                "SERVER_MANAGER_INTERNAL"
+            }
+            InspectError::PayloadTooLarge { .. } => "PAYLOAD_TOO_LARGE",
+            InspectError::ResponseTooLarge { .. } => "RESPONSE_TOO_LARGE",
+            InspectError::ReportsNotFound { .. } => "REPORTS_NOT_FOUND",
+            InspectError::QueueFull { .. } => "QUEUE_FULL",
+            InspectError::RateLimited { .. } => "RATE_LIMITED",
+            InspectError::ServiceUnavailable => "SERVICE_UNAVAILABLE",
+            InspectError::Timeout { .. } => "TIMEOUT",
+        }
+    }
+
+    /// HTTP status of the error in the inspect API.
+    /// A machine exception has the status of its completion status, like in http_status_for.
+    /// The HTTP inspect endpoints still answer it with 200 and the completion status in the body,
+    /// like the successful inspects, so the clients that read the status from the body keep
+    /// working.
+    pub fn http_status(&self) -> u16 {
+        match self {
+            InspectError::HealthCheckError { .. }
+            | InspectError::ServerError { .. } => 500,
+            InspectError::FailedToConnect { .. } => 502,
+            InspectError::InspectFailed { .. } => 400,
+            InspectError::InvalidArgument { .. } => 400,
+            InspectError::MachineException { status, .. } => {
+                http_status_for(*status)
+            }
+            InspectError::SessionNotFound { .. } => 404,
+            InspectError::SessionChanged { .. } => 503,
+            InspectError::ServerManagerInternal { .. } => 502,
+            InspectError::PayloadTooLarge { .. } => 413,
+            InspectError::ResponseTooLarge { .. } => 502,
+            InspectError::ReportsNotFound { .. } => 404,
+            InspectError::QueueFull { .. } => 429,
+            InspectError::RateLimited { .. } => 429,
+            InspectError::ServiceUnavailable => 503,
+            InspectError::Timeout { .. } => 504,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn all_errors() -> Vec<InspectError> {
+        let message = String::from("message");
+        vec![
+            InspectError::HealthCheckError {
+                source:
+                    http_health_check::HealthCheckError::ParseAddressError {
+                        source: "invalid"
+                            .parse::<std::net::SocketAddr>()
+                            .unwrap_err(),
+                    },
+            },
+            InspectError::ServerError {
+                source: std::io::Error::from(std::io::ErrorKind::AddrInUse),
+            },
+            InspectError::FailedToConnect {
+                message: message.clone(),
+            },
+            InspectError::InspectFailed {
+                message: message.clone(),
+                code: tonic::Code::PermissionDenied,
+                details: vec![],
+            },
+            InspectError::InvalidArgument {
+                message: message.clone(),
+            },
+            InspectError::MachineException {
+                status: CompletionStatus::Exception,
+                payload: vec![],
+                message: None,
+                reports: vec![],
+                processed_input_count: 0,
+            },
+            InspectError::SessionNotFound {
+                session_id: String::from("default"),
+            },
+            InspectError::SessionChanged {
+                session_id: String::from("default"),
+            },
+            InspectError::ServerManagerInternal { message },
+            InspectError::PayloadTooLarge { size: 2, limit: 1 },
+            InspectError::ResponseTooLarge {
+                details: String::from("2 reports, 2 bytes"),
+            },
+            InspectError::ReportsNotFound {
+                token: String::from("token"),
+            },
+            InspectError::QueueFull { capacity: 1 },
+            InspectError::RateLimited {
+                retry_after: Duration::from_secs(1),
+            },
+            InspectError::ServiceUnavailable,
+            InspectError::Timeout {
+                elapsed: Duration::from_secs(1),
+            },
+        ]
+    }
+
+    #[test]
+    fn it_has_a_unique_code_for_each_error() {
+        let errors = all_errors();
+        let codes: HashSet<_> = errors.iter().map(InspectError::code).collect();
+        assert_eq!(codes.len(), errors.len());
+        for code in codes {
+            assert!(code.chars().all(|c| c.is_ascii_uppercase() || c == '_'));
+        }
+    }
+
+    #[test]
+    fn it_has_a_valid_http_status_for_each_error() {
+        for error in all_errors() {
+            let status = error.http_status();
+            assert!(
+                (200..600).contains(&status),
+                "{}: {}",
+                error.code(),
+                status
+            );
+        }
+    }
+
+    #[test]
+    fn it_maps_machine_exceptions_like_their_completion_status() {
+        for status in [CompletionStatus::Exception, CompletionStatus::Rejected]
+        {
+            let error = InspectError::MachineException {
+                status,
+                payload: vec![],
+                message: None,
+                reports: vec![],
+                processed_input_count: 0,
+            };
+            assert_eq!(error.http_status(), http_status_for(status));
+            assert_eq!(error.http_status(), 422);
+        }
+    }
 }
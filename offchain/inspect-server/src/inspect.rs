@@ -1,15 +1,51 @@
 // (c) Cartesi and individual authors (see AUTHORS)
 // SPDX-License-Identifier: Apache-2.0 (see LICENSE)
 
-use tokio::sync::{mpsc, oneshot};
-use tonic::Request;
+use backoff::{future::retry_notify, ExponentialBackoffBuilder};
+use base64::{engine::general_purpose::STANDARD as base64_engine, Engine as _};
+use futures::future::{join_all, Either, FutureExt};
+use futures::stream::{self, Stream, StreamExt};
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::UnixStream;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{
+    oneshot, watch, Mutex, Notify, OwnedSemaphorePermit, Semaphore,
+};
+use tonic::codec::CompressionEncoding;
+use tonic::metadata::{Ascii, AsciiMetadataValue, MetadataValue};
+use tonic::transport::{
+    Certificate, Channel, ClientTlsConfig, Endpoint, Identity, Uri,
+};
+use tonic::{Code, Request, Status};
+use tower::service_fn;
+use tracing::Instrument;
 use uuid::Uuid;
 
-use crate::config::InspectServerConfig;
+use crate::cache::InspectCache;
+use crate::circuit_breaker::CircuitBreaker;
+use crate::config::{
+    server_manager_socket_path, GrpcCompression, InspectServerConfig, TlsConfig,
+};
+use crate::dedup::InFlightRequests;
 use crate::error::InspectError;
+use crate::load_shedding::LoadShedder;
+use crate::metrics::{
+    outcome, InspectMetrics, NoopQueueObserver, QueueObserver,
+};
+use crate::queue;
+pub use crate::queue::Priority;
+use crate::recorder::{InspectRecorder, RecordedInspect};
+use crate::reports::{ReportStore, ReportsPage};
+use crate::retry_budget::RetryBudget;
+use crate::session_limit::SessionLimiter;
 
+use grpc_interfaces::cartesi_machine::Void;
 use grpc_interfaces::cartesi_server_manager::{
-    server_manager_client::ServerManagerClient, InspectStateRequest,
+    server_manager_client::ServerManagerClient, GetSessionStatusRequest,
+    InspectStateRequest,
 };
 pub use grpc_interfaces::cartesi_server_manager::{
     CompletionStatus, InspectStateResponse, Report,
@@ -17,99 +53,1936 @@ pub use grpc_interfaces::cartesi_server_manager::{
 
 #[derive(Clone)]
 pub struct InspectClient {
-    inspect_tx: mpsc::Sender<InspectRequest>,
+    inspect_tx: queue::Sender<InspectRequest>,
+    connection: Arc<Connection>,
+    /// Session of the requests that don't select one, which can be replaced at runtime
+    session: Arc<watch::Sender<String>>,
+    max_payload_size: usize,
+    enqueue_timeout: Duration,
+    decode_exception_messages: bool,
+    load_shedder: Arc<LoadShedder>,
+    session_limiter: Arc<SessionLimiter>,
+    metrics: InspectMetrics,
+    shutdown: Arc<Notify>,
+    /// Requests in flight with a client supplied id; None when the deduplication is disabled
+    in_flight: Option<Arc<InFlightRequests>>,
+    /// Cache of inspect responses shared with the worker; None when it is disabled
+    cache: Option<Arc<std::sync::Mutex<InspectCache>>>,
+    /// Runtime where the client was created, which drives the blocking inspects
+    #[cfg(feature = "blocking")]
+    runtime: tokio::runtime::Handle,
+    reports: Arc<ReportStore>,
 }
 
 /// The inspect client is a wrapper that just sends the inspect requests to another thread and
 /// waits for the result. The actual request to the server manager is done by the handle_inspect
 /// function.
 impl InspectClient {
+    /// Create a client whose worker runs on the current tokio runtime.
     pub fn new(config: &InspectServerConfig) -> Self {
-        let (inspect_tx, inspect_rx) = mpsc::channel(config.queue_size);
-        let address = config.server_manager_address.clone();
-        let session_id = config.session_id.clone();
-        tokio::spawn(handle_inspect(address, session_id, inspect_rx));
-        Self { inspect_tx }
+        Self::new_on(&tokio::runtime::Handle::current(), config)
+    }
+
+    /// Create a client whose worker runs on the runtime of the given handle, so it can be created
+    /// outside of a runtime.
+    pub fn new_on(
+        handle: &tokio::runtime::Handle,
+        config: &InspectServerConfig,
+    ) -> Self {
+        Self::build(handle, config, Arc::new(NoopQueueObserver), None)
+    }
+
+    /// Create a client that reports the queue events to the given observer.
+    pub fn with_queue_observer(
+        config: &InspectServerConfig,
+        observer: Arc<dyn QueueObserver>,
+    ) -> Self {
+        Self::build(&tokio::runtime::Handle::current(), config, observer, None)
+    }
+
+    /// Create a client that applies the interceptor to each gRPC request sent to the server
+    /// manager, e.g. to add authorization metadata.
+    pub fn with_interceptor<F>(
+        config: &InspectServerConfig,
+        interceptor: F,
+    ) -> Self
+    where
+        F: Fn(&mut Request<()>) + Send + Sync + Clone + 'static,
+    {
+        Self::build(
+            &tokio::runtime::Handle::current(),
+            config,
+            Arc::new(NoopQueueObserver),
+            Some(Arc::new(interceptor)),
+        )
+    }
+
+    fn build(
+        handle: &tokio::runtime::Handle,
+        config: &InspectServerConfig,
+        observer: Arc<dyn QueueObserver>,
+        interceptor: Option<Interceptor>,
+    ) -> Self {
+        let (inspect_tx, inspect_rx) =
+            queue::channel(config.queue_size, config.priority_aging);
+        let metrics = InspectMetrics::new(config.queue_size, observer);
+        let shutdown = Arc::new(Notify::new());
+        let connection =
+            Arc::new(Connection::new(config, interceptor, metrics.clone()));
+        let (session, session_rx) = watch::channel(config.session_id.clone());
+        let cache = match config.cache_size {
+            0 => None,
+            size => Some(Arc::new(std::sync::Mutex::new(InspectCache::new(
+                size,
+                config.cache_ttl,
+            )))),
+        };
+        handle.spawn(handle_inspect(
+            config.clone(),
+            inspect_rx,
+            connection.clone(),
+            cache.clone(),
+            metrics.clone(),
+            shutdown.clone(),
+            session_rx,
+        ));
+        Self {
+            inspect_tx,
+            connection,
+            session: Arc::new(session),
+            max_payload_size: config.max_payload_size,
+            enqueue_timeout: config.enqueue_timeout,
+            decode_exception_messages: config.decode_exception_messages,
+            load_shedder: Arc::new(LoadShedder::new(
+                config.load_shedding_threshold,
+            )),
+            session_limiter: Arc::new(SessionLimiter::new(
+                config.session_concurrency,
+                config.session_concurrency_overrides.clone(),
+            )),
+            metrics,
+            shutdown,
+            in_flight: config
+                .dedup_request_ids
+                .then(|| Arc::new(InFlightRequests::default())),
+            cache,
+            #[cfg(feature = "blocking")]
+            runtime: handle.clone(),
+            reports: Arc::new(ReportStore::new(config.reports_ttl)),
+        }
+    }
+
+    /// Stop accepting inspect requests and drain the ones in the queue.
+    /// The requests that are not handled before the shutdown timeout are answered with
+    /// ServiceUnavailable.
+    pub fn shutdown(&self) {
+        self.shutdown.notify_one();
+    }
+
+    /// Session of the inspect requests that don't select one.
+    pub fn session_id(&self) -> String {
+        self.session.borrow().clone()
+    }
+
+    /// Replace the session of the inspect requests that don't select one.
+    /// The requests of the previous session that are queued or being processed fail with
+    /// SessionChanged, since their responses would come from a session that is no longer used;
+    /// the cached responses are dropped for the same reason. The new requests use the new session.
+    pub fn set_session(&self, session_id: String) {
+        let changed = self.session.send_if_modified(|current| {
+            if *current == session_id {
+                return false;
+            }
+            tracing::info!(
+                previous = %current,
+                session_id = %session_id,
+                "changing the inspect session"
+            );
+            *current = session_id;
+            true
+        });
+        if !changed {
+            tracing::debug!("inspect session is unchanged");
+        }
     }
 
+    /// Check whether the server manager is reachable and has the inspect session.
+    /// The client is not ready once it is shutting down.
+    pub async fn check_ready(&self) -> Result<(), InspectError> {
+        if self.inspect_tx.is_closed() {
+            return Err(InspectError::ServiceUnavailable);
+        }
+        let (index, mut client) = self.connection.client().await?;
+        let request = self.connection.intercept(Request::new(Void {}));
+        let response = client.get_status(request).await;
+        let response = match response {
+            Ok(response) => response.into_inner(),
+            Err(e) => {
+                if is_transport_error(&e) {
+                    self.connection.reset(index).await;
+                }
+                return Err(InspectError::FailedToConnect {
+                    message: e.message().to_string(),
+                });
+            }
+        };
+        let session_id = self.session_id();
+        if !response.session_id.contains(&session_id) {
+            return Err(InspectError::SessionNotFound { session_id });
+        }
+        Ok(())
+    }
+
+    /// Check whether the session of the config exists in the server manager.
+    pub async fn check_session(&self) -> Result<(), InspectError> {
+        let (index, mut client) = self.connection.client().await?;
+        let session_id = self.session_id();
+        let request = GetSessionStatusRequest {
+            session_id: session_id.clone(),
+        };
+        let request = self.connection.intercept(Request::new(request));
+        match client.get_session_status(request).await {
+            Ok(_) => Ok(()),
+            Err(e) if is_transport_error(&e) => {
+                self.connection.reset(index).await;
+                Err(InspectError::FailedToConnect {
+                    message: e.message().to_string(),
+                })
+            }
+            Err(e) => Err(status_error(&e, &session_id)),
+        }
+    }
+
+    /// Probe the server manager every interval until it is ready or max_wait elapses.
+    /// Return whether the server manager is ready.
+    pub async fn wait_until_ready(
+        &self,
+        interval: Duration,
+        max_wait: Duration,
+    ) -> bool {
+        let start = Instant::now();
+        loop {
+            match self.check_ready().await {
+                Ok(()) => {
+                    tracing::info!("server manager is ready");
+                    return true;
+                }
+                Err(e) => {
+                    tracing::info!("waiting for server manager: {}", e);
+                }
+            }
+            if start.elapsed() + interval > max_wait {
+                return false;
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Remove all the responses from the cache, so the next inspects are sent to the server manager
+    /// even if they were answered before.
+    /// Return the number of responses removed, which is zero when the cache is disabled.
+    pub fn flush_cache(&self) -> usize {
+        let flushed = match &self.cache {
+            Some(cache) => cache.lock().unwrap().clear(),
+            None => 0,
+        };
+        tracing::info!(flushed, "inspect cache flushed");
+        flushed
+    }
+
+    /// Metrics of the inspect requests sent by this client.
+    pub fn metrics(&self) -> &InspectMetrics {
+        &self.metrics
+    }
+
+    /// Number of inspect requests waiting in the queue.
+    pub fn queue_depth(&self) -> usize {
+        self.queue_capacity() - self.inspect_tx.capacity()
+    }
+
+    /// Maximum number of inspect requests waiting in the queue.
+    pub fn queue_capacity(&self) -> usize {
+        self.inspect_tx.max_capacity()
+    }
+
+    /// Send an inspect request to the server manager, with the default options.
+    /// An inspect that raises an exception in the machine or is rejected by it fails with
+    /// MachineException, which has the exception payload; the other completion statuses return the
+    /// whole response.
     pub async fn inspect(
         &self,
         payload: Vec<u8>,
     ) -> Result<InspectStateResponse, InspectError> {
+        self.inspect_with(payload, InspectOptions::default()).await
+    }
+
+    /// Send an inspect request to the server manager with the given options.
+    pub async fn inspect_with(
+        &self,
+        payload: Vec<u8>,
+        options: InspectOptions,
+    ) -> Result<InspectStateResponse, InspectError> {
+        let mut response = self
+            .observe(
+                payload,
+                options.session_id,
+                options.request_id,
+                options.priority,
+                Duration::ZERO,
+            )
+            .await?;
+        if let Some(report_filter) = options.report_filter {
+            report_filter.apply(&mut response.reports);
+        }
+        Ok(response)
+    }
+
+    /// Send an inspect request and keep its reports to be read in pages with get_reports.
+    /// Return the response without the reports and the token of the reports, which expires after
+    /// the reports TTL of the config.
+    pub async fn inspect_paged(
+        &self,
+        payload: Vec<u8>,
+        session_id: Option<String>,
+        request_id: Option<String>,
+    ) -> Result<(InspectStateResponse, String), InspectError> {
+        let options = InspectOptions {
+            session_id,
+            request_id,
+            ..Default::default()
+        };
+        let mut response = self.inspect_with(payload, options).await?;
+        let reports = std::mem::take(&mut response.reports);
+        Ok((response, self.reports.insert(reports)))
+    }
+
+    /// Return up to limit reports of a paged inspect, starting at offset.
+    pub fn get_reports(
+        &self,
+        token: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<ReportsPage, InspectError> {
+        self.reports.get(token, offset, limit).ok_or_else(|| {
+            InspectError::ReportsNotFound {
+                token: token.to_string(),
+            }
+        })
+    }
+
+    /// Send an inspect request to the server manager, waiting for space in the queue when it is
+    /// full.
+    /// Unlike inspect, which fails as soon as the queue is full, this function waits up to the
+    /// enqueue timeout of the config before failing with QueueFull; a zero timeout behaves like
+    /// inspect.
+    pub async fn inspect_blocking(
+        &self,
+        payload: Vec<u8>,
+        session_id: Option<String>,
+        request_id: Option<String>,
+    ) -> Result<InspectStateResponse, InspectError> {
+        self.observe(
+            payload,
+            session_id,
+            request_id,
+            Priority::Normal,
+            self.enqueue_timeout,
+        )
+        .await
+    }
+
+    /// Send an inspect request from synchronous code, blocking the current thread until the
+    /// response arrives.
+    /// The request is driven by the runtime of the client worker, which must be a
+    /// multi-thread runtime. This function panics if called from an asynchronous context, where
+    /// it would block the runtime; use inspect there instead.
+    /// This function is only available with the blocking feature.
+    #[cfg(feature = "blocking")]
+    pub fn blocking_inspect(
+        &self,
+        payload: Vec<u8>,
+        session_id: Option<String>,
+        request_id: Option<String>,
+    ) -> Result<InspectStateResponse, InspectError> {
+        let options = InspectOptions {
+            session_id,
+            request_id,
+            ..Default::default()
+        };
+        self.runtime.block_on(self.inspect_with(payload, options))
+    }
+
+    /// Send the inspect request, record its metrics, and check whether it raised an exception.
+    /// The request runs in an inspect span, which records its outcome when it finishes, so each
+    /// request has one event with all its fields, even when it fails.
+    async fn observe(
+        &self,
+        payload: Vec<u8>,
+        session_id: Option<String>,
+        request_id: Option<String>,
+        priority: Priority,
+        enqueue_timeout: Duration,
+    ) -> Result<InspectStateResponse, InspectError> {
+        // Only the ids supplied by the client can repeat
+        let in_flight =
+            self.in_flight.as_ref().filter(|_| request_id.is_some());
+        let request_id =
+            request_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+        let default_session = self.session_id();
+        let span = tracing::info_span!(
+            "inspect",
+            request_id = %request_id,
+            session_id = %session_id.as_deref().unwrap_or(&default_session),
+            payload_len = payload.len(),
+            status = tracing::field::Empty,
+            report_count = tracing::field::Empty,
+            elapsed_ms = tracing::field::Empty,
+        );
+        async {
+            let start = Instant::now();
+            let enqueue = |payload| {
+                self.enqueue(
+                    payload,
+                    session_id.clone(),
+                    request_id.clone(),
+                    priority,
+                    enqueue_timeout,
+                )
+            };
+            let result = match in_flight {
+                Some(in_flight) => {
+                    let dedup_session_id =
+                        session_id.as_deref().unwrap_or(&default_session);
+                    in_flight
+                        .run(&request_id, dedup_session_id, payload, enqueue)
+                        .await
+                }
+                None => enqueue(payload).await,
+            };
+            let elapsed = start.elapsed();
+            self.metrics.observe(&result, elapsed);
+            let span = tracing::Span::current();
+            span.record("status", outcome(&result));
+            span.record(
+                "report_count",
+                result.as_ref().map_or(0, |response| response.reports.len()),
+            );
+            span.record("elapsed_ms", elapsed.as_millis() as u64);
+            tracing::info!("inspect finished");
+            result.and_then(|response| {
+                check_exception(response, self.decode_exception_messages)
+            })
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Add the inspect request to the queue and wait for its response.
+    async fn enqueue(
+        &self,
+        payload: Vec<u8>,
+        session_id: Option<String>,
+        request_id: String,
+        priority: Priority,
+        enqueue_timeout: Duration,
+    ) -> Result<InspectStateResponse, InspectError> {
+        if payload.len() > self.max_payload_size {
+            return Err(InspectError::PayloadTooLarge {
+                size: payload.len(),
+                limit: self.max_payload_size,
+            });
+        }
+        let (session_id, default_session) = match session_id {
+            Some(session_id) => {
+                if session_id.is_empty() {
+                    return Err(InspectError::InvalidArgument {
+                        message: String::from("empty session id"),
+                    });
+                }
+                (session_id, false)
+            }
+            None => (self.session_id(), true),
+        };
+        if !is_valid_request_id(&request_id) {
+            return Err(InspectError::InvalidArgument {
+                message: format!("invalid request id {:?}", request_id),
+            });
+        }
+        // Held until the response arrives, so the session has a slot while its request is queued
+        let _session_permit = self.session_limiter.acquire(&session_id).await;
+        let capacity = self.queue_capacity();
+        let len = self.queue_depth();
+        if self.load_shedder.should_shed(len, capacity) {
+            tracing::debug!(len, capacity, "shedding inspect request");
+            return Err(InspectError::QueueFull { capacity });
+        }
         let (response_tx, response_rx) = oneshot::channel();
         let request = InspectRequest {
             payload,
+            session_id,
+            default_session,
+            request_id,
             response_tx,
+            span: tracing::Span::current(),
         };
-        if let Err(e) = self.inspect_tx.try_send(request) {
-            return Err(InspectError::InspectFailed {
-                message: e.to_string(),
-            });
+        let permit = if enqueue_timeout.is_zero() {
+            self.inspect_tx.try_reserve().map_err(|e| match e {
+                TrySendError::Full(()) => InspectError::QueueFull {
+                    capacity: self.inspect_tx.max_capacity(),
+                },
+                TrySendError::Closed(()) => InspectError::ServiceUnavailable,
+            })?
         } else {
-            tracing::debug!("inspect request added to the queue");
+            // Dropping this future, for instance when the caller disconnects, gives up the wait
+            let reserve = self.inspect_tx.reserve();
+            match tokio::time::timeout(enqueue_timeout, reserve).await {
+                Ok(Ok(permit)) => permit,
+                Ok(Err(_)) => return Err(InspectError::ServiceUnavailable),
+                Err(_) => {
+                    return Err(InspectError::QueueFull {
+                        capacity: self.inspect_tx.max_capacity(),
+                    })
+                }
+            }
+        };
+        // Count the request before sending it, so handle_inspect never dequeues it first
+        self.metrics.enqueued();
+        permit.send(priority, request);
+        tracing::debug!("inspect request added to the queue");
+        // The request is dropped without a response if its task panics
+        response_rx.await.unwrap_or_else(|_| {
+            tracing::error!("inspect request dropped without a response");
+            Err(InspectError::ServiceUnavailable)
+        })
+    }
+
+    /// Send an inspect request and yield its reports in order.
+    /// The server manager doesn't stream the reports, so they are yielded from the response once it
+    /// arrives. The completion status is not part of the stream; an error is yielded if the inspect
+    /// fails.
+    pub fn inspect_stream(
+        &self,
+        payload: Vec<u8>,
+        request_id: Option<String>,
+    ) -> impl Stream<Item = Result<Report, InspectError>> {
+        let client = self.clone();
+        stream::once(async move {
+            let options = InspectOptions {
+                request_id,
+                ..Default::default()
+            };
+            client.inspect_with(payload, options).await
+        })
+        .flat_map(|result| {
+            let reports = match result {
+                Ok(response) => response.reports.into_iter().map(Ok).collect(),
+                Err(e) => vec![Err(e)],
+            };
+            stream::iter(reports)
+        })
+    }
+
+    /// Send multiple inspect requests and wait for all of them.
+    /// The results are returned in the same order as the payloads, and a failure in one of them
+    /// doesn't affect the others. Each payload takes a slot in the inspect queue, so the number of
+    /// requests being handled at the same time is still bounded by the queue size.
+    /// The batch is also recorded in the batch metrics, with the fraction of its inspects that
+    /// succeeded.
+    pub async fn inspect_batch(
+        &self,
+        payloads: Vec<Vec<u8>>,
+    ) -> Vec<Result<InspectStateResponse, InspectError>> {
+        let results =
+            join_all(payloads.into_iter().map(|payload| self.inspect(payload)))
+                .await;
+        self.metrics.observe_batch(&results);
+        results
+    }
+
+    /// Send multiple inspect requests and yield each result with the index of its payload.
+    /// In the Ordered mode the results are yielded in the order of the payloads, so a slow
+    /// inspect holds back the ones after it; in the AsCompleted mode each result is yielded as
+    /// soon as its inspect finishes.
+    pub fn inspect_batch_stream(
+        &self,
+        payloads: Vec<Vec<u8>>,
+        mode: InspectBatchMode,
+    ) -> impl Stream<Item = (usize, Result<InspectStateResponse, InspectError>)>
+    {
+        // Every inspect is sent at once, as in inspect_batch
+        let limit = payloads.len().max(1);
+        let client = self.clone();
+        let inspects = stream::iter(payloads.into_iter().enumerate()).map(
+            move |(index, payload)| {
+                let client = client.clone();
+                async move { (index, client.inspect(payload).await) }
+            },
+        );
+        match mode {
+            InspectBatchMode::Ordered => Either::Left(inspects.buffered(limit)),
+            InspectBatchMode::AsCompleted => {
+                Either::Right(inspects.buffer_unordered(limit))
+            }
+        }
+    }
+}
+
+/// Order of the results of inspect_batch_stream.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InspectBatchMode {
+    /// Yield the results in the order of the payloads
+    #[default]
+    Ordered,
+    /// Yield each result as soon as its inspect finishes
+    AsCompleted,
+}
+
+/// Turn the response of an inspect that raised an exception or was rejected by the machine into
+/// MachineException, with the status of the response.
+/// The exception payload is also decoded as UTF-8 when decode_message is set and it is valid
+/// UTF-8, since the machines often raise exceptions with an error message.
+fn check_exception(
+    response: InspectStateResponse,
+    decode_message: bool,
+) -> Result<InspectStateResponse, InspectError> {
+    let status = match CompletionStatus::from_i32(response.status) {
+        Some(
+            status @ (CompletionStatus::Exception | CompletionStatus::Rejected),
+        ) => status,
+        _ => return Ok(response),
+    };
+    let payload = response.exception_data.unwrap_or_default();
+    let message = if decode_message && !payload.is_empty() {
+        String::from_utf8(payload.clone()).ok()
+    } else {
+        None
+    };
+    Err(InspectError::MachineException {
+        status,
+        payload,
+        message,
+        reports: response.reports,
+        processed_input_count: response.processed_input_count,
+    })
+}
+
+/// Reject the inspect responses that exceed the report limits of the config.
+fn check_report_limits(
+    config: &InspectServerConfig,
+    response: InspectStateResponse,
+) -> Result<InspectStateResponse, InspectError> {
+    let reports = response.reports.len();
+    let bytes = response
+        .reports
+        .iter()
+        .map(|report| report.payload.len())
+        .sum();
+    let exceeds = |count: usize, limit: usize| limit > 0 && count > limit;
+    if exceeds(reports, config.max_reports)
+        || exceeds(bytes, config.max_total_report_bytes)
+    {
+        return Err(InspectError::ResponseTooLarge {
+            details: format!("{} reports, {} bytes", reports, bytes),
+        });
+    }
+    Ok(response)
+}
+
+/// Check whether the request id only has visible ASCII characters, so it can be sent as gRPC
+/// metadata.
+fn is_valid_request_id(request_id: &str) -> bool {
+    !request_id.is_empty()
+        && request_id.chars().all(|c| c.is_ascii_graphic())
+        && MetadataValue::<Ascii>::try_from(request_id).is_ok()
+}
+
+/// Check whether the server manager at the endpoint is reachable by connecting to it and sending
+/// the status RPC, which doesn't touch the sessions.
+/// Fail with FailedToConnect when the server manager is unreachable or the RPC fails, or with
+/// Timeout when both take longer than the timeout; zero disables the timeout.
+pub async fn probe_server_manager(
+    endpoint: Endpoint,
+    timeout: Duration,
+) -> Result<(), InspectError> {
+    let probe = async {
+        let channel = endpoint.connect().await.map_err(|e| {
+            InspectError::FailedToConnect {
+                message: error_chain(&e),
+            }
+        })?;
+        ServerManagerClient::new(channel)
+            .get_status(Request::new(Void {}))
+            .await
+            .map_err(|e| InspectError::FailedToConnect {
+                message: e.message().to_string(),
+            })?;
+        Ok(())
+    };
+    if timeout.is_zero() {
+        return probe.await;
+    }
+    tokio::time::timeout(timeout, probe)
+        .await
+        .unwrap_or(Err(InspectError::Timeout { elapsed: timeout }))
+}
+
+/// Text encoding of the report payloads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadEncoding {
+    /// 0x-prefixed hex, as in Ethereum
+    Hex,
+    /// Standard base64 with padding
+    Base64,
+    /// UTF-8, with the invalid sequences replaced by U+FFFD
+    Utf8Lossy,
+}
+
+/// Encode the payload of each report with the given encoding.
+pub fn encode_reports(
+    reports: &[Report],
+    encoding: PayloadEncoding,
+) -> Vec<String> {
+    reports
+        .iter()
+        .map(|report| match encoding {
+            PayloadEncoding::Hex => {
+                String::from("0x") + &hex::encode(&report.payload)
+            }
+            PayloadEncoding::Base64 => base64_engine.encode(&report.payload),
+            PayloadEncoding::Utf8Lossy => {
+                String::from_utf8_lossy(&report.payload).into_owned()
+            }
+        })
+        .collect()
+}
+
+/// Prefix of the payloads decoded as base64 by decode_payload
+pub const BASE64_PAYLOAD_PREFIX: &str = "base64:";
+
+/// Decode a payload received as text, which is hex with or without the 0x prefix, or standard
+/// base64 after the base64: prefix. An empty input is an empty payload.
+pub fn decode_payload(input: &str) -> Result<Vec<u8>, InspectError> {
+    if let Some(encoded) = input.strip_prefix(BASE64_PAYLOAD_PREFIX) {
+        return base64_engine.decode(encoded).map_err(|e| {
+            InspectError::InvalidArgument {
+                message: format!("invalid base64 payload: {}", e),
+            }
+        });
+    }
+    let encoded = input.strip_prefix("0x").unwrap_or(input);
+    hex::decode(encoded).map_err(|e| InspectError::InvalidArgument {
+        message: format!("invalid hex payload: {}", e),
+    })
+}
+
+/// Options of an inspect request; the default sends it to the session from the config, with a new
+/// request id, all the reports, and the normal priority.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InspectOptions {
+    session_id: Option<String>,
+    request_id: Option<String>,
+    report_filter: Option<ReportFilter>,
+    priority: Priority,
+}
+
+impl InspectOptions {
+    /// Select the server manager session instead of the one from the config.
+    pub fn session_id(mut self, session_id: impl Into<String>) -> Self {
+        self.session_id = Some(session_id.into());
+        self
+    }
+
+    /// Forward the request id as the request-id gRPC metadata instead of a new UUID.
+    pub fn request_id(mut self, request_id: impl Into<String>) -> Self {
+        self.request_id = Some(request_id.into());
+        self
+    }
+
+    /// Keep only a range of the reports of the response; the server manager still sends all of
+    /// them.
+    pub fn report_filter(mut self, report_filter: ReportFilter) -> Self {
+        self.report_filter = Some(report_filter);
+        self
+    }
+
+    /// Decide which requests leave the queue first when it is backed up.
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+/// Range of the reports of an inspect response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReportFilter {
+    pub start_index: usize,
+    pub count: usize,
+}
+
+impl ReportFilter {
+    /// Keep the reports in the range; the part of the range past the last report is ignored.
+    pub fn apply(&self, reports: &mut Vec<Report>) {
+        reports.drain(..self.start_index.min(reports.len()));
+        reports.truncate(self.count);
+    }
+}
+
+/// Map the completion status of an inspect to the HTTP status code that represents it.
+pub fn http_status_for(status: CompletionStatus) -> u16 {
+    match status {
+        CompletionStatus::Accepted => 200,
+        CompletionStatus::Rejected => 422,
+        CompletionStatus::Exception => 422,
+        CompletionStatus::MachineHalted => 400,
+        CompletionStatus::CycleLimitExceeded => 408,
+        CompletionStatus::TimeLimitExceeded => 408,
+        CompletionStatus::PayloadLengthLimitExceeded => 413,
+    }
+}
+
+/// Fields of an inspect response that are relevant to the frontends, so they don't need to handle
+/// the gRPC types.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InspectSummary {
+    /// Completion status of the inspect; None if the server manager sent an unknown status
+    pub status: Option<CompletionStatus>,
+    pub reports: Vec<Vec<u8>>,
+    pub exception_payload: Option<Vec<u8>>,
+    pub processed_input_count: u64,
+}
+
+impl From<InspectStateResponse> for InspectSummary {
+    fn from(response: InspectStateResponse) -> InspectSummary {
+        InspectSummary {
+            status: CompletionStatus::from_i32(response.status),
+            reports: response
+                .reports
+                .into_iter()
+                .map(|report| report.payload)
+                .collect(),
+            exception_payload: response.exception_data,
+            processed_input_count: response.processed_input_count,
         }
-        response_rx.await.expect("handle_inspect never fails")
     }
 }
 
 struct InspectRequest {
     payload: Vec<u8>,
+    session_id: String,
+    /// Whether the request uses the default session, so it is cancelled if the session changes
+    default_session: bool,
+    request_id: String,
     response_tx: oneshot::Sender<Result<InspectStateResponse, InspectError>>,
+    /// Span of the caller, so the logs of the task are part of the request
+    span: tracing::Span,
+}
+
+/// Whether the response of an inspect request reached the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Delivery {
+    Delivered,
+    /// The client stopped waiting, so the work on the inspect was wasted
+    Dropped,
 }
 
 fn respond(
     response_tx: oneshot::Sender<Result<InspectStateResponse, InspectError>>,
     response: Result<InspectStateResponse, InspectError>,
-) {
-    if response_tx.send(response).is_err() {
-        tracing::warn!("failed to respond inspect request (client dropped)");
+) -> Delivery {
+    match response_tx.send(response) {
+        Ok(()) => Delivery::Delivered,
+        Err(_) => {
+            warn_dropped_response();
+            Delivery::Dropped
+        }
+    }
+}
+
+/// Minimum time between the warnings about dropped responses, so a mass disconnect of the clients
+/// doesn't flood the logs.
+const DROPPED_WARNING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Minimum time between the warnings about slow inspects, so a server manager that is slow for
+/// every request doesn't flood the logs.
+const SLOW_WARNING_INTERVAL: Duration = Duration::from_secs(10);
+
+fn warn_dropped_response() {
+    static WARNINGS: WarningLimiter =
+        WarningLimiter::new(DROPPED_WARNING_INTERVAL);
+    if let Some(suppressed) = WARNINGS.check(Instant::now()) {
+        tracing::warn!(
+            suppressed,
+            "failed to respond inspect request (client dropped)"
+        );
+    }
+}
+
+/// Allows at most one warning per interval and counts the ones suppressed in between.
+struct WarningLimiter {
+    interval: Duration,
+    last_warning: std::sync::Mutex<Option<Instant>>,
+    suppressed: AtomicUsize,
+}
+
+impl WarningLimiter {
+    const fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_warning: std::sync::Mutex::new(None),
+            suppressed: AtomicUsize::new(0),
+        }
+    }
+
+    /// Return the number of warnings suppressed since the last one if a warning can be logged now.
+    fn check(&self, now: Instant) -> Option<usize> {
+        let mut last_warning = self.last_warning.lock().unwrap();
+        match *last_warning {
+            Some(last) if now.duration_since(last) < self.interval => {
+                self.suppressed.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+            _ => {
+                *last_warning = Some(now);
+                Some(self.suppressed.swap(0, Ordering::Relaxed))
+            }
+        }
     }
 }
 
-/// Loop that answers requests coming from inspect_rx.
+/// Loop that answers requests coming from inspect_rx, taking the ones with a higher priority first.
+/// Each request is handled in its own task and the number of requests being processed at the same
+/// time is limited by the concurrency config. While all tasks are busy, the remaining requests wait
+/// in the inspect_rx queue.
+/// When the shutdown is requested, the queue stops accepting requests and the requests already in
+/// it are handled until the shutdown timeout expires. The requests still pending after that are
+/// answered with ServiceUnavailable.
+/// A panic while taking the requests from the queue is logged and the loop restarts, so the client
+/// keeps working; the request being taken is dropped, which fails it with ServiceUnavailable.
+/// When the default session changes, the requests of the previous one are cancelled.
 async fn handle_inspect(
-    address: String,
-    session_id: String,
-    mut inspect_rx: mpsc::Receiver<InspectRequest>,
+    config: InspectServerConfig,
+    mut inspect_rx: queue::Receiver<InspectRequest>,
+    connection: Arc<Connection>,
+    cache: Option<Arc<std::sync::Mutex<InspectCache>>>,
+    metrics: InspectMetrics,
+    shutdown: Arc<Notify>,
+    session: watch::Receiver<String>,
 ) {
-    let endpoint = format!("http://{}", address);
+    let recorder = config
+        .record_path
+        .clone()
+        .map(|path| Arc::new(InspectRecorder::start(path)));
+    let worker = Worker {
+        connection,
+        cache,
+        recorder,
+        slow_warnings: Arc::new(WarningLimiter::new(SLOW_WARNING_INTERVAL)),
+        semaphore: Arc::new(Semaphore::new(config.concurrency)),
+        config: Arc::new(config),
+        metrics,
+        expired: watch::channel(false).0,
+        session,
+    };
+    loop {
+        let serve = AssertUnwindSafe(worker.serve(&mut inspect_rx, &shutdown));
+        match serve.catch_unwind().await {
+            Ok(true) => break,
+            Ok(false) => return,
+            Err(panic) => tracing::error!(
+                "inspect worker panicked, restarting it: {}",
+                panic_message(&panic)
+            ),
+        }
+    }
+
+    tracing::info!("draining inspect queue before shutting down");
+    inspect_rx.close();
+    let deadline = tokio::time::sleep(worker.config.shutdown_timeout);
+    tokio::pin!(deadline);
+    loop {
+        tokio::select! {
+            next = worker.next_request(&mut inspect_rx) => match next {
+                Some((permit, request)) => worker.spawn(permit, request),
+                None => break,
+            },
+            _ = &mut deadline => break,
+        }
+    }
+    // Wait for the requests being processed
+    tokio::select! {
+        _ = worker.wait_idle() => {}
+        _ = &mut deadline => {}
+    }
+
     while let Some(request) = inspect_rx.recv().await {
-        match ServerManagerClient::connect(endpoint.clone()).await {
-            Err(e) => {
-                respond(
-                    request.response_tx,
-                    Err(InspectError::FailedToConnect {
-                        message: e.to_string(),
-                    }),
-                );
+        worker.metrics.dequeued();
+        let delivery =
+            respond(request.response_tx, Err(InspectError::ServiceUnavailable));
+        if delivery == Delivery::Dropped {
+            worker.metrics.dropped_response();
+        }
+    }
+    // Answer the requests still being processed and wait for the tasks to finish
+    worker.expired.send_replace(true);
+    worker.wait_idle().await;
+    tracing::info!("inspect queue drained");
+}
+
+/// State shared by the tasks that handle the inspect requests.
+struct Worker {
+    config: Arc<InspectServerConfig>,
+    connection: Arc<Connection>,
+    /// Cache of inspect responses; None when it is disabled
+    cache: Option<Arc<std::sync::Mutex<InspectCache>>>,
+    /// Recorder of the inspects sent to the server manager; None when it is disabled
+    recorder: Option<Arc<InspectRecorder>>,
+    /// Limit of the warnings about the inspects slower than the threshold
+    slow_warnings: Arc<WarningLimiter>,
+    semaphore: Arc<Semaphore>,
+    metrics: InspectMetrics,
+    /// Set when the shutdown timeout expires
+    expired: watch::Sender<bool>,
+    /// Default session of the client
+    session: watch::Receiver<String>,
+}
+
+impl Worker {
+    /// Handle the requests in the queue until the shutdown is requested or the queue is closed.
+    /// Return whether the shutdown was requested.
+    async fn serve(
+        &self,
+        inspect_rx: &mut queue::Receiver<InspectRequest>,
+        shutdown: &Notify,
+    ) -> bool {
+        let mut session = self.session.clone();
+        loop {
+            tokio::select! {
+                next = self.next_request(inspect_rx) => match next {
+                    Some((permit, request)) => self.spawn(permit, request),
+                    None => return false,
+                },
+                _ = shutdown.notified() => return true,
+                Ok(()) = session.changed() => {
+                    if let Some(cache) = &self.cache {
+                        cache.lock().unwrap().clear();
+                    }
+                }
             }
-            Ok(mut client) => {
-                let request_id = Uuid::new_v4().to_string();
-                let grpc_request = InspectStateRequest {
-                    session_id: session_id.clone(),
-                    query_payload: request.payload,
+        }
+    }
+
+    /// Wait for a free task and the next request in the queue.
+    /// Return None when the queue is closed and empty.
+    async fn next_request(
+        &self,
+        inspect_rx: &mut queue::Receiver<InspectRequest>,
+    ) -> Option<(OwnedSemaphorePermit, InspectRequest)> {
+        let permit = self
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("semaphore is never closed");
+        let request = inspect_rx.recv().await?;
+        self.metrics.dequeued();
+        Some((permit, request))
+    }
+
+    /// Handle the request in a new task.
+    /// The inspect is cancelled if the client stops waiting for the response.
+    fn spawn(&self, permit: OwnedSemaphorePermit, request: InspectRequest) {
+        let config = self.config.clone();
+        let connection = self.connection.clone();
+        let cache = self.cache.clone();
+        let recorder = self.recorder.clone();
+        let slow_warnings = self.slow_warnings.clone();
+        let metrics = self.metrics.clone();
+        let expired = self.expired.subscribe();
+        let mut session = self.session.clone();
+        let InspectRequest {
+            payload,
+            session_id,
+            default_session,
+            request_id,
+            mut response_tx,
+            span,
+        } = request;
+        tokio::spawn(
+            async move {
+                // The cache is invalidated by the processed_input_count of the default session, so
+                // the other sessions are not cached
+                let cache = cache
+                    .as_ref()
+                    .filter(|_| session_id == *session.borrow());
+                let key = cache.map(|cache| {
+                    let key = InspectCache::key(&payload);
+                    (cache, key)
+                });
+                if let Some((cache, key)) = &key {
+                    if let Some(response) = cache.lock().unwrap().get(key) {
+                        tracing::debug!("inspect response found in the cache");
+                        if respond(response_tx, Ok(response)) == Delivery::Dropped {
+                            metrics.dropped_response();
+                        }
+                        return;
+                    }
+                }
+                // The payload is only copied when the inspects are recorded
+                let recorded_payload = recorder.as_ref().map(|_| payload.clone());
+                let payload_len = payload.len();
+                let start = Instant::now();
+                let response = tokio::select! {
+                    response = inspect_state(
+                        &config,
+                        &connection,
+                        &session_id,
+                        payload,
+                        &request_id,
+                    ) => response,
+                    _ = wait_expired(expired) => {
+                        tracing::warn!("inspect request expired during shutdown");
+                        Err(InspectError::ServiceUnavailable)
+                    }
+                    _ = wait_session_changed(&mut session, &session_id), if default_session => {
+                        tracing::info!("inspect request cancelled (session changed)");
+                        Err(InspectError::SessionChanged {
+                            session_id: session_id.clone(),
+                        })
+                    }
+                    // Dropping the gRPC call cancels it in the server manager
+                    _ = response_tx.closed() => {
+                        tracing::info!("inspect request cancelled (client dropped)");
+                        return;
+                    }
                 };
+                let elapsed = start.elapsed();
+                let threshold = config.slow_inspect_threshold;
+                if !threshold.is_zero() && elapsed > threshold {
+                    if let Some(suppressed) = slow_warnings.check(Instant::now()) {
+                        tracing::warn!(
+                            request_id = %request_id,
+                            payload_len,
+                            elapsed_ms = elapsed.as_millis() as u64,
+                            suppressed,
+                            "inspect slower than the threshold"
+                        );
+                    }
+                }
+                let response = response
+                    .and_then(|response| check_report_limits(&config, response));
+                if let (Some(recorder), Some(payload)) = (&recorder, &recorded_payload) {
+                    recorder.record(RecordedInspect::new(
+                        &session_id,
+                        &request_id,
+                        payload,
+                        &response,
+                        elapsed,
+                    ));
+                }
+                if let (Some((cache, key)), Ok(response)) = (&key, &response) {
+                    cache.lock().unwrap().insert(*key, response);
+                }
+                if respond(response_tx, response) == Delivery::Dropped {
+                    metrics.dropped_response();
+                }
+                // The permit is released when the task finishes, even if the inspect failed.
+                drop(permit);
+            }
+            .instrument(span),
+        );
+    }
+
+    /// Wait until all tasks are finished.
+    async fn wait_idle(&self) {
+        let _permits = self
+            .semaphore
+            .acquire_many(self.config.concurrency as u32)
+            .await
+            .expect("semaphore is never closed");
+    }
+}
+
+/// Message of a caught panic, when it has one.
+fn panic_message(panic: &Box<dyn std::any::Any + Send>) -> &str {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
+
+/// Wait until the shutdown timeout expires.
+async fn wait_expired(mut expired: watch::Receiver<bool>) {
+    if expired.wait_for(|expired| *expired).await.is_err() {
+        // The worker is gone, so the timeout never expires
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Wait until the default session is not the given one.
+async fn wait_session_changed(
+    session: &mut watch::Receiver<String>,
+    session_id: &str,
+) {
+    if session
+        .wait_for(|current| current != session_id)
+        .await
+        .is_err()
+    {
+        // The clients are gone, so the session never changes
+        std::future::pending::<()>().await;
+    }
+}
 
+/// Send an inspect request to the server manager, retrying it with exponential backoff when the
+/// server manager is unreachable.
+/// The retries also take from the retry budget of the worker; once it is exhausted, the inspect
+/// fails without retrying.
+async fn inspect_state(
+    config: &InspectServerConfig,
+    connection: &Connection,
+    session_id: &str,
+    payload: Vec<u8>,
+    request_id: &str,
+) -> Result<InspectStateResponse, InspectError> {
+    let backoff = ExponentialBackoffBuilder::new()
+        .with_initial_interval(config.initial_backoff)
+        .with_max_elapsed_time(None)
+        .build();
+    let attempts = AtomicU32::new(0);
+    retry_notify(
+        backoff,
+        || async {
+            let attempt = attempts.fetch_add(1, Ordering::Relaxed);
+            try_inspect_state(
+                config,
+                connection,
+                session_id,
+                payload.clone(),
+                request_id,
+            )
+            .await
+            .map_err(|e| match e {
+                backoff::Error::Transient { err, .. }
+                    if attempt >= config.max_retries =>
+                {
+                    backoff::Error::permanent(err)
+                }
+                backoff::Error::Transient { err, .. }
+                    if !connection.retry_budget.try_acquire() =>
+                {
+                    tracing::debug!(
+                        "retry budget is exhausted, failing inspect without retrying"
+                    );
+                    backoff::Error::permanent(err)
+                }
+                e => e,
+            })
+        },
+        |e, duration: Duration| {
+            tracing::warn!(
+                attempt = attempts.load(Ordering::Relaxed),
+                backoff_ms = duration.as_millis() as u64,
+                "retrying inspect request: {}",
+                e
+            );
+        },
+    )
+    .await
+}
+
+/// Send a single inspect request to the server manager.
+/// Transport errors are transient, so they can be retried; other errors are permanent.
+/// The failures are logged with an error.kind field: "transport" when the server manager could
+/// not be reached, "application" when it answered with a gRPC status, whose code is in the
+/// error.code field.
+/// With the otel feature, each attempt is a client span of the trace.
+#[cfg_attr(
+    feature = "otel",
+    tracing::instrument(
+        name = "inspect_state",
+        skip_all,
+        fields(otel.kind = "client", rpc.system = "grpc")
+    )
+)]
+async fn try_inspect_state(
+    config: &InspectServerConfig,
+    connection: &Connection,
+    session_id: &str,
+    payload: Vec<u8>,
+    request_id: &str,
+) -> Result<InspectStateResponse, backoff::Error<InspectError>> {
+    if !connection.breaker.try_acquire() {
+        tracing::debug!("circuit breaker is open, rejecting inspect");
+        return Err(backoff::Error::permanent(
+            InspectError::ServiceUnavailable,
+        ));
+    }
+    let (index, mut client) = connection.client().await.map_err(|e| {
+        connection.breaker.record_failure();
+        tracing::warn!(
+            error.kind = "transport",
+            error.message = %e,
+            "failed to connect to server manager"
+        );
+        backoff::Error::transient(e)
+    })?;
+
+    let grpc_request = InspectStateRequest {
+        session_id: session_id.to_string(),
+        query_payload: payload,
+    };
+
+    tracing::debug!(request = ?grpc_request, "calling grpc inspect_state");
+    let mut grpc_request = Request::new(grpc_request);
+    // The client ids are validated before they are queued, so this only skips ids generated here
+    match MetadataValue::<Ascii>::try_from(request_id) {
+        Ok(request_id) => {
+            grpc_request.metadata_mut().insert("request-id", request_id);
+        }
+        Err(_) => tracing::warn!(
+            "request id is not valid gRPC metadata, not forwarding it"
+        ),
+    }
+    if let Some(node_id) = &connection.node_id {
+        grpc_request
+            .metadata_mut()
+            .insert("x-node-id", node_id.clone());
+    }
+    #[cfg(feature = "otel")]
+    crate::otel::inject_context(grpc_request.metadata_mut());
+    let mut grpc_request = connection.intercept(grpc_request);
+    let started = Instant::now();
+    let grpc_response = if config.request_timeout.is_zero() {
+        client.inspect_state(grpc_request).await
+    } else {
+        grpc_request.set_timeout(grpc_timeout(config));
+        tokio::time::timeout(
+            config.request_timeout,
+            client.inspect_state(grpc_request),
+        )
+        .await
+        .map_err(|_| {
+            // The server manager is reachable, it just didn't answer in time
+            connection.breaker.record_success();
+            tracing::warn!("inspect_state timed out");
+            backoff::Error::permanent(InspectError::Timeout {
+                elapsed: config.request_timeout,
+            })
+        })?
+    };
+
+    tracing::debug!(
+        response = ?grpc_response,
+        "got grpc response from inspect_state"
+    );
+
+    match grpc_response {
+        Ok(response) => {
+            connection.breaker.record_success();
+            Ok(response.into_inner())
+        }
+        Err(e) if is_message_too_large(&e) => {
+            connection.breaker.record_success();
+            tracing::warn!("inspect_state response exceeds the size limit");
+            Err(backoff::Error::permanent(InspectError::ResponseTooLarge {
+                details: format!(
+                    "limit: {} bytes",
+                    connection.max_response_bytes
+                ),
+            }))
+        }
+        Err(e) if is_deadline_error(config, &e, started.elapsed()) => {
+            connection.breaker.record_success();
+            tracing::warn!("inspect_state deadline exceeded in server manager");
+            Err(backoff::Error::permanent(InspectError::Timeout {
+                elapsed: config.request_timeout,
+            }))
+        }
+        Err(e) => {
+            let err = status_error(&e, session_id);
+            if is_transport_error(&e) {
+                connection.breaker.record_failure();
+                tracing::warn!(
+                    error.kind = "transport",
+                    error.code = ?e.code(),
+                    error.message = e.message(),
+                    "inspect_state failed to reach the server manager"
+                );
+                connection.reset(index).await;
+                Err(backoff::Error::transient(err))
+            } else {
+                connection.breaker.record_success();
+                tracing::info!(
+                    error.kind = "application",
+                    error.code = ?e.code(),
+                    error.message = e.message(),
+                    "inspect_state failed in the server manager"
+                );
+                Err(backoff::Error::permanent(err))
+            }
+        }
+    }
+}
+
+/// User agent of the connections to the server manager, which tells it the version of the node.
+/// Tonic appends its own user agent to it.
+pub const USER_AGENT: &str =
+    concat!("rollups-node/", env!("CARGO_PKG_VERSION"));
+
+/// Hook applied to the gRPC requests sent to the server manager.
+type Interceptor = Arc<dyn Fn(&mut Request<()>) + Send + Sync>;
+
+/// Pool of connections to the server manager shared by the inspect tasks.
+/// The requests are distributed over the connections in round-robin, so a slow response doesn't
+/// hold the others. Each connection is established lazily and reused across requests. It is only
+/// dropped when a call fails with a transport error, or when it is unused for the idle timeout, so
+/// the next request reconnects.
+/// When there are several server manager endpoints, a connection goes to the first healthy one.
+/// An endpoint that fails to connect or whose connection is dropped is unhealthy for the failover
+/// cooldown, and it is only tried again during that time if all the others fail too.
+struct Connection {
+    endpoints: Vec<ServerManagerEndpoint>,
+    failover_cooldown: Duration,
+    interceptor: Option<Interceptor>,
+    node_id: Option<AsciiMetadataValue>,
+    max_response_bytes: usize,
+    compression: GrpcCompression,
+    connect_timeout: Duration,
+    idle_timeout: Duration,
+    keepalive_interval: Duration,
+    tls_config: Option<TlsConfig>,
+    clients: Vec<Mutex<Option<PooledClient>>>,
+    next: AtomicUsize,
+    /// Counts the new connections, apart from the requests that reuse them
+    metrics: InspectMetrics,
+    breaker: CircuitBreaker,
+    retry_budget: RetryBudget,
+}
+
+/// Client of a connection of the pool.
+struct PooledClient {
+    client: ServerManagerClient<Channel>,
+    /// Index of the endpoint the client is connected to
+    endpoint: usize,
+    last_used: Instant,
+}
+
+/// Server manager replica that the connections may use.
+struct ServerManagerEndpoint {
+    url: String,
+    socket_path: Option<String>,
+    /// Set when the endpoint fails, until the failover cooldown expires
+    unhealthy_until: std::sync::Mutex<Option<Instant>>,
+}
+
+impl ServerManagerEndpoint {
+    fn is_healthy(&self, now: Instant) -> bool {
+        self.unhealthy_until
+            .lock()
+            .unwrap()
+            .map_or(true, |until| now >= until)
+    }
+
+    fn mark_unhealthy(&self, cooldown: Duration) {
+        *self.unhealthy_until.lock().unwrap() = Some(Instant::now() + cooldown);
+    }
+}
+
+impl Connection {
+    fn new(
+        config: &InspectServerConfig,
+        interceptor: Option<Interceptor>,
+        metrics: InspectMetrics,
+    ) -> Self {
+        Self {
+            endpoints: config
+                .server_manager_addresses()
+                .into_iter()
+                .map(|address| ServerManagerEndpoint {
+                    url: config.server_manager_url(address),
+                    socket_path: server_manager_socket_path(address)
+                        .map(String::from),
+                    unhealthy_until: std::sync::Mutex::new(None),
+                })
+                .collect(),
+            failover_cooldown: config.failover_cooldown,
+            interceptor,
+            // The config validation rejects the ids that are not valid metadata
+            node_id: config
+                .node_id
+                .as_deref()
+                .and_then(|node_id| node_id.parse().ok()),
+            max_response_bytes: config.max_response_bytes,
+            compression: config.compression,
+            connect_timeout: config.connect_timeout,
+            idle_timeout: config.idle_timeout,
+            keepalive_interval: config.keepalive_interval,
+            tls_config: config.tls_config.clone(),
+            clients: (0..config.connection_pool_size.max(1))
+                .map(|_| Mutex::new(None))
+                .collect(),
+            next: AtomicUsize::new(0),
+            metrics,
+            breaker: CircuitBreaker::new(
+                config.circuit_breaker_threshold,
+                config.circuit_breaker_cooldown,
+            ),
+            retry_budget: RetryBudget::new(
+                config.retry_budget,
+                config.retry_budget_refill,
+            ),
+        }
+    }
+
+    /// Return the client of the next connection of the pool, connecting to the server manager if
+    /// it is not connected.
+    /// The index of the connection is also returned, so it can be reset.
+    async fn client(
+        &self,
+    ) -> Result<(usize, ServerManagerClient<Channel>), InspectError> {
+        let index =
+            self.next.fetch_add(1, Ordering::Relaxed) % self.clients.len();
+        let mut client = self.clients[index].lock().await;
+        let now = Instant::now();
+        match client.as_mut() {
+            Some(pooled)
+                if !self.idle_timeout.is_zero()
+                    && now.duration_since(pooled.last_used)
+                        >= self.idle_timeout =>
+            {
                 tracing::debug!(
-                    "calling grpc inspect_state request={:?} request_id={}",
-                    grpc_request,
-                    request_id
+                    connection = index,
+                    "dropping idle server manager connection"
                 );
-                let mut grpc_request = Request::new(grpc_request);
-                grpc_request
-                    .metadata_mut()
-                    .insert("request-id", request_id.parse().unwrap());
-                let grpc_response = client.inspect_state(grpc_request).await;
-
-                tracing::debug!("got grpc response from inspect_state response={:?} request_id={}", grpc_response, request_id);
-
-                let response = grpc_response
-                    .map(|result| result.into_inner())
-                    .map_err(|e| InspectError::InspectFailed {
-                        message: e.message().to_string(),
+                *client = None;
+            }
+            Some(pooled) => {
+                pooled.last_used = now;
+                return Ok((index, pooled.client.clone()));
+            }
+            None => {}
+        }
+        let mut last_error = None;
+        for endpoint_index in self.endpoint_order() {
+            let endpoint = &self.endpoints[endpoint_index];
+            match self.connect(endpoint).await {
+                Ok(new_client) => {
+                    self.metrics.connected();
+                    *client = Some(PooledClient {
+                        client: new_client.clone(),
+                        endpoint: endpoint_index,
+                        last_used: now,
                     });
-                respond(request.response_tx, response);
+                    return Ok((index, new_client));
+                }
+                Err(e) => {
+                    self.metrics.connect_failed();
+                    if self.endpoints.len() > 1 {
+                        tracing::warn!(
+                            endpoint = endpoint.url,
+                            "failed to connect to server manager endpoint: {}",
+                            e
+                        );
+                    }
+                    endpoint.mark_unhealthy(self.failover_cooldown);
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.expect("there is at least one endpoint"))
+    }
+
+    /// Indices of the endpoints in the order they should be tried: the healthy ones first, then
+    /// the unhealthy ones, each in the order of the config.
+    fn endpoint_order(&self) -> Vec<usize> {
+        let now = Instant::now();
+        let (mut healthy, unhealthy): (Vec<usize>, Vec<usize>) =
+            (0..self.endpoints.len())
+                .partition(|&index| self.endpoints[index].is_healthy(now));
+        healthy.extend(unhealthy);
+        healthy
+    }
+
+    /// Connect to the server manager endpoint.
+    async fn connect(
+        &self,
+        server_manager: &ServerManagerEndpoint,
+    ) -> Result<ServerManagerClient<Channel>, InspectError> {
+        let endpoint = self.endpoint(&server_manager.url)?;
+        let channel = match &server_manager.socket_path {
+            Some(socket_path) => {
+                let socket_path = socket_path.clone();
+                endpoint
+                    .connect_with_connector(service_fn(move |_: Uri| {
+                        UnixStream::connect(socket_path.clone())
+                    }))
+                    .await
+            }
+            None => endpoint.connect().await,
+        }
+        .map_err(|e| self.connect_error(&e))?;
+        let mut new_client = ServerManagerClient::new(channel)
+            .max_decoding_message_size(self.max_response_bytes);
+        if self.compression == GrpcCompression::Gzip {
+            new_client = new_client
+                .send_compressed(CompressionEncoding::Gzip)
+                .accept_compressed(CompressionEncoding::Gzip);
+        }
+        Ok(new_client)
+    }
+
+    /// Build the endpoint of the server manager, loading the TLS certificates when it is enabled.
+    fn endpoint(&self, url: &str) -> Result<Endpoint, InspectError> {
+        let mut endpoint = Endpoint::from_shared(url.to_string())
+            .and_then(|endpoint| endpoint.user_agent(USER_AGENT))
+            .map_err(|e| self.connect_error(&e))?;
+        if !self.connect_timeout.is_zero() {
+            endpoint = endpoint.connect_timeout(self.connect_timeout);
+        }
+        if !self.keepalive_interval.is_zero() {
+            endpoint = endpoint
+                .http2_keep_alive_interval(self.keepalive_interval)
+                .keep_alive_while_idle(true);
+        }
+        let tls_config = match &self.tls_config {
+            Some(tls_config) => tls_config,
+            None => return Ok(endpoint),
+        };
+        let mut client_tls_config = ClientTlsConfig::new().ca_certificate(
+            Certificate::from_pem(read_pem(&tls_config.ca_cert_path)?),
+        );
+        if let Some((cert_path, key_path)) = &tls_config.client_identity {
+            client_tls_config = client_tls_config.identity(Identity::from_pem(
+                read_pem(cert_path)?,
+                read_pem(key_path)?,
+            ));
+        }
+        if let Some(domain_name) = &tls_config.domain_name {
+            client_tls_config = client_tls_config.domain_name(domain_name);
+        }
+        endpoint
+            .tls_config(client_tls_config)
+            .map_err(|e| self.connect_error(&e))
+    }
+
+    /// Tonic transport errors only display a generic message and keep the cause in the source, so
+    /// the full chain is reported when TLS is enabled to expose handshake failures.
+    fn connect_error(&self, error: &dyn std::error::Error) -> InspectError {
+        let message = match self.tls_config {
+            Some(_) => error_chain(error),
+            None => error.to_string(),
+        };
+        InspectError::FailedToConnect { message }
+    }
+
+    /// Apply the interceptor of the client to a gRPC request.
+    /// The interceptor only sees the metadata and extensions, so it works for any message type.
+    fn intercept<T>(&self, request: Request<T>) -> Request<T> {
+        let interceptor = match &self.interceptor {
+            Some(interceptor) => interceptor,
+            None => return request,
+        };
+        let (metadata, extensions, message) = request.into_parts();
+        let mut parts = Request::from_parts(metadata, extensions, ());
+        interceptor(&mut parts);
+        let (metadata, extensions, ()) = parts.into_parts();
+        Request::from_parts(metadata, extensions, message)
+    }
+
+    /// Drop the client of the given connection so the next request that uses it reconnects,
+    /// preferring another endpoint.
+    async fn reset(&self, index: usize) {
+        tracing::warn!(
+            connection = index,
+            "dropping server manager connection after transport error"
+        );
+        if let Some(pooled) = self.clients[index].lock().await.take() {
+            self.endpoints[pooled.endpoint]
+                .mark_unhealthy(self.failover_cooldown);
+        }
+    }
+}
+
+fn read_pem(path: &str) -> Result<Vec<u8>, InspectError> {
+    std::fs::read(path).map_err(|e| InspectError::FailedToConnect {
+        message: format!("failed to read {}: {}", path, e),
+    })
+}
+
+/// Format the error along with its sources.
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(e) = source {
+        message = format!("{}: {}", message, e);
+        source = e.source();
+    }
+    message
+}
+
+/// Check whether the client rejected the response because it exceeds the max decoding message
+/// size. Tonic reports it as OutOfRange, which the server manager doesn't use for inspects.
+fn is_message_too_large(status: &Status) -> bool {
+    status.code() == Code::OutOfRange
+        && status.message().contains("message length too large")
+}
+
+/// Timeout sent to the server manager, so it gives up slightly before the client does.
+fn grpc_timeout(config: &InspectServerConfig) -> Duration {
+    config.request_timeout * 9 / 10
+}
+
+/// Check whether the server manager gave up because of the grpc-timeout sent along the request.
+/// Tonic servers report an expired deadline as cancelled instead of deadline exceeded, so a
+/// cancellation is only a deadline when the call took at least the grpc-timeout; an earlier one,
+/// such as an abort in the server manager, is reported as is.
+fn is_deadline_error(
+    config: &InspectServerConfig,
+    status: &Status,
+    elapsed: Duration,
+) -> bool {
+    if config.request_timeout.is_zero() {
+        return false;
+    }
+    match status.code() {
+        Code::DeadlineExceeded => true,
+        Code::Cancelled => elapsed >= grpc_timeout(config),
+        _ => false,
+    }
+}
+
+/// Check whether the gRPC call failed because of the underlying connection instead of an error
+/// returned by the server manager.
+fn is_transport_error(status: &Status) -> bool {
+    status.code() == Code::Unavailable
+        || std::error::Error::source(status)
+            .map_or(false, |source| source.is::<tonic::transport::Error>())
+}
+
+/// Map the gRPC status returned by the server manager to the inspect error that represents it.
+/// Codes without a dedicated error are reported as InspectFailed.
+/// The server manager only answers NotFound when the session of the request doesn't exist.
+fn status_error(status: &Status, session_id: &str) -> InspectError {
+    let message = status.message().to_string();
+    match status.code() {
+        Code::NotFound => {
+            tracing::debug!("session not found: {}", message);
+            InspectError::SessionNotFound {
+                session_id: session_id.to_string(),
             }
         }
+        Code::InvalidArgument => InspectError::InvalidArgument { message },
+        Code::Internal => InspectError::ServerManagerInternal { message },
+        code => {
+            tracing::debug!(
+                ?code,
+                details_len = status.details().len(),
+                "inspect failed: {}",
+                message
+            );
+            InspectError::InspectFailed {
+                message,
+                code,
+                details: status.details().to_vec(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response() -> InspectStateResponse {
+        InspectStateResponse {
+            session_id: String::from("default"),
+            active_epoch_index: 0,
+            processed_input_count: 0,
+            status: CompletionStatus::Accepted as i32,
+            exception_data: None,
+            reports: vec![],
+        }
+    }
+
+    #[test]
+    fn it_reports_a_delivered_response() {
+        let (response_tx, mut response_rx) = oneshot::channel();
+        assert_eq!(respond(response_tx, Ok(response())), Delivery::Delivered);
+        let delivered =
+            response_rx.try_recv().expect("response should be sent");
+        assert_eq!(delivered.expect("response should be ok"), response());
+    }
+
+    #[test]
+    fn it_reports_a_dropped_response() {
+        let (response_tx, response_rx) = oneshot::channel();
+        drop(response_rx);
+        assert_eq!(respond(response_tx, Ok(response())), Delivery::Dropped);
+        // The repeated warnings are rate limited, but the drop is still reported
+        let (response_tx, response_rx) = oneshot::channel();
+        drop(response_rx);
+        assert_eq!(respond(response_tx, Ok(response())), Delivery::Dropped);
+    }
+
+    #[test]
+    fn it_counts_the_suppressed_warnings() {
+        let limiter = WarningLimiter::new(Duration::from_secs(1));
+        let now = Instant::now();
+        assert_eq!(limiter.check(now), Some(0));
+        assert_eq!(limiter.check(now + Duration::from_millis(500)), None);
+        assert_eq!(limiter.check(now + Duration::from_millis(900)), None);
+        assert_eq!(limiter.check(now + Duration::from_secs(1)), Some(2));
+    }
+
+    #[test]
+    fn it_maps_every_completion_status_to_http_status() {
+        let expected = [
+            (CompletionStatus::Accepted, 200),
+            (CompletionStatus::Rejected, 422),
+            (CompletionStatus::Exception, 422),
+            (CompletionStatus::MachineHalted, 400),
+            (CompletionStatus::CycleLimitExceeded, 408),
+            (CompletionStatus::TimeLimitExceeded, 408),
+            (CompletionStatus::PayloadLengthLimitExceeded, 413),
+        ];
+        for (status, http_status) in expected {
+            assert_eq!(http_status_for(status), http_status);
+        }
+        // Fail if the gRPC interface gets a status that is not listed above
+        let count = (0..)
+            .take_while(|&i| CompletionStatus::from_i32(i).is_some())
+            .count();
+        assert_eq!(count, expected.len());
+    }
+
+    #[test]
+    fn it_builds_summary_from_response() {
+        let response = InspectStateResponse {
+            session_id: String::from("session"),
+            active_epoch_index: 1,
+            processed_input_count: 42,
+            status: CompletionStatus::Exception as i32,
+            exception_data: Some(b"exception".to_vec()),
+            reports: vec![
+                Report {
+                    payload: b"first".to_vec(),
+                },
+                Report {
+                    payload: b"second".to_vec(),
+                },
+            ],
+        };
+        let summary = InspectSummary::from(response);
+        assert_eq!(summary.status, Some(CompletionStatus::Exception));
+        assert_eq!(
+            summary.reports,
+            vec![b"first".to_vec(), b"second".to_vec()]
+        );
+        assert_eq!(summary.exception_payload, Some(b"exception".to_vec()));
+        assert_eq!(summary.processed_input_count, 42);
+    }
+
+    #[test]
+    fn it_filters_reports_by_range() {
+        let filter = |start_index, count| {
+            let mut filtered = reports(&[b"a", b"b", b"c"]);
+            ReportFilter { start_index, count }.apply(&mut filtered);
+            filtered
+        };
+        assert_eq!(filter(1, 1), reports(&[b"b"]));
+        assert_eq!(filter(0, 3), reports(&[b"a", b"b", b"c"]));
+        assert_eq!(filter(2, 5), reports(&[b"c"]));
+        assert_eq!(filter(3, 1), reports(&[]));
+        assert_eq!(filter(10, 1), reports(&[]));
+        assert_eq!(filter(0, 0), reports(&[]));
+    }
+
+    #[test]
+    fn it_builds_summary_with_unknown_status() {
+        let response = InspectStateResponse {
+            status: -1,
+            ..Default::default()
+        };
+        assert_eq!(InspectSummary::from(response).status, None);
+    }
+
+    fn reports(payloads: &[&[u8]]) -> Vec<Report> {
+        payloads
+            .iter()
+            .map(|payload| Report {
+                payload: payload.to_vec(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn it_encodes_reports_as_hex() {
+        let reports = reports(&[b"hello", b""]);
+        let encoded = encode_reports(&reports, PayloadEncoding::Hex);
+        assert_eq!(encoded, vec!["0x68656c6c6f", "0x"]);
+        let decoded: Vec<Vec<u8>> = encoded
+            .iter()
+            .map(|payload| hex::decode(&payload[2..]).unwrap())
+            .collect();
+        assert_eq!(decoded, vec![b"hello".to_vec(), vec![]]);
+    }
+
+    #[test]
+    fn it_encodes_reports_as_base64() {
+        let reports = reports(&[b"hello", b""]);
+        let encoded = encode_reports(&reports, PayloadEncoding::Base64);
+        assert_eq!(encoded, vec!["aGVsbG8=", ""]);
+        let decoded: Vec<Vec<u8>> = encoded
+            .iter()
+            .map(|payload| base64_engine.decode(payload).unwrap())
+            .collect();
+        assert_eq!(decoded, vec![b"hello".to_vec(), vec![]]);
+    }
+
+    #[test]
+    fn it_decodes_hex_payloads() {
+        let reports = reports(&[b"hello", b""]);
+        let encoded = encode_reports(&reports, PayloadEncoding::Hex);
+        for (payload, report) in encoded.iter().zip(&reports) {
+            assert_eq!(decode_payload(payload).unwrap(), report.payload);
+            assert_eq!(decode_payload(&payload[2..]).unwrap(), report.payload);
+        }
+        assert_eq!(decode_payload("0x68656C6C6F").unwrap(), b"hello");
+        assert_eq!(decode_payload("").unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn it_decodes_base64_payloads() {
+        let reports = reports(&[b"hello", b""]);
+        let encoded = encode_reports(&reports, PayloadEncoding::Base64);
+        for (payload, report) in encoded.iter().zip(&reports) {
+            let payload = String::from(BASE64_PAYLOAD_PREFIX) + payload;
+            assert_eq!(decode_payload(&payload).unwrap(), report.payload);
+        }
+    }
+
+    #[test]
+    fn it_rejects_malformed_payloads() {
+        for input in ["0x123", "0xzz", "hello", "0x 00", "base64:a", "base64:*"]
+        {
+            let err = decode_payload(input).expect_err("payload is malformed");
+            assert!(
+                matches!(err, InspectError::InvalidArgument { .. }),
+                "unexpected error {:?} for {:?}",
+                err,
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn it_encodes_reports_as_utf8_lossy() {
+        let reports = reports(&[b"hello", b"", b"\xff"]);
+        let encoded = encode_reports(&reports, PayloadEncoding::Utf8Lossy);
+        assert_eq!(encoded, vec!["hello", "", "\u{fffd}"]);
+        assert_eq!(encoded[0].as_bytes(), b"hello");
     }
 }
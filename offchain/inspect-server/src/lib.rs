@@ -4,20 +4,69 @@
 use error::InspectError;
 use snafu::ResultExt;
 
-pub use config::InspectServerConfig;
+pub use config::{InspectServerConfig, InspectServerConfigBuilder};
 pub use inspect::InspectClient;
 
+mod cache;
+mod circuit_breaker;
 pub mod config;
-mod error;
+mod dedup;
+pub mod error;
 pub mod inspect;
+mod load_shedding;
+pub mod metrics;
+#[cfg(feature = "otel")]
+pub mod otel;
+mod queue;
+mod rate_limit;
+pub mod recorder;
+pub mod reports;
+mod retry_budget;
 pub mod server;
+mod session_limit;
+#[cfg(feature = "test-utils")]
+pub mod testing;
 
 #[tracing::instrument(level = "trace", skip_all)]
 pub async fn run(config: InspectServerConfig) -> Result<(), InspectError> {
     let health_handle = http_health_check::start(config.healthcheck_port);
     let inspect_client = InspectClient::new(&config);
-    let inspect_server =
-        server::create(&config, inspect_client).context(error::ServerSnafu)?;
+    let ready = inspect_client
+        .wait_until_ready(config.readiness_interval, config.readiness_timeout)
+        .await;
+    if !ready {
+        tracing::warn!(
+            "server manager is not ready after {:?}; starting anyway",
+            config.readiness_timeout
+        );
+    }
+    if config.verify_session {
+        match inspect_client.check_session().await {
+            Ok(()) => {}
+            Err(e @ InspectError::SessionNotFound { .. }) => {
+                tracing::error!(
+                    "{}; every inspect request will fail until the session is started",
+                    e
+                );
+            }
+            Err(e) => {
+                tracing::warn!("failed to verify inspect session: {}", e);
+            }
+        }
+    }
+    if config.warm_up {
+        warm_up(&config, &inspect_client).await?;
+    }
+    let inspect_server = server::create(&config, inspect_client.clone())
+        .context(error::ServerSnafu)?;
+    let server_handle = inspect_server.handle();
+    tokio::spawn(async move {
+        wait_for_signal().await;
+        tracing::info!("shutting down inspect server");
+        // Stop accepting HTTP requests and let the queued inspects finish
+        inspect_client.shutdown();
+        server_handle.stop(true).await;
+    });
     tokio::select! {
         ret = health_handle => {
             ret.context(error::HealthCheckSnafu)
@@ -27,3 +76,49 @@ pub async fn run(config: InspectServerConfig) -> Result<(), InspectError> {
         }
     }
 }
+
+/// Send the warm-up inspect of the config, which establishes the connection to the server manager
+/// before the HTTP server accepts requests.
+/// A failure is only logged unless the warm-up is required.
+async fn warm_up(
+    config: &InspectServerConfig,
+    inspect_client: &InspectClient,
+) -> Result<(), InspectError> {
+    let start = std::time::Instant::now();
+    let result = inspect_client.inspect(config.warm_up_payload.clone()).await;
+    match result {
+        // An exception in the machine still warms up the connection
+        Ok(_) | Err(InspectError::MachineException { .. }) => {
+            tracing::info!("warm-up inspect finished in {:?}", start.elapsed());
+            Ok(())
+        }
+        Err(e) if config.warm_up_required => {
+            tracing::error!("warm-up inspect failed: {}", e);
+            Err(e)
+        }
+        Err(e) => {
+            tracing::warn!("warm-up inspect failed: {}", e);
+            Ok(())
+        }
+    }
+}
+
+/// Wait for SIGINT or SIGTERM.
+async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut sigterm = signal(SignalKind::terminate())
+            .expect("failed to listen for SIGTERM");
+        tokio::select! {
+            ret = tokio::signal::ctrl_c() => {
+                ret.expect("failed to listen for SIGINT");
+            }
+            _ = sigterm.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c()
+        .await
+        .expect("failed to listen for SIGINT");
+}
@@ -0,0 +1,74 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+/// Probabilistic load shedding of the inspect requests when the queue is getting full.
+/// Below the threshold (a fraction of the queue capacity) every request is accepted. Above it, a
+/// request is rejected with a probability that grows linearly from 0 at the threshold to 1 at the
+/// full queue, so the queue doesn't stay pinned at full under sustained overload. A threshold of
+/// zero disables the shedding.
+pub struct LoadShedder {
+    threshold: f64,
+}
+
+impl LoadShedder {
+    pub fn new(threshold: f64) -> Self {
+        Self { threshold }
+    }
+
+    /// Decide whether to reject a request with the queue at len of capacity.
+    pub fn should_shed(&self, len: usize, capacity: usize) -> bool {
+        let probability = self.probability(len, capacity);
+        probability > 0.0 && rand::random::<f64>() < probability
+    }
+
+    fn probability(&self, len: usize, capacity: usize) -> f64 {
+        if self.threshold <= 0.0 || capacity == 0 {
+            return 0.0;
+        }
+        let fill = len as f64 / capacity as f64;
+        ((fill - self.threshold) / (1.0 - self.threshold)).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAPACITY: usize = 100;
+    const SAMPLES: usize = 10_000;
+
+    fn shed_rate(shedder: &LoadShedder, len: usize) -> f64 {
+        let shed = (0..SAMPLES)
+            .filter(|_| shedder.should_shed(len, CAPACITY))
+            .count();
+        shed as f64 / SAMPLES as f64
+    }
+
+    #[test]
+    fn it_never_sheds_below_the_threshold() {
+        let shedder = LoadShedder::new(0.8);
+        assert_eq!(shed_rate(&shedder, 0), 0.0);
+        assert_eq!(shed_rate(&shedder, 80), 0.0);
+        assert_eq!(shed_rate(&shedder, CAPACITY), 1.0);
+    }
+
+    #[test]
+    fn it_sheds_more_as_the_queue_fills() {
+        let shedder = LoadShedder::new(0.8);
+        let rates: Vec<f64> = [85, 90, 95]
+            .iter()
+            .map(|&len| shed_rate(&shedder, len))
+            .collect();
+        // The expected rates are 0.25, 0.5, and 0.75; the tolerance is about 10 deviations
+        for (rate, expected) in rates.iter().zip([0.25, 0.5, 0.75]) {
+            assert!((rate - expected).abs() < 0.05, "rate: {}", rate);
+        }
+        assert!(rates.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn it_never_sheds_when_disabled() {
+        let shedder = LoadShedder::new(0.0);
+        assert_eq!(shed_rate(&shedder, CAPACITY), 0.0);
+    }
+}
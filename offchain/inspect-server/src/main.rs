@@ -1,17 +1,25 @@
 // (c) Cartesi and individual authors (see AUTHORS)
 // SPDX-License-Identifier: Apache-2.0 (see LICENSE)
 
-use clap::Parser;
-
-use inspect_server::{config::CLIConfig, InspectServerConfig};
+use inspect_server::config::{CLIConfig, InspectServerConfigBuilder};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let config: InspectServerConfig = CLIConfig::parse().into();
+    let config =
+        InspectServerConfigBuilder::from(CLIConfig::parse_with_sources())
+            .build()?;
 
-    log::configure(&config.log_config);
+    #[cfg(feature = "otel")]
+    let layers = inspect_server::otel::layer()?.into_iter().collect();
+    #[cfg(not(feature = "otel"))]
+    let layers = Vec::new();
+    log::configure_with_layers(&config.log_config, layers);
 
     log::log_service_start(&config, "Inspect Server");
+    config.log_effective();
 
-    inspect_server::run(config).await.map_err(|e| e.into())
+    let result = inspect_server::run(config).await;
+    #[cfg(feature = "otel")]
+    inspect_server::otel::shutdown();
+    result.map_err(|e| e.into())
 }
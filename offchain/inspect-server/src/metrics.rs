@@ -0,0 +1,318 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+//! Metrics of the inspect requests.
+//! The metrics are only recorded when the metrics feature is enabled; otherwise, the methods of
+//! InspectMetrics only notify the queue observer.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(feature = "metrics")]
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{
+        counter::Counter,
+        family::Family,
+        gauge::Gauge,
+        histogram::{exponential_buckets, linear_buckets, Histogram},
+    },
+    registry::Registry,
+};
+
+use crate::error::InspectError;
+use crate::inspect::{CompletionStatus, InspectStateResponse};
+
+#[cfg(feature = "metrics")]
+const METRICS_PREFIX: &str = "cartesi_rollups_inspect_server";
+
+#[cfg(feature = "metrics")]
+fn prefixed_metrics(name: &str) -> String {
+    format!("{}_{}", METRICS_PREFIX, name)
+}
+
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct OutcomeLabels {
+    outcome: &'static str,
+}
+
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct BatchLabels {
+    /// Most frequent error of the batch, or None when all the inspects succeeded
+    dominant_error: &'static str,
+}
+
+/// Observer of the inspect queue, to report it to a monitoring system other than Prometheus.
+/// The methods are called while handling the inspect requests, so they must return quickly and
+/// never block.
+pub trait QueueObserver: Send + Sync {
+    /// Called after a request is added to the queue, with the queue length and capacity.
+    fn enqueued(&self, _len: usize, _capacity: usize) {}
+
+    /// Called after a request is removed from the queue, with the queue length and capacity.
+    fn dequeued(&self, _len: usize, _capacity: usize) {}
+}
+
+/// Queue observer that ignores the events.
+pub struct NoopQueueObserver;
+
+impl QueueObserver for NoopQueueObserver {}
+
+#[derive(Clone)]
+pub struct InspectMetrics {
+    #[cfg(feature = "metrics")]
+    latency: Histogram,
+    #[cfg(feature = "metrics")]
+    outcomes: Family<OutcomeLabels, Counter>,
+    #[cfg(feature = "metrics")]
+    queue_depth: Gauge,
+    #[cfg(feature = "metrics")]
+    dropped_responses: Counter,
+    #[cfg(feature = "metrics")]
+    connects: Counter,
+    #[cfg(feature = "metrics")]
+    connect_failures: Counter,
+    #[cfg(feature = "metrics")]
+    batch_success_ratio: Histogram,
+    #[cfg(feature = "metrics")]
+    batch_items: Family<BatchLabels, Counter>,
+    #[cfg(feature = "metrics")]
+    batch_failed_items: Family<BatchLabels, Counter>,
+    queue_len: Arc<AtomicUsize>,
+    queue_capacity: usize,
+    observer: Arc<dyn QueueObserver>,
+}
+
+impl InspectMetrics {
+    pub fn new(
+        queue_capacity: usize,
+        observer: Arc<dyn QueueObserver>,
+    ) -> Self {
+        Self {
+            // From 1ms to about 32s
+            #[cfg(feature = "metrics")]
+            latency: Histogram::new(exponential_buckets(0.001, 2.0, 16)),
+            #[cfg(feature = "metrics")]
+            outcomes: Family::default(),
+            #[cfg(feature = "metrics")]
+            queue_depth: Gauge::default(),
+            #[cfg(feature = "metrics")]
+            dropped_responses: Counter::default(),
+            #[cfg(feature = "metrics")]
+            connects: Counter::default(),
+            #[cfg(feature = "metrics")]
+            connect_failures: Counter::default(),
+            // From 0 to 1 in steps of 0.1
+            #[cfg(feature = "metrics")]
+            batch_success_ratio: Histogram::new(linear_buckets(0.0, 0.1, 11)),
+            #[cfg(feature = "metrics")]
+            batch_items: Family::default(),
+            #[cfg(feature = "metrics")]
+            batch_failed_items: Family::default(),
+            queue_len: Arc::new(AtomicUsize::new(0)),
+            queue_capacity,
+            observer,
+        }
+    }
+
+    /// Record the latency (from enqueue to response) and the outcome of an inspect request.
+    pub fn observe(
+        &self,
+        result: &Result<InspectStateResponse, InspectError>,
+        elapsed: Duration,
+    ) {
+        #[cfg(feature = "metrics")]
+        {
+            self.latency.observe(elapsed.as_secs_f64());
+            self.outcomes
+                .get_or_create(&OutcomeLabels {
+                    outcome: outcome(result),
+                })
+                .inc();
+        }
+        #[cfg(not(feature = "metrics"))]
+        let _ = (result, elapsed);
+    }
+
+    /// Record the fraction of the inspects of a batch that succeeded and the number of inspects and
+    /// failures, labeled by the most frequent error. An empty batch is not recorded.
+    pub fn observe_batch(
+        &self,
+        results: &[Result<InspectStateResponse, InspectError>],
+    ) {
+        #[cfg(feature = "metrics")]
+        {
+            if results.is_empty() {
+                return;
+            }
+            let (failed, dominant_error) = dominant_error(results);
+            let labels = BatchLabels { dominant_error };
+            let total = results.len();
+            self.batch_success_ratio
+                .observe((total - failed) as f64 / total as f64);
+            self.batch_items.get_or_create(&labels).inc_by(total as u64);
+            self.batch_failed_items
+                .get_or_create(&labels)
+                .inc_by(failed as u64);
+        }
+        #[cfg(not(feature = "metrics"))]
+        let _ = results;
+    }
+
+    /// Record that a request was added to the inspect queue.
+    pub fn enqueued(&self) {
+        #[cfg(feature = "metrics")]
+        self.queue_depth.inc();
+        let len = self.queue_len.fetch_add(1, Ordering::Relaxed) + 1;
+        self.observer.enqueued(len, self.queue_capacity);
+    }
+
+    /// Record that a request was removed from the inspect queue.
+    pub fn dequeued(&self) {
+        #[cfg(feature = "metrics")]
+        self.queue_depth.dec();
+        let len = self.queue_len.fetch_sub(1, Ordering::Relaxed) - 1;
+        self.observer.dequeued(len, self.queue_capacity);
+    }
+
+    /// Record that the client was gone when the response of its inspect was ready.
+    pub fn dropped_response(&self) {
+        #[cfg(feature = "metrics")]
+        self.dropped_responses.inc();
+    }
+
+    /// Record that a new connection to the server manager was established.
+    pub fn connected(&self) {
+        #[cfg(feature = "metrics")]
+        self.connects.inc();
+    }
+
+    /// Record that connecting to a server manager endpoint failed.
+    pub fn connect_failed(&self) {
+        #[cfg(feature = "metrics")]
+        self.connect_failures.inc();
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl From<InspectMetrics> for Registry {
+    fn from(metrics: InspectMetrics) -> Self {
+        let mut registry = Registry::default();
+        registry.register(
+            prefixed_metrics("inspect_latency_seconds"),
+            "Latency of the inspect requests from enqueue to response",
+            metrics.latency,
+        );
+        registry.register(
+            prefixed_metrics("inspect_outcomes"),
+            "Counts the inspect requests by completion status or error",
+            metrics.outcomes,
+        );
+        registry.register(
+            prefixed_metrics("inspect_queue_depth"),
+            "Number of inspect requests waiting in the queue",
+            metrics.queue_depth,
+        );
+        registry.register(
+            prefixed_metrics("inspect_dropped_responses"),
+            "Counts the inspect responses that were ready after the client was gone",
+            metrics.dropped_responses,
+        );
+        registry.register(
+            prefixed_metrics("server_manager_connects"),
+            "Counts the connections established to the server manager, without the reused ones",
+            metrics.connects,
+        );
+        registry.register(
+            prefixed_metrics("server_manager_connect_failures"),
+            "Counts the failed attempts to connect to a server manager endpoint",
+            metrics.connect_failures,
+        );
+        registry.register(
+            prefixed_metrics("inspect_batch_success_ratio"),
+            "Fraction of the inspects of each batch that succeeded",
+            metrics.batch_success_ratio,
+        );
+        registry.register(
+            prefixed_metrics("inspect_batch_items"),
+            "Counts the inspects sent in batches by the most frequent error of their batch",
+            metrics.batch_items,
+        );
+        registry.register(
+            prefixed_metrics("inspect_batch_failed_items"),
+            "Counts the inspects that failed in batches by the most frequent error of their batch",
+            metrics.batch_failed_items,
+        );
+        registry
+    }
+}
+
+/// Number of failures of a batch and the name of its most frequent error; the first one to reach
+/// the count wins a tie.
+#[cfg(feature = "metrics")]
+fn dominant_error(
+    results: &[Result<InspectStateResponse, InspectError>],
+) -> (usize, &'static str) {
+    // There are only a few error variants, so a list is enough
+    let mut counts: Vec<(&'static str, usize)> = vec![];
+    for result in results.iter().filter(|result| result.is_err()) {
+        let name = outcome(result);
+        match counts.iter_mut().find(|(error, _)| *error == name) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((name, 1)),
+        }
+    }
+    let failed = counts.iter().map(|(_, count)| count).sum();
+    let dominant = counts
+        .iter()
+        .fold(
+            None::<(&'static str, usize)>,
+            |dominant, &(error, count)| match dominant {
+                Some((_, max)) if max >= count => dominant,
+                _ => Some((error, count)),
+            },
+        )
+        .map_or("None", |(error, _)| error);
+    (failed, dominant)
+}
+
+/// Name of the completion status or error of an inspect request.
+pub(crate) fn outcome(
+    result: &Result<InspectStateResponse, InspectError>,
+) -> &'static str {
+    match result {
+        Ok(response) => match CompletionStatus::from_i32(response.status) {
+            Some(CompletionStatus::Accepted) => "Accepted",
+            Some(CompletionStatus::Rejected) => "Rejected",
+            Some(CompletionStatus::Exception) => "Exception",
+            Some(CompletionStatus::MachineHalted) => "MachineHalted",
+            Some(CompletionStatus::CycleLimitExceeded) => "CycleLimitExceeded",
+            Some(CompletionStatus::TimeLimitExceeded) => "TimeLimitExceeded",
+            Some(CompletionStatus::PayloadLengthLimitExceeded) => {
+                "PayloadLengthLimitExceeded"
+            }
+            None => "Unknown",
+        },
+        Err(InspectError::HealthCheckError { .. }) => "HealthCheckError",
+        Err(InspectError::ServerError { .. }) => "ServerError",
+        Err(InspectError::FailedToConnect { .. }) => "FailedToConnect",
+        Err(InspectError::InspectFailed { .. }) => "InspectFailed",
+        Err(InspectError::InvalidArgument { .. }) => "InvalidArgument",
+        Err(InspectError::MachineException { .. }) => "MachineException",
+        Err(InspectError::SessionNotFound { .. }) => "SessionNotFound",
+        Err(InspectError::SessionChanged { .. }) => "SessionChanged",
+        Err(InspectError::ServerManagerInternal { .. }) => {
+            "ServerManagerInternal"
+        }
+        Err(InspectError::PayloadTooLarge { .. }) => "PayloadTooLarge",
+        Err(InspectError::ResponseTooLarge { .. }) => "ResponseTooLarge",
+        Err(InspectError::ReportsNotFound { .. }) => "ReportsNotFound",
+        Err(InspectError::QueueFull { .. }) => "QueueFull",
+        Err(InspectError::RateLimited { .. }) => "RateLimited",
+        Err(InspectError::ServiceUnavailable) => "ServiceUnavailable",
+        Err(InspectError::Timeout { .. }) => "Timeout",
+    }
+}
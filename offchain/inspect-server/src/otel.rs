@@ -0,0 +1,69 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+//! Export of the inspect spans to an OpenTelemetry collector over OTLP.
+//! The W3C trace context of the HTTP requests is extracted by the tracing-actix-web root span, and
+//! it is injected in the metadata of the inspect_state calls, so the server manager spans are part
+//! of the same trace.
+
+use opentelemetry::propagation::Injector;
+use opentelemetry::sdk::propagation::TraceContextPropagator;
+use opentelemetry::trace::TraceError;
+use opentelemetry::{global, runtime};
+use opentelemetry_otlp::WithExportConfig;
+use tonic::metadata::{MetadataKey, MetadataMap, MetadataValue};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::Layer;
+
+/// Environment variable of the OTLP endpoint; the spans are only exported when it is set.
+/// The other OTEL_EXPORTER_OTLP_* variables, such as the protocol and the timeout, and
+/// OTEL_SERVICE_NAME are read by the exporter as well.
+pub const OTLP_ENDPOINT_ENV: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+
+/// Create the layer that exports the spans, or None if the OTLP endpoint is not set.
+/// It must be called inside the tokio runtime, which runs the batch exporter.
+pub fn layer() -> Result<Option<log::BoxedLayer>, TraceError> {
+    if std::env::var_os(OTLP_ENDPOINT_ENV).is_none() {
+        return Ok(None);
+    }
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_env())
+        .install_batch(runtime::Tokio)?;
+    Ok(Some(
+        tracing_opentelemetry::layer().with_tracer(tracer).boxed(),
+    ))
+}
+
+/// Export the spans that are still buffered; call it before exiting.
+pub fn shutdown() {
+    global::shutdown_tracer_provider();
+}
+
+/// Add the trace context of the current span to the metadata of a gRPC request.
+/// It adds nothing when the spans are not exported.
+pub(crate) fn inject_context(metadata: &mut MetadataMap) {
+    let context = tracing::Span::current().context();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut MetadataInjector(metadata))
+    });
+}
+
+struct MetadataInjector<'a>(&'a mut MetadataMap);
+
+impl Injector for MetadataInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        match (
+            MetadataKey::from_bytes(key.as_bytes()),
+            MetadataValue::try_from(&value),
+        ) {
+            (Ok(key), Ok(value)) => {
+                self.0.insert(key, value);
+            }
+            _ => {
+                tracing::debug!(key, "trace context is not valid gRPC metadata")
+            }
+        }
+    }
+}
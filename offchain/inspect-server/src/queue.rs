@@ -0,0 +1,327 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::error::{SendError, TrySendError};
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore, TryAcquireError};
+
+/// Priority of an inspect request in the queue.
+/// When the queue is backed up, the requests with a higher priority are taken first, e.g. the
+/// interactive ones before the background ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+impl Priority {
+    const ALL: [Priority; 3] =
+        [Priority::High, Priority::Normal, Priority::Low];
+
+    fn rank(self) -> u32 {
+        self as u32
+    }
+}
+
+/// Bounded queue that works like a tokio mpsc channel with a single receiver, except that the
+/// receiver takes the items by priority instead of in the order they were sent.
+/// The items of the same priority are taken in order. To prevent the starvation of the lower
+/// priorities, an item waiting in the queue is promoted by one priority for each aging interval
+/// it waited; a zero interval disables the aging.
+pub fn channel<T>(
+    capacity: usize,
+    aging: Duration,
+) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        queues: Mutex::new(Queues::new(aging)),
+        capacity: Arc::new(Semaphore::new(capacity)),
+        max_capacity: capacity,
+        senders: AtomicUsize::new(1),
+        item_ready: Notify::new(),
+    });
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+struct Shared<T> {
+    queues: Mutex<Queues<T>>,
+    /// One permit for each free slot; closed when the receiver is closed
+    capacity: Arc<Semaphore>,
+    max_capacity: usize,
+    senders: AtomicUsize,
+    item_ready: Notify,
+}
+
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Reserve a slot in the queue, failing if it is full or closed.
+    pub fn try_reserve(&self) -> Result<Permit<'_, T>, TrySendError<()>> {
+        match self.shared.capacity.clone().try_acquire_owned() {
+            Ok(slot) => Ok(Permit { sender: self, slot }),
+            Err(TryAcquireError::NoPermits) => Err(TrySendError::Full(())),
+            Err(TryAcquireError::Closed) => Err(TrySendError::Closed(())),
+        }
+    }
+
+    /// Wait for a slot in the queue, failing if it is closed.
+    pub async fn reserve(&self) -> Result<Permit<'_, T>, SendError<()>> {
+        match self.shared.capacity.clone().acquire_owned().await {
+            Ok(slot) => Ok(Permit { sender: self, slot }),
+            Err(_) => Err(SendError(())),
+        }
+    }
+
+    /// Number of free slots in the queue.
+    pub fn capacity(&self) -> usize {
+        self.shared.capacity.available_permits()
+    }
+
+    pub fn max_capacity(&self) -> usize {
+        self.shared.max_capacity
+    }
+
+    /// Whether the receiver was closed or dropped.
+    pub fn is_closed(&self) -> bool {
+        self.shared.capacity.is_closed()
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            // Wake up the receiver, so it returns None once the queue is empty
+            self.shared.item_ready.notify_one();
+        }
+    }
+}
+
+/// Slot reserved in the queue, which is released if the permit is dropped without sending.
+pub struct Permit<'a, T> {
+    sender: &'a Sender<T>,
+    slot: OwnedSemaphorePermit,
+}
+
+impl<T> Permit<'_, T> {
+    pub fn send(self, priority: Priority, item: T) {
+        let shared = &self.sender.shared;
+        shared.queues.lock().unwrap().push(
+            priority,
+            Entry {
+                item,
+                enqueued_at: Instant::now(),
+                _slot: self.slot,
+            },
+        );
+        shared.item_ready.notify_one();
+    }
+}
+
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// Take the item with the highest priority, waiting for one if the queue is empty.
+    /// Return None when the queue is empty and either closed or without senders.
+    pub async fn recv(&mut self) -> Option<T> {
+        loop {
+            if let Some(item) =
+                self.shared.queues.lock().unwrap().pop(Instant::now())
+            {
+                return Some(item);
+            }
+            if self.shared.capacity.is_closed()
+                || self.shared.senders.load(Ordering::Acquire) == 0
+            {
+                return None;
+            }
+            // A notification sent before this point is stored, so it is not missed
+            self.shared.item_ready.notified().await;
+        }
+    }
+
+    /// Stop accepting items; the ones already in the queue can still be received.
+    pub fn close(&mut self) {
+        self.shared.capacity.close();
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+struct Entry<T> {
+    item: T,
+    enqueued_at: Instant,
+    /// Released when the item leaves the queue
+    _slot: OwnedSemaphorePermit,
+}
+
+/// One FIFO queue for each priority.
+/// The oldest item of each queue is the one that aged the most, so only the fronts are compared.
+struct Queues<T> {
+    aging: Duration,
+    queues: [VecDeque<Entry<T>>; 3],
+}
+
+impl<T> Queues<T> {
+    fn new(aging: Duration) -> Self {
+        Self {
+            aging,
+            queues: Default::default(),
+        }
+    }
+
+    fn push(&mut self, priority: Priority, entry: Entry<T>) {
+        self.queues[priority.rank() as usize].push_back(entry);
+    }
+
+    fn pop(&mut self, now: Instant) -> Option<T> {
+        let (index, _) = Priority::ALL
+            .iter()
+            .enumerate()
+            .filter_map(|(index, priority)| {
+                let entry = self.queues[index].front()?;
+                let rank = self.effective_rank(*priority, entry, now);
+                Some((index, (rank, entry.enqueued_at)))
+            })
+            .min_by_key(|(_, key)| *key)?;
+        self.queues[index].pop_front().map(|entry| entry.item)
+    }
+
+    fn effective_rank(
+        &self,
+        priority: Priority,
+        entry: &Entry<T>,
+        now: Instant,
+    ) -> u32 {
+        if self.aging.is_zero() {
+            return priority.rank();
+        }
+        let waited = now.saturating_duration_since(entry.enqueued_at);
+        let promotions = waited.as_nanos() / self.aging.as_nanos();
+        priority
+            .rank()
+            .saturating_sub(promotions.min(u32::MAX as u128) as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AGING: Duration = Duration::from_secs(1);
+
+    fn push(
+        queues: &mut Queues<u32>,
+        priority: Priority,
+        item: u32,
+        at: Instant,
+    ) {
+        let slot = Arc::new(Semaphore::new(1)).try_acquire_owned().unwrap();
+        queues.push(
+            priority,
+            Entry {
+                item,
+                enqueued_at: at,
+                _slot: slot,
+            },
+        );
+    }
+
+    fn drain(queues: &mut Queues<u32>, now: Instant) -> Vec<u32> {
+        std::iter::from_fn(|| queues.pop(now)).collect()
+    }
+
+    #[test]
+    fn it_takes_the_higher_priorities_first() {
+        let mut queues = Queues::new(AGING);
+        let now = Instant::now();
+        push(&mut queues, Priority::Low, 1, now);
+        push(&mut queues, Priority::Normal, 2, now);
+        push(&mut queues, Priority::Low, 3, now);
+        push(&mut queues, Priority::High, 4, now);
+        push(&mut queues, Priority::Normal, 5, now);
+        assert_eq!(drain(&mut queues, now), vec![4, 2, 5, 1, 3]);
+    }
+
+    #[test]
+    fn it_promotes_the_items_that_waited() {
+        let mut queues = Queues::new(AGING);
+        let start = Instant::now();
+        push(&mut queues, Priority::Low, 1, start);
+        push(&mut queues, Priority::High, 2, start + AGING);
+        push(&mut queues, Priority::Normal, 3, start + 2 * AGING);
+        // The low priority item waited two intervals, so it is as high as the high priority one,
+        // which came later, while the normal priority one didn't wait
+        assert_eq!(drain(&mut queues, start + 2 * AGING), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn it_disables_the_aging() {
+        let mut queues = Queues::new(Duration::ZERO);
+        let start = Instant::now();
+        push(&mut queues, Priority::Low, 1, start);
+        push(&mut queues, Priority::High, 2, start + AGING);
+        assert_eq!(drain(&mut queues, start + 100 * AGING), vec![2, 1]);
+    }
+
+    #[tokio::test]
+    async fn it_releases_the_slot_when_the_item_is_received() {
+        let (sender, mut receiver) = channel(1, AGING);
+        sender.try_reserve().unwrap().send(Priority::Normal, 1);
+        assert!(matches!(sender.try_reserve(), Err(TrySendError::Full(()))));
+        assert_eq!(receiver.recv().await, Some(1));
+        // A permit dropped without sending releases its slot
+        drop(sender.try_reserve().unwrap());
+        assert_eq!(sender.capacity(), 1);
+    }
+
+    #[tokio::test]
+    async fn it_drains_the_queue_after_closing() {
+        let (sender, mut receiver) = channel(2, AGING);
+        sender.try_reserve().unwrap().send(Priority::Low, 1);
+        receiver.close();
+        assert!(sender.is_closed());
+        assert!(matches!(
+            sender.try_reserve(),
+            Err(TrySendError::Closed(()))
+        ));
+        assert_eq!(receiver.recv().await, Some(1));
+        assert_eq!(receiver.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn it_stops_when_the_senders_are_dropped() {
+        let (sender, mut receiver) = channel::<u32>(1, AGING);
+        let other = sender.clone();
+        drop(sender);
+        let recv = tokio::spawn(async move { receiver.recv().await });
+        tokio::task::yield_now().await;
+        drop(other);
+        assert_eq!(recv.await.unwrap(), None);
+    }
+}
@@ -0,0 +1,153 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Token bucket rate limiter of the inspect requests, keyed by client.
+/// Each client has a bucket of `burst` tokens that refills at `rate` tokens per second, and each
+/// request takes a token. A bucket that is idle long enough to refill completely is the same as a
+/// new one, so it is removed to keep the memory bounded by the clients that are currently active.
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    state: Mutex<State>,
+}
+
+struct State {
+    buckets: HashMap<String, Bucket>,
+    last_cleanup: Instant,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(rate: f64, burst: u32) -> Self {
+        Self {
+            rate,
+            burst: burst.max(1) as f64,
+            state: Mutex::new(State {
+                buckets: HashMap::new(),
+                last_cleanup: Instant::now(),
+            }),
+        }
+    }
+
+    /// Take a token from the bucket of the client.
+    /// Return the time until the next token is available if the bucket is empty.
+    pub fn check(&self, client: &str) -> Result<(), Duration> {
+        self.check_at(client, Instant::now())
+    }
+
+    fn check_at(&self, client: &str, now: Instant) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap();
+        if now.saturating_duration_since(state.last_cleanup)
+            >= self.refill_time()
+        {
+            self.cleanup(&mut state, now);
+        }
+        let bucket =
+            state.buckets.entry(client.to_string()).or_insert(Bucket {
+                tokens: self.burst,
+                updated_at: now,
+            });
+        let elapsed = now.saturating_duration_since(bucket.updated_at);
+        bucket.tokens =
+            (bucket.tokens + elapsed.as_secs_f64() * self.rate).min(self.burst);
+        bucket.updated_at = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(
+                Duration::try_from_secs_f64((1.0 - bucket.tokens) / self.rate)
+                    .unwrap_or(Duration::MAX),
+            )
+        }
+    }
+
+    /// Time an empty bucket takes to refill completely.
+    /// The config validation rejects the rates whose refill time is not a valid duration, which
+    /// are taken as never refilling here.
+    fn refill_time(&self) -> Duration {
+        Duration::try_from_secs_f64(self.burst / self.rate)
+            .unwrap_or(Duration::MAX)
+    }
+
+    /// Remove the buckets that are full again.
+    fn cleanup(&self, state: &mut State, now: Instant) {
+        let refill_time = self.refill_time();
+        state.buckets.retain(|_, bucket| {
+            now.saturating_duration_since(bucket.updated_at) < refill_time
+        });
+        state.last_cleanup = now;
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.state.lock().unwrap().buckets.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_does_not_panic_with_a_rate_too_small_for_a_duration() {
+        let limiter = RateLimiter::new(1e-20, 1);
+        let now = Instant::now();
+        assert!(limiter.check_at("client", now).is_ok());
+        let retry_after = limiter
+            .check_at("client", now)
+            .expect_err("bucket should be empty");
+        assert_eq!(retry_after, Duration::MAX);
+    }
+
+    #[test]
+    fn it_limits_after_burst() {
+        let limiter = RateLimiter::new(1.0, 2);
+        let now = Instant::now();
+        assert!(limiter.check_at("client", now).is_ok());
+        assert!(limiter.check_at("client", now).is_ok());
+        let retry_after = limiter
+            .check_at("client", now)
+            .expect_err("bucket should be empty");
+        assert_eq!(retry_after, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn it_refills_over_time() {
+        let limiter = RateLimiter::new(2.0, 1);
+        let now = Instant::now();
+        assert!(limiter.check_at("client", now).is_ok());
+        assert!(limiter.check_at("client", now).is_err());
+        let later = now + Duration::from_millis(500);
+        assert!(limiter.check_at("client", later).is_ok());
+    }
+
+    #[test]
+    fn it_keeps_a_bucket_per_client() {
+        let limiter = RateLimiter::new(1.0, 1);
+        let now = Instant::now();
+        assert!(limiter.check_at("first", now).is_ok());
+        assert!(limiter.check_at("first", now).is_err());
+        assert!(limiter.check_at("second", now).is_ok());
+    }
+
+    #[test]
+    fn it_removes_idle_buckets() {
+        let limiter = RateLimiter::new(10.0, 1);
+        let now = Instant::now();
+        assert!(limiter.check_at("first", now).is_ok());
+        assert!(limiter.check_at("second", now).is_ok());
+        assert_eq!(limiter.len(), 2);
+        let later = now + Duration::from_millis(100);
+        assert!(limiter.check_at("third", later).is_ok());
+        assert_eq!(limiter.len(), 1);
+    }
+}
@@ -0,0 +1,122 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+use crate::error::InspectError;
+use crate::inspect::InspectStateResponse;
+use crate::metrics::outcome;
+
+/// Number of entries waiting to be written before new ones are dropped
+const RECORDER_CAPACITY: usize = 1024;
+
+/// Inspect request sent to the server manager and the outcome of its response, as recorded in
+/// each line of the recording file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedInspect {
+    /// Time the response was received, in millis since the Unix epoch
+    pub timestamp_ms: u64,
+    pub session_id: String,
+    pub request_id: String,
+    /// Hex encoded payload, with the 0x prefix
+    pub payload: String,
+    /// Completion status of the inspect or name of the error, as in the metrics
+    pub outcome: String,
+    pub report_count: usize,
+    pub elapsed_ms: u64,
+}
+
+impl RecordedInspect {
+    pub fn new(
+        session_id: &str,
+        request_id: &str,
+        payload: &[u8],
+        result: &Result<InspectStateResponse, InspectError>,
+        elapsed: Duration,
+    ) -> Self {
+        let report_count = match result {
+            Ok(response) => response.reports.len(),
+            Err(InspectError::MachineException { reports, .. }) => {
+                reports.len()
+            }
+            Err(_) => 0,
+        };
+        Self {
+            timestamp_ms: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            session_id: session_id.to_string(),
+            request_id: request_id.to_string(),
+            payload: String::from("0x") + &hex::encode(payload),
+            outcome: outcome(result).to_string(),
+            report_count,
+            elapsed_ms: elapsed.as_millis() as u64,
+        }
+    }
+}
+
+/// Recorder of the inspect traffic, which appends each entry as a JSON line to a file.
+/// The entries are written by a background task, so recording never blocks the inspects; when the
+/// writer falls behind, the new entries are dropped with a warning.
+pub struct InspectRecorder {
+    entry_tx: mpsc::Sender<RecordedInspect>,
+}
+
+impl InspectRecorder {
+    /// Start the writer task, which appends to the file at path.
+    pub fn start(path: String) -> Self {
+        let (entry_tx, entry_rx) = mpsc::channel(RECORDER_CAPACITY);
+        tokio::spawn(write_entries(path, entry_rx));
+        Self { entry_tx }
+    }
+
+    pub fn record(&self, entry: RecordedInspect) {
+        match self.entry_tx.try_send(entry) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                tracing::warn!("inspect recorder is full, dropping entry");
+            }
+            // The writer failed and already logged the error
+            Err(TrySendError::Closed(_)) => {}
+        }
+    }
+}
+
+async fn write_entries(
+    path: String,
+    mut entry_rx: mpsc::Receiver<RecordedInspect>,
+) {
+    let file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .await;
+    let mut file = match file {
+        Ok(file) => file,
+        Err(e) => {
+            tracing::error!("failed to open inspect recording {}: {}", path, e);
+            return;
+        }
+    };
+    while let Some(entry) = entry_rx.recv().await {
+        let mut line =
+            serde_json::to_vec(&entry).expect("entry is always serializable");
+        line.push(b'\n');
+        let result = match file.write_all(&line).await {
+            Ok(()) => file.flush().await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            tracing::error!(
+                "failed to write inspect recording {}: {}",
+                path,
+                e
+            );
+            return;
+        }
+    }
+}
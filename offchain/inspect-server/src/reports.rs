@@ -0,0 +1,160 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::inspect::Report;
+
+/// Reports of completed inspects, kept for a while so the clients can read them in pages without
+/// running the inspect again.
+/// Each report set is identified by a random token and expires after the TTL. The expired sets are
+/// removed when a new one is stored, so the memory is bounded by the sets stored within a TTL.
+pub struct ReportStore {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+struct Entry {
+    reports: Vec<Report>,
+    inserted_at: Instant,
+}
+
+/// Page of the reports of an inspect.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportsPage {
+    pub reports: Vec<Report>,
+    /// Number of reports of the inspect
+    pub total: usize,
+}
+
+impl ReportStore {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Store the reports and return their token.
+    pub fn insert(&self, reports: Vec<Report>) -> String {
+        self.insert_at(reports, Instant::now())
+    }
+
+    /// Return the reports from offset up to limit, or None if the token is unknown or expired.
+    /// An offset past the last report returns an empty page.
+    pub fn get(
+        &self,
+        token: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Option<ReportsPage> {
+        self.get_at(token, offset, limit, Instant::now())
+    }
+
+    fn insert_at(&self, reports: Vec<Report>, now: Instant) -> String {
+        let token = Uuid::new_v4().to_string();
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, entry| {
+            now.saturating_duration_since(entry.inserted_at) < self.ttl
+        });
+        entries.insert(
+            token.clone(),
+            Entry {
+                reports,
+                inserted_at: now,
+            },
+        );
+        token
+    }
+
+    fn get_at(
+        &self,
+        token: &str,
+        offset: usize,
+        limit: usize,
+        now: Instant,
+    ) -> Option<ReportsPage> {
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.get(token)?;
+        if now.saturating_duration_since(entry.inserted_at) >= self.ttl {
+            return None;
+        }
+        Some(ReportsPage {
+            reports: entry
+                .reports
+                .iter()
+                .skip(offset)
+                .take(limit)
+                .cloned()
+                .collect(),
+            total: entry.reports.len(),
+        })
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TTL: Duration = Duration::from_secs(10);
+
+    fn reports(count: u8) -> Vec<Report> {
+        (0..count).map(|i| Report { payload: vec![i] }).collect()
+    }
+
+    fn payloads(page: &ReportsPage) -> Vec<u8> {
+        page.reports
+            .iter()
+            .map(|report| report.payload[0])
+            .collect()
+    }
+
+    #[test]
+    fn it_returns_the_reports_in_pages() {
+        let store = ReportStore::new(TTL);
+        let token = store.insert(reports(5));
+        let page = store.get(&token, 0, 2).expect("reports should exist");
+        assert_eq!(payloads(&page), vec![0, 1]);
+        assert_eq!(page.total, 5);
+        let page = store.get(&token, 4, 2).expect("reports should exist");
+        assert_eq!(payloads(&page), vec![4]);
+    }
+
+    #[test]
+    fn it_returns_an_empty_page_past_the_last_report() {
+        let store = ReportStore::new(TTL);
+        let token = store.insert(reports(2));
+        let page = store.get(&token, 5, 2).expect("reports should exist");
+        assert!(page.reports.is_empty());
+        assert_eq!(page.total, 2);
+        let page = store.get(&token, 0, 0).expect("reports should exist");
+        assert!(page.reports.is_empty());
+    }
+
+    #[test]
+    fn it_fails_with_unknown_token() {
+        let store = ReportStore::new(TTL);
+        store.insert(reports(1));
+        assert_eq!(store.get("unknown", 0, 1), None);
+    }
+
+    #[test]
+    fn it_expires_the_reports() {
+        let store = ReportStore::new(TTL);
+        let now = Instant::now();
+        let token = store.insert_at(reports(1), now);
+        assert!(store.get_at(&token, 0, 1, now + TTL / 2).is_some());
+        assert_eq!(store.get_at(&token, 0, 1, now + TTL), None);
+        // The expired reports are removed when new ones are stored
+        store.insert_at(reports(1), now + TTL);
+        assert_eq!(store.len(), 1);
+    }
+}
@@ -0,0 +1,93 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Token bucket of the retries of the inspect requests, shared by all the requests of the worker.
+/// Each retry takes a token, and the bucket refills at `refill` tokens per second up to its
+/// capacity. When the bucket is empty the requests fail without retrying, so the total retry rate
+/// is bounded regardless of the number of requests. A capacity of zero disables the budget.
+pub struct RetryBudget {
+    capacity: f64,
+    refill: f64,
+    state: Mutex<State>,
+}
+
+struct State {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl RetryBudget {
+    pub fn new(capacity: u32, refill: f64) -> Self {
+        Self {
+            capacity: capacity as f64,
+            refill,
+            state: Mutex::new(State {
+                tokens: capacity as f64,
+                updated_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Take a token for a retry, returning whether the retry is allowed.
+    pub fn try_acquire(&self) -> bool {
+        self.try_acquire_at(Instant::now())
+    }
+
+    fn try_acquire_at(&self, now: Instant) -> bool {
+        if self.capacity == 0.0 {
+            return true;
+        }
+        let mut state = self.state.lock().unwrap();
+        let elapsed = now.saturating_duration_since(state.updated_at);
+        state.tokens = (state.tokens + elapsed.as_secs_f64() * self.refill)
+            .min(self.capacity);
+        state.updated_at = now;
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn it_allows_the_retries_up_to_the_capacity() {
+        let budget = RetryBudget::new(2, 1.0);
+        let now = Instant::now();
+        assert!(budget.try_acquire_at(now));
+        assert!(budget.try_acquire_at(now));
+        assert!(!budget.try_acquire_at(now));
+    }
+
+    #[test]
+    fn it_refills_over_time() {
+        let budget = RetryBudget::new(1, 2.0);
+        let now = Instant::now();
+        assert!(budget.try_acquire_at(now));
+        assert!(!budget.try_acquire_at(now));
+        let later = now + Duration::from_millis(500);
+        assert!(budget.try_acquire_at(later));
+        // The bucket doesn't refill past its capacity
+        let much_later = later + Duration::from_secs(10);
+        assert!(budget.try_acquire_at(much_later));
+        assert!(!budget.try_acquire_at(much_later));
+    }
+
+    #[test]
+    fn it_does_not_limit_without_capacity() {
+        let budget = RetryBudget::new(0, 1.0);
+        let now = Instant::now();
+        for _ in 0..100 {
+            assert!(budget.try_acquire_at(now));
+        }
+    }
+}
@@ -3,35 +3,95 @@
 
 use actix_cors::Cors;
 use actix_web::{
-    dev::Server, error, web, App, HttpRequest, HttpResponse, HttpServer,
-    Responder,
+    dev::Server,
+    error,
+    http::{
+        header::{AUTHORIZATION, RETRY_AFTER, WWW_AUTHENTICATE},
+        StatusCode,
+    },
+    web, App, HttpRequest, HttpResponse, HttpServer, Responder,
 };
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "metrics")]
+use std::sync::Arc;
 use tracing_actix_web::TracingLogger;
 
-use crate::config::InspectServerConfig;
+use crate::config::{
+    AdminToken, GetPayloadEncoding, InspectServerConfig, RateLimitConfig,
+};
 use crate::error::InspectError;
 use crate::inspect::{
-    CompletionStatus, InspectClient, InspectStateResponse, Report,
+    decode_payload, CompletionStatus, InspectClient, InspectStateResponse,
+    Report,
 };
+use crate::rate_limit::RateLimiter;
 
 // 2^20 bytes, which is the length of the RX buffer
 pub const CARTESI_MACHINE_RX_BUFFER_LIMIT: usize = 1_048_576;
 
+// Header used by the caller to set the request id of the inspect
+pub const REQUEST_ID_HEADER: &str = "request-id";
+
+// Seconds a client should wait before retrying when the inspect queue is full
+const QUEUE_FULL_RETRY_AFTER: u64 = 1;
+
+// Headers of the backpressure responses with the state of the inspect queue
+pub const QUEUE_DEPTH_HEADER: &str = "x-inspect-queue-depth";
+pub const QUEUE_CAPACITY_HEADER: &str = "x-inspect-queue-capacity";
+
 pub fn create(
     config: &InspectServerConfig,
     inspect_client: InspectClient,
 ) -> std::io::Result<Server> {
+    #[cfg(feature = "metrics")]
+    let registry =
+        web::Data::new(Arc::new(prometheus_client::registry::Registry::from(
+            inspect_client.metrics().clone(),
+        )));
+    let rate_limit = web::Data::new(
+        config
+            .rate_limit_config
+            .as_ref()
+            .map(ClientRateLimiter::new),
+    );
+    let max_http_body_bytes = config.max_http_body_bytes;
+    let get_payload_encoding = web::Data::new(config.get_payload_encoding);
+    let admin_token = config.admin_token.clone().map(web::Data::new);
     let server = HttpServer::new(move || {
         let cors = Cors::permissive();
         App::new()
             .app_data(web::Data::new(inspect_client.clone()))
-            .app_data(web::PayloadConfig::new(CARTESI_MACHINE_RX_BUFFER_LIMIT))
+            .app_data(rate_limit.clone())
+            .app_data(get_payload_encoding.clone())
+            .app_data(web::PayloadConfig::new(max_http_body_bytes))
             .wrap(TracingLogger::default())
             .wrap(cors)
             .service(inspect_get)
             .service(inspect_post)
+            .service(session_inspect_get)
+            .service(session_inspect_post)
+            .service(healthz)
+            .service(readyz)
+            .service(version)
+            .configure(|_service_config| {
+                #[cfg(feature = "metrics")]
+                _service_config
+                    .app_data(registry.clone())
+                    .service(metrics_get);
+            })
+            .configure(|service_config| {
+                // The admin endpoints only exist when there is a token to protect them
+                if let Some(admin_token) = &admin_token {
+                    service_config
+                        .app_data(admin_token.clone())
+                        .service(admin_cache_flush);
+                }
+            })
     })
+    // Signals are handled by the caller, which also drains the inspect queue
+    .disable_signals()
+    // Leave some time to answer the inspects that expire when draining the queue
+    .shutdown_timeout(config.shutdown_timeout.as_secs() + 1)
     .bind(config.inspect_server_address.clone())?
     .run();
     Ok(server)
@@ -43,30 +103,237 @@ async fn inspect_get(
     payload: web::Path<String>,
     inspect_client: web::Data<InspectClient>,
 ) -> actix_web::error::Result<impl Responder> {
-    let mut payload = payload.into_inner();
-    if let Some(query) = request.uri().query() {
-        payload = payload + "?" + query;
-    }
-    let payload = payload.as_bytes().to_vec();
-    let response = inspect_client.inspect(payload).await?;
-    let http_response = HttpInspectResponse::from(response);
-    Ok(HttpResponse::Ok().json(http_response))
+    handle_get(request, payload.into_inner(), None, inspect_client).await
 }
 
 #[actix_web::post("/inspect")]
 async fn inspect_post(
+    request: HttpRequest,
     payload: web::Bytes,
     inspect_client: web::Data<InspectClient>,
 ) -> actix_web::error::Result<impl Responder> {
-    let response = inspect_client.inspect(payload.to_vec()).await?;
+    handle_post(request, payload, None, inspect_client).await
+}
+
+#[actix_web::get("/sessions/{session_id}/inspect/{payload:.*}")]
+async fn session_inspect_get(
+    request: HttpRequest,
+    path: web::Path<(String, String)>,
+    inspect_client: web::Data<InspectClient>,
+) -> actix_web::error::Result<impl Responder> {
+    let (session_id, payload) = path.into_inner();
+    handle_get(request, payload, Some(session_id), inspect_client).await
+}
+
+#[actix_web::post("/sessions/{session_id}/inspect")]
+async fn session_inspect_post(
+    request: HttpRequest,
+    session_id: web::Path<String>,
+    payload: web::Bytes,
+    inspect_client: web::Data<InspectClient>,
+) -> actix_web::error::Result<impl Responder> {
+    let session_id = Some(session_id.into_inner());
+    handle_post(request, payload, session_id, inspect_client).await
+}
+
+async fn handle_get(
+    request: HttpRequest,
+    mut payload: String,
+    session_id: Option<String>,
+    inspect_client: web::Data<InspectClient>,
+) -> actix_web::error::Result<HttpResponse> {
+    check_rate_limit(&request)
+        .map_err(|e| inspect_error(e, &inspect_client))?;
+    let payload = match request.app_data::<web::Data<GetPayloadEncoding>>() {
+        Some(encoding) if *encoding.get_ref() == GetPayloadEncoding::Hex => {
+            if request.uri().query().is_some() {
+                return Err(InspectError::InvalidArgument {
+                    message: String::from(
+                        "a query string is not allowed with hex payloads",
+                    ),
+                }
+                .into());
+            }
+            decode_payload(&payload)?
+        }
+        _ => {
+            if let Some(query) = request.uri().query() {
+                payload = payload + "?" + query;
+            }
+            payload.into_bytes()
+        }
+    };
+    let response = inspect_client
+        .inspect_blocking(payload, session_id, get_request_id(&request))
+        .await
+        .map_err(|e| inspect_error(e, &inspect_client))?;
+    let http_response = HttpInspectResponse::from(response);
+    Ok(HttpResponse::Ok().json(http_response))
+}
+
+async fn handle_post(
+    request: HttpRequest,
+    payload: web::Bytes,
+    session_id: Option<String>,
+    inspect_client: web::Data<InspectClient>,
+) -> actix_web::error::Result<HttpResponse> {
+    check_rate_limit(&request)
+        .map_err(|e| inspect_error(e, &inspect_client))?;
+    let response = inspect_client
+        .inspect_blocking(
+            payload.to_vec(),
+            session_id,
+            get_request_id(&request),
+        )
+        .await
+        .map_err(|e| inspect_error(e, &inspect_client))?;
     let http_response = HttpInspectResponse::from(response);
     Ok(HttpResponse::Ok().json(http_response))
 }
 
+#[cfg(feature = "metrics")]
+#[actix_web::get("/metrics")]
+async fn metrics_get(
+    registry: web::Data<Arc<prometheus_client::registry::Registry>>,
+) -> actix_web::error::Result<impl Responder> {
+    let mut buffer = String::new();
+    prometheus_client::encoding::text::encode(&mut buffer, &registry)
+        .map_err(error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok()
+        .content_type(
+            "application/openmetrics-text; version=1.0.0; charset=utf-8",
+        )
+        .body(buffer))
+}
+
+/// Report that the inspect server is up, regardless of the server manager.
+#[actix_web::get("/healthz")]
+async fn healthz() -> HttpResponse {
+    HttpResponse::Ok().finish()
+}
+
+/// Report whether the server manager is ready to answer inspect requests.
+#[actix_web::get("/readyz")]
+async fn readyz(inspect_client: web::Data<InspectClient>) -> HttpResponse {
+    match inspect_client.check_ready().await {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(e) => HttpResponse::ServiceUnavailable().body(e.to_string()),
+    }
+}
+
+/// Remove all the responses from the inspect cache.
+/// Requires the admin token as a bearer token in the Authorization header.
+#[actix_web::post("/admin/cache/flush")]
+async fn admin_cache_flush(
+    request: HttpRequest,
+    admin_token: web::Data<AdminToken>,
+    inspect_client: web::Data<InspectClient>,
+) -> HttpResponse {
+    let authorized = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| admin_token.matches(token));
+    if !authorized {
+        return HttpResponse::Unauthorized()
+            .insert_header((WWW_AUTHENTICATE, "Bearer"))
+            .finish();
+    }
+    let flushed = inspect_client.flush_cache();
+    HttpResponse::Ok().json(HttpCacheFlushResponse { flushed })
+}
+
+/// Report the version of the inspect server and of the gRPC interfaces it was built against.
+#[actix_web::get("/version")]
+async fn version() -> HttpResponse {
+    HttpResponse::Ok().json(HttpVersionResponse::current())
+}
+
+/// Rate limiter of the HTTP clients, which are identified by the configured header or their IP.
+struct ClientRateLimiter {
+    limiter: RateLimiter,
+    header: Option<String>,
+}
+
+impl ClientRateLimiter {
+    fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            limiter: RateLimiter::new(config.rate, config.burst),
+            header: config.header.clone(),
+        }
+    }
+
+    fn client(&self, request: &HttpRequest) -> String {
+        let header = self
+            .header
+            .as_ref()
+            .and_then(|header| request.headers().get(header));
+        match header {
+            Some(value) => {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            }
+            None => request
+                .peer_addr()
+                .map(|address| address.ip().to_string())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Reject the request if its client exceeded the rate limit.
+fn check_rate_limit(request: &HttpRequest) -> Result<(), InspectError> {
+    let rate_limiter = request
+        .app_data::<web::Data<Option<ClientRateLimiter>>>()
+        .and_then(|rate_limiter| rate_limiter.as_ref().as_ref());
+    match rate_limiter {
+        Some(rate_limiter) => rate_limiter
+            .limiter
+            .check(&rate_limiter.client(request))
+            .map_err(|retry_after| InspectError::RateLimited { retry_after }),
+        None => Ok(()),
+    }
+}
+
+/// Get the request id sent by the caller, so it can be used to trace the inspect.
+fn get_request_id(request: &HttpRequest) -> Option<String> {
+    request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct HttpVersionResponse {
+    pub version: String,
+    pub git_commit: Option<String>,
+    pub proto_schema_hash: String,
+}
+
+impl HttpVersionResponse {
+    /// Version compiled into the binary
+    pub fn current() -> Self {
+        Self {
+            version: log::built_info::PKG_VERSION.to_string(),
+            git_commit: log::built_info::GIT_COMMIT_HASH.map(String::from),
+            proto_schema_hash: grpc_interfaces::SCHEMA_HASH.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct HttpCacheFlushResponse {
+    /// Number of responses removed from the cache
+    pub flushed: usize,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct HttpInspectResponse {
     pub status: String,
     pub exception_payload: Option<String>,
+    /// Exception payload decoded as UTF-8, when it is valid UTF-8
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exception_message: Option<String>,
     pub reports: Vec<HttpReport>,
     pub processed_input_count: u64,
 }
@@ -78,6 +345,7 @@ impl From<InspectStateResponse> for HttpInspectResponse {
         HttpInspectResponse {
             status: convert_status(response.status),
             exception_payload: response.exception_data.map(hex_encode),
+            exception_message: None,
             reports,
             processed_input_count: response.processed_input_count,
         }
@@ -129,15 +397,81 @@ fn hex_encode(payload: Vec<u8>) -> String {
 
 impl From<InspectError> for error::Error {
     fn from(e: InspectError) -> error::Error {
-        tracing::warn!("{}", e.to_string());
-        match e {
-            InspectError::FailedToConnect { .. } => {
-                error::ErrorBadGateway(e.to_string())
-            }
-            InspectError::InspectFailed { .. } => {
-                error::ErrorBadRequest(e.to_string())
+        http_error(e, None)
+    }
+}
+
+/// State of the inspect queue sent in the backpressure responses.
+struct QueueState {
+    depth: usize,
+    capacity: usize,
+}
+
+/// Convert the error of an inspect into an HTTP error.
+/// The backpressure responses also have the state of the inspect queue, so the clients can adapt
+/// their rate.
+fn inspect_error(
+    e: InspectError,
+    inspect_client: &InspectClient,
+) -> error::Error {
+    let queue = QueueState {
+        depth: inspect_client.queue_depth(),
+        capacity: inspect_client.queue_capacity(),
+    };
+    http_error(e, Some(queue))
+}
+
+fn http_error(e: InspectError, queue: Option<QueueState>) -> error::Error {
+    tracing::warn!("{}", e.to_string());
+    match e {
+        InspectError::MachineException {
+            status,
+            ref payload,
+            ref message,
+            ref reports,
+            processed_input_count,
+        } => {
+            // The HTTP API reports exceptions in the body, like the other statuses, instead of
+            // using the status of InspectError::http_status
+            // A rejected inspect has no exception payload unless the machine sent one
+            let has_payload =
+                status == CompletionStatus::Exception || !payload.is_empty();
+            let response = HttpResponse::Ok().json(HttpInspectResponse {
+                status: convert_status(status as i32),
+                exception_payload: has_payload
+                    .then(|| hex_encode(payload.clone())),
+                exception_message: message.clone(),
+                reports: reports
+                    .iter()
+                    .cloned()
+                    .map(HttpReport::from)
+                    .collect(),
+                processed_input_count,
+            });
+            error::InternalError::from_response(e, response).into()
+        }
+        InspectError::QueueFull { .. } | InspectError::RateLimited { .. } => {
+            let retry_after = match e {
+                // Retry-After is in whole seconds, so round up to not retry too early
+                InspectError::RateLimited { retry_after } => {
+                    retry_after.as_secs_f64().ceil().max(1.0) as u64
+                }
+                _ => QUEUE_FULL_RETRY_AFTER,
+            };
+            let mut response = HttpResponse::TooManyRequests();
+            response.insert_header((RETRY_AFTER, retry_after));
+            if let Some(queue) = queue {
+                response
+                    .insert_header((QUEUE_DEPTH_HEADER, queue.depth))
+                    .insert_header((QUEUE_CAPACITY_HEADER, queue.capacity));
             }
-            _ => error::ErrorBadGateway(e.to_string()),
+            let response = response.body(e.to_string());
+            error::InternalError::from_response(e, response).into()
+        }
+        _ => {
+            let status = StatusCode::from_u16(e.http_status())
+                .expect("inspect errors have valid HTTP statuses");
+            error::InternalError::new(e.to_string(), status).into()
         }
     }
 }
@@ -0,0 +1,123 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Limit of the inspect requests of each session handled at the same time, so a burst of requests
+/// to one session doesn't take all the capacity of the queue and the workers from the others.
+/// A request over the limit of its session waits until another request of the session finishes.
+/// The semaphore of a session is created on its first request; the idle ones are removed when a
+/// new session is added, so the memory is bounded by the sessions that are currently active.
+pub struct SessionLimiter {
+    default_limit: usize,
+    overrides: HashMap<String, usize>,
+    semaphores: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl SessionLimiter {
+    /// Create a limiter with the default limit of the sessions and the limits of specific
+    /// sessions. A limit of zero doesn't limit the session.
+    pub fn new(
+        default_limit: usize,
+        overrides: HashMap<String, usize>,
+    ) -> Self {
+        Self {
+            default_limit,
+            overrides,
+            semaphores: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Wait for a free slot of the session, which is released when the permit is dropped.
+    /// Return None when the session is not limited.
+    pub async fn acquire(
+        &self,
+        session_id: &str,
+    ) -> Option<OwnedSemaphorePermit> {
+        let limit = self
+            .overrides
+            .get(session_id)
+            .copied()
+            .unwrap_or(self.default_limit);
+        if limit == 0 {
+            return None;
+        }
+        let semaphore = {
+            let mut semaphores = self.semaphores.lock().unwrap();
+            if !semaphores.contains_key(session_id) {
+                // Semaphores that are only referenced by the map have no permits taken or waiters
+                semaphores
+                    .retain(|_, semaphore| Arc::strong_count(semaphore) > 1);
+            }
+            semaphores
+                .entry(session_id.to_string())
+                .or_insert_with(|| Arc::new(Semaphore::new(limit)))
+                .clone()
+        };
+        Some(
+            semaphore
+                .acquire_owned()
+                .await
+                .expect("semaphore is never closed"),
+        )
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.semaphores.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    async fn acquire_now(
+        limiter: &SessionLimiter,
+        session_id: &str,
+    ) -> Option<Option<OwnedSemaphorePermit>> {
+        tokio::time::timeout(
+            Duration::from_millis(10),
+            limiter.acquire(session_id),
+        )
+        .await
+        .ok()
+    }
+
+    #[tokio::test]
+    async fn it_limits_each_session() {
+        let limiter = SessionLimiter::new(1, HashMap::new());
+        let first = acquire_now(&limiter, "first").await.unwrap();
+        assert!(first.is_some());
+        assert!(acquire_now(&limiter, "first").await.is_none());
+        assert!(acquire_now(&limiter, "second").await.unwrap().is_some());
+        drop(first);
+        assert!(acquire_now(&limiter, "first").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn it_applies_the_overrides() {
+        let overrides = HashMap::from([
+            (String::from("unlimited"), 0),
+            (String::from("large"), 2),
+        ]);
+        let limiter = SessionLimiter::new(1, overrides);
+        assert!(acquire_now(&limiter, "unlimited").await.unwrap().is_none());
+        let _first = acquire_now(&limiter, "large").await.unwrap();
+        let _second = acquire_now(&limiter, "large").await.unwrap();
+        assert!(acquire_now(&limiter, "large").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn it_removes_idle_sessions() {
+        let limiter = SessionLimiter::new(1, HashMap::new());
+        let _first = acquire_now(&limiter, "first").await.unwrap();
+        drop(acquire_now(&limiter, "second").await.unwrap());
+        assert_eq!(limiter.len(), 2);
+        let _third = acquire_now(&limiter, "third").await.unwrap();
+        assert_eq!(limiter.len(), 2);
+    }
+}
@@ -0,0 +1,227 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+//! Mock server manager to test the inspect client without a real server manager.
+//! This module is only available with the test-utils feature.
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{transport::Server, Request, Response, Status};
+
+use grpc_interfaces::cartesi_machine::Void;
+use grpc_interfaces::cartesi_server_manager::{
+    server_manager_server::{ServerManager, ServerManagerServer},
+    AdvanceStateRequest, CompletionStatus, DeleteEpochRequest,
+    EndSessionRequest, FinishEpochRequest, FinishEpochResponse,
+    GetEpochStatusRequest, GetEpochStatusResponse, GetSessionStatusRequest,
+    GetSessionStatusResponse, GetStatusResponse, InspectStateRequest,
+    InspectStateResponse, StartSessionRequest, StartSessionResponse,
+};
+use grpc_interfaces::versioning::GetVersionResponse;
+
+pub const MOCK_SESSION_ID: &str = "default";
+
+/// Server manager that answers every inspect with the same configured response.
+/// The other methods of the server manager fail with Unimplemented, except get_status, which
+/// reports the mock session.
+#[derive(Clone)]
+pub struct MockServerManager {
+    session_id: String,
+    response: Result<InspectStateResponse, Status>,
+    delay: Duration,
+    received: Arc<Mutex<Vec<InspectStateRequest>>>,
+}
+
+impl Default for MockServerManager {
+    fn default() -> Self {
+        Self {
+            session_id: MOCK_SESSION_ID.to_string(),
+            response: Ok(InspectStateResponse {
+                session_id: MOCK_SESSION_ID.to_string(),
+                active_epoch_index: 0,
+                processed_input_count: 0,
+                status: CompletionStatus::Accepted as i32,
+                exception_data: None,
+                reports: vec![],
+            }),
+            delay: Duration::ZERO,
+            received: Arc::new(Mutex::new(vec![])),
+        }
+    }
+}
+
+impl MockServerManager {
+    /// Mock that accepts every inspect without reports.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the session reported by get_status.
+    pub fn with_session_id(mut self, session_id: &str) -> Self {
+        self.session_id = session_id.to_string();
+        self
+    }
+
+    /// Answer the inspects with the given response.
+    pub fn with_response(mut self, response: InspectStateResponse) -> Self {
+        self.response = Ok(response);
+        self
+    }
+
+    /// Fail the inspects with the given status.
+    pub fn with_status(mut self, status: Status) -> Self {
+        self.response = Err(status);
+        self
+    }
+
+    /// Wait for the given time before answering each inspect.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Start the mock on an ephemeral port of localhost.
+    /// This function returns once the mock is accepting connections.
+    pub async fn start(self) -> MockServerManagerHandle {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("failed to bind mock server manager");
+        let address = listener
+            .local_addr()
+            .expect("failed to get mock server manager address");
+        let received = self.received.clone();
+        let shutdown = Arc::new(Notify::new());
+        let join_handle = {
+            let shutdown = shutdown.clone();
+            tokio::spawn(async move {
+                Server::builder()
+                    .add_service(ServerManagerServer::new(self))
+                    .serve_with_incoming_shutdown(
+                        TcpListenerStream::new(listener),
+                        shutdown.notified(),
+                    )
+                    .await
+                    .expect("mock server manager failed");
+            })
+        };
+        MockServerManagerHandle {
+            address,
+            shutdown,
+            join_handle,
+            received,
+        }
+    }
+}
+
+/// Handle of a running mock server manager.
+pub struct MockServerManagerHandle {
+    address: SocketAddr,
+    shutdown: Arc<Notify>,
+    join_handle: JoinHandle<()>,
+    received: Arc<Mutex<Vec<InspectStateRequest>>>,
+}
+
+impl MockServerManagerHandle {
+    /// Address of the mock, which can be used as the server_manager_address of the config.
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Return the inspect requests received so far.
+    pub fn received_requests(&self) -> Vec<InspectStateRequest> {
+        self.received.lock().unwrap().clone()
+    }
+
+    /// Stop the mock.
+    /// This function returns once the mock is shut down.
+    pub async fn stop(self) {
+        self.shutdown.notify_one();
+        self.join_handle
+            .await
+            .expect("failed to stop mock server manager");
+    }
+}
+
+#[tonic::async_trait]
+impl ServerManager for MockServerManager {
+    async fn inspect_state(
+        &self,
+        request: Request<InspectStateRequest>,
+    ) -> Result<Response<InspectStateResponse>, Status> {
+        self.received.lock().unwrap().push(request.into_inner());
+        if !self.delay.is_zero() {
+            tokio::time::sleep(self.delay).await;
+        }
+        self.response.clone().map(Response::new)
+    }
+
+    async fn get_version(
+        &self,
+        _: Request<Void>,
+    ) -> Result<Response<GetVersionResponse>, Status> {
+        Err(Status::unimplemented("get_version"))
+    }
+
+    async fn start_session(
+        &self,
+        _: Request<StartSessionRequest>,
+    ) -> Result<Response<StartSessionResponse>, Status> {
+        Err(Status::unimplemented("start_session"))
+    }
+
+    async fn end_session(
+        &self,
+        _: Request<EndSessionRequest>,
+    ) -> Result<Response<Void>, Status> {
+        Err(Status::unimplemented("end_session"))
+    }
+
+    async fn advance_state(
+        &self,
+        _: Request<AdvanceStateRequest>,
+    ) -> Result<Response<Void>, Status> {
+        Err(Status::unimplemented("advance_state"))
+    }
+
+    async fn finish_epoch(
+        &self,
+        _: Request<FinishEpochRequest>,
+    ) -> Result<Response<FinishEpochResponse>, Status> {
+        Err(Status::unimplemented("finish_epoch"))
+    }
+
+    async fn get_status(
+        &self,
+        _: Request<Void>,
+    ) -> Result<Response<GetStatusResponse>, Status> {
+        Ok(Response::new(GetStatusResponse {
+            session_id: vec![self.session_id.clone()],
+        }))
+    }
+
+    async fn get_session_status(
+        &self,
+        _: Request<GetSessionStatusRequest>,
+    ) -> Result<Response<GetSessionStatusResponse>, Status> {
+        Err(Status::unimplemented("get_session_status"))
+    }
+
+    async fn get_epoch_status(
+        &self,
+        _: Request<GetEpochStatusRequest>,
+    ) -> Result<Response<GetEpochStatusResponse>, Status> {
+        Err(Status::unimplemented("get_epoch_status"))
+    }
+
+    async fn delete_epoch(
+        &self,
+        _: Request<DeleteEpochRequest>,
+    ) -> Result<Response<Void>, Status> {
+        Err(Status::unimplemented("delete_epoch"))
+    }
+}
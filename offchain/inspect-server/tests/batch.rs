@@ -0,0 +1,127 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use futures::StreamExt;
+use inspect_server::inspect::{InspectBatchMode, InspectClient};
+
+/// Echo the payload as a report or fail if the payload is "fail"
+struct EchoOrFailInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for EchoOrFailInspect {
+    async fn inspect_state(
+        &self,
+        payload: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        if payload == b"fail" {
+            return Err(Status::invalid_argument("failed"));
+        }
+        Ok(MockInspectResponse {
+            reports: vec![Report { payload }],
+            exception: None,
+            completion_status: CompletionStatus::Accepted,
+            processed_input_count: None,
+        })
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_batch_returns_results_in_order() {
+    let server_manager =
+        MockServerManagerWrapper::start(EchoOrFailInspect {}).await;
+    let client = InspectClient::new(&test_config());
+    let payloads: Vec<Vec<u8>> =
+        vec![b"first".to_vec(), b"second".to_vec(), b"third".to_vec()];
+    let results = client.inspect_batch(payloads.clone()).await;
+    assert_eq!(results.len(), payloads.len());
+    for (result, payload) in results.into_iter().zip(payloads) {
+        let response = result.expect("failed to inspect");
+        assert_eq!(response.reports, vec![Report { payload }]);
+    }
+    server_manager.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_batch_failure_does_not_affect_other_payloads() {
+    let server_manager =
+        MockServerManagerWrapper::start(EchoOrFailInspect {}).await;
+    let client = InspectClient::new(&test_config());
+    let payloads: Vec<Vec<u8>> =
+        vec![b"first".to_vec(), b"fail".to_vec(), b"third".to_vec()];
+    let results = client.inspect_batch(payloads).await;
+    assert!(results[0].is_ok());
+    assert_eq!(
+        results[1]
+            .as_ref()
+            .expect_err("inspect should fail")
+            .to_string(),
+        "Invalid argument: failed"
+    );
+    assert!(results[2].is_ok());
+    server_manager.stop().await;
+}
+
+/// Answers the payloads that start with "slow" after a delay.
+struct SlowOrFastInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for SlowOrFastInspect {
+    async fn inspect_state(
+        &self,
+        payload: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        if payload.starts_with(b"slow") {
+            tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        }
+        Ok(MockInspectResponse {
+            reports: vec![Report { payload }],
+            exception: None,
+            completion_status: CompletionStatus::Accepted,
+            processed_input_count: None,
+        })
+    }
+}
+
+async fn batch_stream_indices(mode: InspectBatchMode) -> Vec<usize> {
+    let server_manager =
+        MockServerManagerWrapper::start(SlowOrFastInspect {}).await;
+    let client = InspectClient::new(&test_config());
+    let payloads: Vec<Vec<u8>> = vec![b"slow".to_vec(), b"fast".to_vec()];
+    let results: Vec<_> = client
+        .inspect_batch_stream(payloads.clone(), mode)
+        .collect()
+        .await;
+    server_manager.stop().await;
+    results
+        .into_iter()
+        .map(|(index, result)| {
+            let response = result.expect("failed to inspect");
+            assert_eq!(
+                response.reports,
+                vec![Report {
+                    payload: payloads[index].clone()
+                }]
+            );
+            index
+        })
+        .collect()
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_batch_stream_yields_results_as_completed() {
+    let indices = batch_stream_indices(InspectBatchMode::AsCompleted).await;
+    assert_eq!(indices, vec![1, 0]);
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_batch_stream_yields_results_in_order() {
+    let indices = batch_stream_indices(InspectBatchMode::Ordered).await;
+    assert_eq!(indices, vec![0, 1]);
+}
@@ -0,0 +1,43 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+#![cfg(feature = "blocking")]
+
+mod common;
+use crate::common::*;
+
+struct EchoInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for EchoInspect {
+    async fn inspect_state(
+        &self,
+        payload: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse {
+            reports: vec![Report { payload }],
+            exception: None,
+            completion_status: CompletionStatus::Accepted,
+            processed_input_count: None,
+        })
+    }
+}
+
+#[test]
+#[serial_test::serial]
+fn test_it_inspects_from_synchronous_code() {
+    let runtime =
+        tokio::runtime::Runtime::new().expect("failed to build runtime");
+    let server_manager =
+        runtime.block_on(MockServerManagerWrapper::start(EchoInspect {}));
+    let client = {
+        let _guard = runtime.enter();
+        InspectClient::new(&test_config())
+    };
+    let response = client
+        .blocking_inspect(b"hello".to_vec(), None, None)
+        .expect("failed to inspect");
+    assert_eq!(response.reports.len(), 1);
+    assert_eq!(response.reports[0].payload, b"hello");
+    runtime.block_on(server_manager.stop());
+}
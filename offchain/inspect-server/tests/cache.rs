@@ -0,0 +1,171 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use inspect_server::config::{AdminToken, InspectServerConfig};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Echo the payload with the given processed input count
+struct CountInspect {
+    processed_input_count: Arc<AtomicU64>,
+}
+
+#[tonic::async_trait]
+impl MockInspect for CountInspect {
+    async fn inspect_state(
+        &self,
+        payload: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse {
+            reports: vec![Report { payload }],
+            exception: None,
+            completion_status: CompletionStatus::Accepted,
+            processed_input_count: Some(
+                self.processed_input_count.load(Ordering::Relaxed),
+            ),
+        })
+    }
+}
+
+fn cache_config(cache_ttl: Duration) -> InspectServerConfig {
+    let mut config = test_config();
+    config.cache_size = 10;
+    config.cache_ttl = cache_ttl;
+    config
+}
+
+async fn setup(cache_ttl: Duration) -> (TestState, Arc<AtomicU64>) {
+    let processed_input_count = Arc::new(AtomicU64::new(0));
+    let mock = CountInspect {
+        processed_input_count: processed_input_count.clone(),
+    };
+    let state =
+        TestState::setup_with_config(mock, cache_config(cache_ttl)).await;
+    (state, processed_input_count)
+}
+
+/// Start the servers with the cache and the admin endpoints enabled
+async fn setup_admin(
+    admin_token: Option<&str>,
+) -> (MockServerManagerWrapper, InspectServerWrapper) {
+    let server_manager = MockServerManagerWrapper::start(CountInspect {
+        processed_input_count: Arc::new(AtomicU64::new(0)),
+    })
+    .await;
+    let mut config = cache_config(Duration::from_secs(60));
+    config.admin_token = admin_token.map(AdminToken::new);
+    let inspect_server = InspectServerWrapper::start_with_config(config).await;
+    (server_manager, inspect_server)
+}
+
+async fn send_flush_request(token: Option<&str>) -> reqwest::Response {
+    let url = format!("http://{}/admin/cache/flush", INSPECT_SERVER_ADDRESS);
+    let mut request = reqwest::Client::new().post(url);
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    request.send().await.expect("failed to send flush request")
+}
+
+async fn inspect(payload: &str) {
+    let response = send_get_request(payload)
+        .await
+        .expect("failed to obtain response");
+    assert_eq!(response.reports[0].payload, hex_to_bin(&payload.into()));
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_skips_server_manager_on_cache_hit() {
+    let (state, _) = setup(Duration::from_secs(60)).await;
+    inspect("hello").await;
+    inspect("hello").await;
+    inspect("world").await;
+    assert_eq!(state.received_requests().len(), 2);
+    state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_invalidates_cache_when_input_is_processed() {
+    let (state, processed_input_count) = setup(Duration::from_secs(60)).await;
+    inspect("hello").await;
+    processed_input_count.store(1, Ordering::Relaxed);
+    // Cache miss that sees the new processed input count
+    inspect("world").await;
+    inspect("hello").await;
+    assert_eq!(state.received_requests().len(), 3);
+    state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_expires_cached_responses() {
+    let (state, _) = setup(Duration::from_millis(100)).await;
+    inspect("hello").await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    inspect("hello").await;
+    assert_eq!(state.received_requests().len(), 2);
+    state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_sends_the_inspect_again_after_a_flush() {
+    let (server_manager, inspect_server) = setup_admin(None).await;
+    inspect("hello").await;
+    inspect("hello").await;
+    assert_eq!(server_manager.received_requests().len(), 1);
+    assert_eq!(inspect_server.inspect_client().flush_cache(), 1);
+    inspect("hello").await;
+    assert_eq!(server_manager.received_requests().len(), 2);
+    inspect_server.stop().await;
+    server_manager.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_flushes_the_cache_with_the_admin_endpoint() {
+    let (server_manager, inspect_server) = setup_admin(Some("secret")).await;
+    inspect("hello").await;
+    inspect("world").await;
+    let response = send_flush_request(Some("secret")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: serde_json::Value =
+        response.json().await.expect("failed to decode response");
+    assert_eq!(body, serde_json::json!({ "flushed": 2 }));
+    inspect("hello").await;
+    assert_eq!(server_manager.received_requests().len(), 3);
+    inspect_server.stop().await;
+    server_manager.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_rejects_a_flush_without_the_admin_token() {
+    let (server_manager, inspect_server) = setup_admin(Some("secret")).await;
+    inspect("hello").await;
+    for token in [None, Some("wrong"), Some("secre")] {
+        let response = send_flush_request(token).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+    // The cache was kept
+    inspect("hello").await;
+    assert_eq!(server_manager.received_requests().len(), 1);
+    inspect_server.stop().await;
+    server_manager.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_disables_the_admin_endpoint_without_a_token() {
+    let (server_manager, inspect_server) = setup_admin(None).await;
+    let response = send_flush_request(Some("secret")).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    inspect_server.stop().await;
+    server_manager.stop().await;
+}
@@ -0,0 +1,71 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Inspect that never finishes and records when the server drops it
+struct HangInspect {
+    cancelled: Arc<AtomicBool>,
+}
+
+/// Set the flag when dropped
+struct DropGuard(Arc<AtomicBool>);
+
+impl Drop for DropGuard {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+#[tonic::async_trait]
+impl MockInspect for HangInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        let _guard = DropGuard(self.cancelled.clone());
+        tokio::time::sleep(Duration::from_secs(3600)).await;
+        Err(Status::deadline_exceeded("inspect took too long"))
+    }
+}
+
+async fn wait_for(condition: impl Fn() -> bool) -> bool {
+    for _ in 0..100 {
+        if condition() {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    false
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_cancels_inspect_when_client_drops_request() {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let server_manager = MockServerManagerWrapper::start(HangInspect {
+        cancelled: cancelled.clone(),
+    })
+    .await;
+    let client = InspectClient::new(&test_config());
+    let handle = {
+        let client = client.clone();
+        tokio::spawn(async move {
+            let _ = client.inspect(b"hello".to_vec()).await;
+        })
+    };
+    assert!(wait_for(|| server_manager.received_requests().len() == 1).await);
+    assert!(!cancelled.load(Ordering::SeqCst));
+    // Dropping the future drops the receiver of the response
+    handle.abort();
+    assert!(
+        wait_for(|| cancelled.load(Ordering::SeqCst)).await,
+        "inspect was not cancelled in the server manager"
+    );
+    server_manager.stop().await;
+}
@@ -0,0 +1,87 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use std::time::Duration;
+
+const THRESHOLD: u32 = 2;
+const COOLDOWN: Duration = Duration::from_millis(300);
+
+struct AcceptInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for AcceptInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse::default())
+    }
+}
+
+async fn start_inspect_server() -> InspectServerWrapper {
+    let mut config = test_config();
+    config.circuit_breaker_threshold = THRESHOLD;
+    config.circuit_breaker_cooldown = COOLDOWN;
+    InspectServerWrapper::start_with_config(config).await
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_circuit_breaker_closed_open_half_open_closed() {
+    let inspect_server = start_inspect_server().await;
+
+    // Closed: the requests try to reach the server manager
+    for _ in 0..THRESHOLD {
+        let (status, _) = send_get_request("hello")
+            .await
+            .expect_err("server manager is down");
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+    }
+
+    // Open: the requests are rejected without connecting
+    let server_manager =
+        MockServerManagerWrapper::start(AcceptInspect {}).await;
+    let (status, message) = send_get_request("hello")
+        .await
+        .expect_err("circuit breaker should be open");
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(message, "Inspect service is unavailable");
+    assert!(server_manager.received_requests().is_empty());
+
+    // Half-open: the probe reaches the server manager and closes the circuit
+    tokio::time::sleep(COOLDOWN).await;
+    send_get_request("hello")
+        .await
+        .expect("probe should succeed");
+    send_get_request("hello")
+        .await
+        .expect("circuit breaker should be closed");
+    assert_eq!(server_manager.received_requests().len(), 2);
+
+    server_manager.stop().await;
+    inspect_server.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_circuit_breaker_opens_again_when_probe_fails() {
+    let inspect_server = start_inspect_server().await;
+    for _ in 0..THRESHOLD {
+        send_get_request("hello")
+            .await
+            .expect_err("server manager is down");
+    }
+    tokio::time::sleep(COOLDOWN).await;
+    let (status, _) = send_get_request("hello")
+        .await
+        .expect_err("probe should fail");
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    let (status, _) = send_get_request("hello")
+        .await
+        .expect_err("circuit breaker should be open");
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    inspect_server.stop().await;
+}
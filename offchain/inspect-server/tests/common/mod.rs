@@ -4,13 +4,19 @@
 #![allow(dead_code)]
 
 use actix_web::dev::ServerHandle;
-use inspect_server::config::InspectServerConfig;
+pub use inspect_server::config::{GetPayloadEncoding, GrpcCompression};
+use inspect_server::config::{InspectServerConfig, DEFAULT_MAX_RESPONSE_BYTES};
 use log::LogConfig;
 pub use reqwest::StatusCode;
-use std::sync::Arc;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::sync::{oneshot, Notify};
 use tokio::task::JoinHandle;
-use tonic::{transport::Server, Request, Response, Status};
+use tonic::codec::CompressionEncoding;
+pub use tonic::transport::{Identity, ServerTlsConfig};
+pub use tonic::Status;
+use tonic::{metadata::MetadataMap, transport::Server, Request, Response};
 
 use grpc_interfaces::cartesi_machine::Void;
 use grpc_interfaces::cartesi_server_manager::{
@@ -24,8 +30,10 @@ use grpc_interfaces::cartesi_server_manager::{
 pub use grpc_interfaces::cartesi_server_manager::{CompletionStatus, Report};
 use grpc_interfaces::versioning::GetVersionResponse;
 
-use inspect_server::inspect::InspectClient;
-use inspect_server::server::HttpInspectResponse;
+pub use inspect_server::inspect::{InspectClient, InspectOptions};
+use inspect_server::server::{
+    HttpInspectResponse, CARTESI_MACHINE_RX_BUFFER_LIMIT,
+};
 
 pub const SERVER_MANAGER_ADDRESS: &'static str = "127.0.0.1:50001";
 pub const INSPECT_SERVER_ADDRESS: &'static str = "127.0.0.1:50002";
@@ -33,23 +41,47 @@ pub const SESSION_ID: &'static str = "default session";
 pub const ACTIVE_EPOCH_INDEX: u64 = 123;
 pub const PROCESSED_INPUT_COUNT: u64 = 456;
 pub const QUEUE_SIZE: usize = 3;
+pub const CONCURRENCY: usize = 2;
 
 pub struct TestState {
     server_manager: MockServerManagerWrapper,
     inspect_server: InspectServerWrapper,
 }
 
+/// Inspect request received by the mock server manager
+#[derive(Clone, Debug)]
+pub struct ReceivedRequest {
+    pub metadata: MetadataMap,
+    pub remote_addr: Option<SocketAddr>,
+    pub session_id: String,
+    pub payload: Vec<u8>,
+}
+
 impl TestState {
     /// Start the inspect-server and the mock-server-manager
     pub async fn setup(mock: impl MockInspect) -> Self {
+        Self::setup_with_config(mock, test_config()).await
+    }
+
+    /// Start the inspect-server with the given config and the mock-server-manager
+    pub async fn setup_with_config(
+        mock: impl MockInspect,
+        config: InspectServerConfig,
+    ) -> Self {
         let server_manager = MockServerManagerWrapper::start(mock).await;
-        let inspect_server = InspectServerWrapper::start().await;
+        let inspect_server =
+            InspectServerWrapper::start_with_config(config).await;
         Self {
             server_manager,
             inspect_server,
         }
     }
 
+    /// Return the inspect requests received by the mock server manager.
+    pub fn received_requests(&self) -> Vec<ReceivedRequest> {
+        self.server_manager.received_requests()
+    }
+
     /// Shutdown both servers.
     /// This function cannot be implemented as the drop trait because it is async.
     pub async fn teardown(self) {
@@ -63,37 +95,98 @@ pub struct MockInspectResponse {
     pub reports: Vec<Report>,
     pub exception: Option<Vec<u8>>,
     pub completion_status: CompletionStatus,
+    /// Overrides the default processed input count when set
+    pub processed_input_count: Option<u64>,
 }
 
 #[tonic::async_trait]
 pub trait MockInspect: Send + Sync + 'static {
-    async fn inspect_state(&self, payload: Vec<u8>) -> MockInspectResponse;
+    async fn inspect_state(
+        &self,
+        payload: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status>;
+}
+
+/// Default config used by the tests
+pub fn test_config() -> InspectServerConfig {
+    InspectServerConfig {
+        inspect_server_address: INSPECT_SERVER_ADDRESS.to_string(),
+        server_manager_address: SERVER_MANAGER_ADDRESS.to_string(),
+        session_id: SESSION_ID.to_string(),
+        queue_size: QUEUE_SIZE,
+        load_shedding_threshold: 0.0,
+        enqueue_timeout: Duration::ZERO,
+        priority_aging: Duration::from_secs(1),
+        max_payload_size: CARTESI_MACHINE_RX_BUFFER_LIMIT,
+        max_http_body_bytes: CARTESI_MACHINE_RX_BUFFER_LIMIT,
+        max_reports: 0,
+        max_total_report_bytes: 0,
+        max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+        decode_exception_messages: true,
+        request_timeout: Duration::ZERO,
+        connect_timeout: Duration::ZERO,
+        slow_inspect_threshold: Duration::ZERO,
+        idle_timeout: Duration::ZERO,
+        keepalive_interval: Duration::ZERO,
+        concurrency: CONCURRENCY,
+        session_concurrency: 0,
+        session_concurrency_overrides: Default::default(),
+        connection_pool_size: 1,
+        max_retries: 0,
+        initial_backoff: Duration::from_millis(10),
+        retry_budget: 0,
+        retry_budget_refill: 10.0,
+        circuit_breaker_threshold: 0,
+        circuit_breaker_cooldown: Duration::from_secs(1),
+        failover_cooldown: Duration::from_secs(5),
+        readiness_interval: Duration::from_millis(50),
+        readiness_timeout: Duration::ZERO,
+        verify_session: false,
+        dedup_request_ids: false,
+        warm_up: false,
+        warm_up_payload: vec![],
+        warm_up_required: false,
+        shutdown_timeout: Duration::from_secs(1),
+        cache_size: 0,
+        cache_ttl: Duration::from_secs(1),
+        reports_ttl: Duration::from_secs(60),
+        record_path: None,
+        node_id: None,
+        rate_limit_config: None,
+        tls_config: None,
+        compression: GrpcCompression::None,
+        get_payload_encoding: GetPayloadEncoding::Utf8,
+        admin_token: None,
+        healthcheck_port: 0,
+        sources: Default::default(),
+        log_config: LogConfig::default(),
+    }
 }
 
 pub struct InspectServerWrapper {
     server_handle: ServerHandle,
     join_handle: JoinHandle<()>,
+    inspect_client: InspectClient,
 }
 
 impl InspectServerWrapper {
     /// Start the inspect server in another thread.
     /// This function blocks until the server is ready.
     pub async fn start() -> Self {
-        let inspect_server_config = InspectServerConfig {
-            inspect_server_address: INSPECT_SERVER_ADDRESS.to_string(),
-            server_manager_address: SERVER_MANAGER_ADDRESS.to_string(),
-            session_id: SESSION_ID.to_string(),
-            queue_size: QUEUE_SIZE,
-            healthcheck_port: 0,
-            log_config: LogConfig::default(),
-        };
+        Self::start_with_config(test_config()).await
+    }
 
+    /// Start the inspect server with the given config.
+    pub async fn start_with_config(
+        inspect_server_config: InspectServerConfig,
+    ) -> Self {
         let inspect_client = InspectClient::new(&inspect_server_config);
         let (handle_tx, handle_rx) = oneshot::channel();
+        let server_client = inspect_client.clone();
         let join_handle = tokio::spawn(async move {
             let server = inspect_server::server::create(
                 &inspect_server_config,
-                inspect_client,
+                server_client,
             )
             .expect("failed to start inspect server");
             handle_tx
@@ -106,9 +199,15 @@ impl InspectServerWrapper {
         Self {
             server_handle,
             join_handle,
+            inspect_client,
         }
     }
 
+    /// Inspect client used by the server.
+    pub fn inspect_client(&self) -> &InspectClient {
+        &self.inspect_client
+    }
+
     /// Stop the inspect server.
     /// This function blocks util the server is shut down.
     pub async fn stop(self) {
@@ -122,13 +221,27 @@ impl InspectServerWrapper {
 pub struct MockServerManagerWrapper {
     shutdown: Arc<Notify>,
     join_handle: JoinHandle<()>,
+    received: Arc<Mutex<Vec<ReceivedRequest>>>,
 }
 
 impl MockServerManagerWrapper {
     /// Start the server manager in another thread.
     /// This function blocks until the server is ready.
     pub async fn start(mock: impl MockInspect) -> Self {
-        let service = MockServerManager { mock };
+        Self::start_with_tls(mock, None).await
+    }
+
+    /// Start the server manager in another thread, serving TLS when tls_config is given.
+    /// This function blocks until the server is ready.
+    pub async fn start_with_tls(
+        mock: impl MockInspect,
+        tls_config: Option<ServerTlsConfig>,
+    ) -> Self {
+        let received = Arc::new(Mutex::new(vec![]));
+        let service = MockServerManager {
+            mock,
+            received: received.clone(),
+        };
         let address = SERVER_MANAGER_ADDRESS.parse().expect("invalid address");
         let ready = Arc::new(Notify::new());
         let shutdown = Arc::new(Notify::new());
@@ -136,8 +249,19 @@ impl MockServerManagerWrapper {
             let ready = ready.clone();
             let shutdown = shutdown.clone();
             tokio::spawn(async move {
-                let server = Server::builder()
-                    .add_service(ServerManagerServer::new(service))
+                let mut builder = Server::builder();
+                if let Some(tls_config) = tls_config {
+                    builder = builder
+                        .tls_config(tls_config)
+                        .expect("invalid tls config");
+                }
+                let server = builder
+                    .add_service(
+                        // Compress the responses only for the clients that accept it
+                        ServerManagerServer::new(service)
+                            .accept_compressed(CompressionEncoding::Gzip)
+                            .send_compressed(CompressionEncoding::Gzip),
+                    )
                     .serve_with_shutdown(address, shutdown.notified());
                 ready.notify_one();
                 server.await.expect("failed to start server manager");
@@ -147,9 +271,56 @@ impl MockServerManagerWrapper {
         Self {
             shutdown,
             join_handle,
+            received,
         }
     }
 
+    /// Start the server manager in another thread, listening on the Unix domain socket at path.
+    /// This function blocks until the server is ready.
+    pub async fn start_with_unix_socket(
+        mock: impl MockInspect,
+        path: &std::path::Path,
+    ) -> Self {
+        let received = Arc::new(Mutex::new(vec![]));
+        let service = MockServerManager {
+            mock,
+            received: received.clone(),
+        };
+        let listener = tokio::net::UnixListener::bind(path)
+            .expect("failed to bind socket");
+        let shutdown = Arc::new(Notify::new());
+        let join_handle = {
+            let shutdown = shutdown.clone();
+            tokio::spawn(async move {
+                Server::builder()
+                    .add_service(
+                        // Compress the responses only for the clients that accept it
+                        ServerManagerServer::new(service)
+                            .accept_compressed(CompressionEncoding::Gzip)
+                            .send_compressed(CompressionEncoding::Gzip),
+                    )
+                    .serve_with_incoming_shutdown(
+                        tokio_stream::wrappers::UnixListenerStream::new(
+                            listener,
+                        ),
+                        shutdown.notified(),
+                    )
+                    .await
+                    .expect("failed to start server manager");
+            })
+        };
+        Self {
+            shutdown,
+            join_handle,
+            received,
+        }
+    }
+
+    /// Return the inspect requests received so far.
+    pub fn received_requests(&self) -> Vec<ReceivedRequest> {
+        self.received.lock().unwrap().clone()
+    }
+
     /// Stop the server manager.
     /// This function blocks until the server is shut down.
     pub async fn stop(self) {
@@ -162,6 +333,7 @@ impl MockServerManagerWrapper {
 
 struct MockServerManager<T: MockInspect> {
     mock: T,
+    received: Arc<Mutex<Vec<ReceivedRequest>>>,
 }
 
 #[tonic::async_trait]
@@ -170,14 +342,23 @@ impl<T: MockInspect> ServerManager for MockServerManager<T> {
         &self,
         request: Request<InspectStateRequest>,
     ) -> Result<Response<InspectStateResponse>, Status> {
-        let mock_response = self
-            .mock
-            .inspect_state(request.into_inner().query_payload)
-            .await;
+        let metadata = request.metadata().clone();
+        let remote_addr = request.remote_addr();
+        let request = request.into_inner();
+        self.received.lock().unwrap().push(ReceivedRequest {
+            metadata,
+            remote_addr,
+            session_id: request.session_id,
+            payload: request.query_payload.clone(),
+        });
+        let mock_response =
+            self.mock.inspect_state(request.query_payload).await?;
         let response = InspectStateResponse {
             session_id: SESSION_ID.to_string(),
             active_epoch_index: ACTIVE_EPOCH_INDEX,
-            processed_input_count: PROCESSED_INPUT_COUNT,
+            processed_input_count: mock_response
+                .processed_input_count
+                .unwrap_or(PROCESSED_INPUT_COUNT),
             exception_data: mock_response.exception,
             status: mock_response.completion_status as i32,
             reports: mock_response.reports,
@@ -224,14 +405,24 @@ impl<T: MockInspect> ServerManager for MockServerManager<T> {
         &self,
         _: Request<Void>,
     ) -> Result<Response<GetStatusResponse>, Status> {
-        unimplemented!()
+        Ok(Response::new(GetStatusResponse {
+            session_id: vec![SESSION_ID.to_string()],
+        }))
     }
 
     async fn get_session_status(
         &self,
-        _: Request<GetSessionStatusRequest>,
+        request: Request<GetSessionStatusRequest>,
     ) -> Result<Response<GetSessionStatusResponse>, Status> {
-        unimplemented!()
+        let session_id = request.into_inner().session_id;
+        if session_id != SESSION_ID {
+            return Err(Status::not_found("session id not found"));
+        }
+        Ok(Response::new(GetSessionStatusResponse {
+            session_id,
+            active_epoch_index: ACTIVE_EPOCH_INDEX,
+            ..Default::default()
+        }))
     }
 
     async fn get_epoch_status(
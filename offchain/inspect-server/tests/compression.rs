@@ -0,0 +1,226 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+struct LargeReportsInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for LargeReportsInspect {
+    async fn inspect_state(
+        &self,
+        payload: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse {
+            reports: vec![
+                Report {
+                    payload: payload.repeat(1000),
+                };
+                10
+            ],
+            exception: None,
+            completion_status: CompletionStatus::Accepted,
+            processed_input_count: None,
+        })
+    }
+}
+
+async fn inspect_with(compression: GrpcCompression) -> ReceivedRequest {
+    let server_manager =
+        MockServerManagerWrapper::start(LargeReportsInspect {}).await;
+    let mut config = test_config();
+    config.compression = compression;
+    let client = InspectClient::new(&config);
+    let response = client
+        .inspect(b"hello".to_vec())
+        .await
+        .expect("failed to inspect");
+    assert_eq!(response.reports.len(), 10);
+    assert_eq!(response.reports[0].payload, b"hello".repeat(1000));
+    let received = server_manager.received_requests();
+    server_manager.stop().await;
+    received[0].clone()
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_negotiates_gzip_when_enabled() {
+    let received = inspect_with(GrpcCompression::Gzip).await;
+    assert_eq!(received.metadata.get("grpc-encoding").unwrap(), "gzip");
+    let accepted = received
+        .metadata
+        .get("grpc-accept-encoding")
+        .expect("missing grpc-accept-encoding")
+        .to_str()
+        .unwrap();
+    assert!(accepted.contains("gzip"), "accepted: {}", accepted);
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_does_not_compress_by_default() {
+    let received = inspect_with(GrpcCompression::None).await;
+    assert!(received.metadata.get("grpc-encoding").is_none());
+    assert!(received.metadata.get("grpc-accept-encoding").is_none());
+}
+
+/// Answer every inspect with the same reports
+struct FixedReportsInspect {
+    reports: Vec<Report>,
+}
+
+#[tonic::async_trait]
+impl MockInspect for FixedReportsInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse {
+            reports: self.reports.clone(),
+            exception: None,
+            completion_status: CompletionStatus::Accepted,
+            processed_input_count: None,
+        })
+    }
+}
+
+const PROXY_ADDRESS: &str = "127.0.0.1:50003";
+
+/// Forward the connections to the server manager, counting the bytes sent by both sides.
+async fn start_counting_proxy() -> (Arc<AtomicUsize>, JoinHandle<()>) {
+    let listener = TcpListener::bind(PROXY_ADDRESS)
+        .await
+        .expect("failed to bind proxy");
+    let bytes = Arc::new(AtomicUsize::new(0));
+    let counter = bytes.clone();
+    let handle = tokio::spawn(async move {
+        loop {
+            let (client, _) =
+                listener.accept().await.expect("failed to accept");
+            let server = TcpStream::connect(SERVER_MANAGER_ADDRESS)
+                .await
+                .expect("failed to connect to server manager");
+            let (client_rx, client_tx) = client.into_split();
+            let (server_rx, server_tx) = server.into_split();
+            tokio::spawn(forward(client_rx, server_tx, counter.clone()));
+            tokio::spawn(forward(server_rx, client_tx, counter.clone()));
+        }
+    });
+    (bytes, handle)
+}
+
+async fn forward(
+    mut rx: OwnedReadHalf,
+    mut tx: OwnedWriteHalf,
+    counter: Arc<AtomicUsize>,
+) {
+    let mut buffer = vec![0; 64 * 1024];
+    while let Ok(n) = rx.read(&mut buffer).await {
+        if n == 0 || tx.write_all(&buffer[..n]).await.is_err() {
+            break;
+        }
+        counter.fetch_add(n, Ordering::Relaxed);
+    }
+}
+
+/// Reports like the JSON that applications usually return
+fn json_reports(total_size: usize) -> Vec<Report> {
+    let mut payload = String::from("[");
+    let mut index = 0;
+    while payload.len() < total_size {
+        payload.push_str(&format!(
+            r#"{{"index":{},"owner":"0x{:040x}","amount":"{}","status":"accepted"}},"#,
+            index,
+            index * 7919,
+            index * 1_000_000_007
+        ));
+        index += 1;
+    }
+    payload.truncate(total_size);
+    vec![Report {
+        payload: payload.into_bytes(),
+    }]
+}
+
+/// Reports that don't compress, such as hashes and signatures
+fn random_reports(total_size: usize) -> Vec<Report> {
+    let payload = (0..total_size).map(|_| rand::random::<u8>()).collect();
+    vec![Report { payload }]
+}
+
+const MEASURED_INSPECTS: u32 = 50;
+
+/// Return the bytes on the wire and the mean latency of an inspect.
+async fn measure(
+    reports: Vec<Report>,
+    compression: GrpcCompression,
+) -> (usize, Duration) {
+    let server_manager =
+        MockServerManagerWrapper::start(FixedReportsInspect { reports }).await;
+    let (bytes, proxy) = start_counting_proxy().await;
+    let mut config = test_config();
+    config.server_manager_address = PROXY_ADDRESS.to_string();
+    config.compression = compression;
+    config.max_response_bytes = usize::MAX;
+    let client = InspectClient::new(&config);
+    // Connect before measuring
+    client
+        .inspect(b"warm up".to_vec())
+        .await
+        .expect("failed to inspect");
+    bytes.store(0, Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..MEASURED_INSPECTS {
+        client
+            .inspect(b"hello".to_vec())
+            .await
+            .expect("failed to inspect");
+    }
+    let elapsed = start.elapsed() / MEASURED_INSPECTS;
+    let wire_bytes = bytes.load(Ordering::Relaxed) / MEASURED_INSPECTS as usize;
+    proxy.abort();
+    let _ = proxy.await;
+    server_manager.stop().await;
+    (wire_bytes, elapsed)
+}
+
+/// Print the bytes on the wire and the latency of an inspect with and without gzip, for the
+/// tables of the README.
+/// Run it with `cargo test -p inspect-server --release --test compression -- --ignored
+/// --nocapture`.
+#[tokio::test]
+#[serial_test::serial]
+#[ignore]
+async fn measure_compression() {
+    println!("| reports | size | compression | bytes on the wire | latency |");
+    println!("|---|---|---|---|---|");
+    for (kind, generate) in [
+        ("JSON", json_reports as fn(usize) -> Vec<Report>),
+        ("random", random_reports),
+    ] {
+        for size in [1024, 64 * 1024, 1024 * 1024] {
+            let reports = generate(size);
+            for compression in [GrpcCompression::None, GrpcCompression::Gzip] {
+                let (wire_bytes, latency) =
+                    measure(reports.clone(), compression).await;
+                println!(
+                    "| {} | {} KiB | {:?} | {} | {:.2} ms |",
+                    kind,
+                    size / 1024,
+                    compression,
+                    wire_bytes,
+                    latency.as_secs_f64() * 1000.0
+                );
+            }
+        }
+    }
+}
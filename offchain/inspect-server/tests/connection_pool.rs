@@ -0,0 +1,106 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+struct AcceptInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for AcceptInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse {
+            reports: vec![],
+            exception: None,
+            completion_status: CompletionStatus::Accepted,
+            processed_input_count: None,
+        })
+    }
+}
+
+/// Send sequential inspect requests and return the number of connections they arrived from
+async fn count_connections(connection_pool_size: usize) -> usize {
+    let mut config = test_config();
+    config.connection_pool_size = connection_pool_size;
+    let test_state =
+        TestState::setup_with_config(AcceptInspect {}, config).await;
+    for _ in 0..4 {
+        send_get_request("hello")
+            .await
+            .expect("failed to obtain response");
+    }
+    let received = test_state.received_requests();
+    assert_eq!(received.len(), 4);
+    let addresses: HashSet<_> = received
+        .iter()
+        .map(|request| request.remote_addr.expect("missing remote address"))
+        .collect();
+    test_state.teardown().await;
+    addresses.len()
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_uses_a_single_connection_by_default() {
+    assert_eq!(count_connections(1).await, 1);
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_distributes_requests_over_the_pool() {
+    assert_eq!(count_connections(2).await, 2);
+}
+
+/// Send two inspect requests with a pause between them and return whether they arrived from the
+/// same connection
+async fn reuses_connection(idle_timeout: Duration) -> bool {
+    let mut config = test_config();
+    config.idle_timeout = idle_timeout;
+    let test_state =
+        TestState::setup_with_config(AcceptInspect {}, config).await;
+    for _ in 0..2 {
+        send_get_request("hello")
+            .await
+            .expect("failed to obtain response");
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    let received = test_state.received_requests();
+    test_state.teardown().await;
+    received[0].remote_addr == received[1].remote_addr
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_keeps_the_connection_without_idle_timeout() {
+    assert!(reuses_connection(Duration::ZERO).await);
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_reconnects_after_the_idle_timeout() {
+    assert!(!reuses_connection(Duration::from_millis(100)).await);
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_inspects_with_keepalive() {
+    let mut config = test_config();
+    config.keepalive_interval = Duration::from_millis(50);
+    let test_state =
+        TestState::setup_with_config(AcceptInspect {}, config).await;
+    for _ in 0..2 {
+        send_get_request("hello")
+            .await
+            .expect("failed to obtain response");
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    let received = test_state.received_requests();
+    assert_eq!(received[0].remote_addr, received[1].remote_addr);
+    test_state.teardown().await;
+}
@@ -0,0 +1,86 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use std::time::Duration;
+
+struct SlowInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for SlowInspect {
+    async fn inspect_state(
+        &self,
+        payload: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        Ok(MockInspectResponse {
+            reports: vec![Report { payload }],
+            exception: None,
+            completion_status: CompletionStatus::Accepted,
+            processed_input_count: None,
+        })
+    }
+}
+
+/// Send two concurrent inspects with the given ids and return the number of inspects received
+/// by the server manager
+async fn send_concurrent_inspects(
+    dedup_request_ids: bool,
+    request_ids: [&str; 2],
+) -> usize {
+    let server_manager = MockServerManagerWrapper::start(SlowInspect {}).await;
+    let mut config = test_config();
+    config.dedup_request_ids = dedup_request_ids;
+    let client = InspectClient::new(&config);
+    let handles: Vec<_> = request_ids
+        .iter()
+        .map(|request_id| {
+            let client = client.clone();
+            let request_id = request_id.to_string();
+            tokio::spawn(async move {
+                client
+                    .inspect_with(
+                        b"hello".to_vec(),
+                        InspectOptions::default().request_id(request_id),
+                    )
+                    .await
+            })
+        })
+        .collect();
+    for handle in handles {
+        let response = handle
+            .await
+            .expect("failed to join handle")
+            .expect("failed to inspect");
+        assert_eq!(response.reports.len(), 1);
+        assert_eq!(response.reports[0].payload, b"hello");
+    }
+    let received = server_manager.received_requests().len();
+    server_manager.stop().await;
+    received
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_attaches_repeated_request_id_to_request_in_flight() {
+    let received =
+        send_concurrent_inspects(true, ["retry-id", "retry-id"]).await;
+    assert_eq!(received, 1);
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_sends_different_request_ids() {
+    let received = send_concurrent_inspects(true, ["first", "second"]).await;
+    assert_eq!(received, 2);
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_sends_repeated_request_id_when_disabled() {
+    let received =
+        send_concurrent_inspects(false, ["retry-id", "retry-id"]).await;
+    assert_eq!(received, 2);
+}
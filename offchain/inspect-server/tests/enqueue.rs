@@ -0,0 +1,165 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+
+/// Answer the inspects only when the test sends a response
+struct SyncInspect {
+    response_rx: Mutex<mpsc::Receiver<MockInspectResponse>>,
+}
+
+#[tonic::async_trait]
+impl MockInspect for SyncInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(self.response_rx.lock().await.recv().await.unwrap())
+    }
+}
+
+async fn setup(
+    enqueue_timeout: Duration,
+) -> (
+    MockServerManagerWrapper,
+    InspectClient,
+    mpsc::Sender<MockInspectResponse>,
+) {
+    let (response_tx, response_rx) = mpsc::channel(1000);
+    let mock = SyncInspect {
+        response_rx: Mutex::new(response_rx),
+    };
+    let server_manager = MockServerManagerWrapper::start(mock).await;
+    let mut config = test_config();
+    config.enqueue_timeout = enqueue_timeout;
+    let client = InspectClient::new(&config);
+    (server_manager, client, response_tx)
+}
+
+fn spawn_inspect(client: &InspectClient) -> JoinHandle<Result<(), String>> {
+    let client = client.clone();
+    tokio::spawn(async move {
+        client
+            .inspect_blocking(b"hello".to_vec(), None, None)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    })
+}
+
+/// Send enough inspect requests to occupy all the tasks and fill the queue
+async fn fill_queue(
+    client: &InspectClient,
+) -> Vec<JoinHandle<Result<(), String>>> {
+    let mut handles = vec![];
+    // Wait for the running requests to leave the queue before filling it
+    for count in [CONCURRENCY, QUEUE_SIZE] {
+        for _ in 0..count {
+            handles.push(spawn_inspect(client));
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    handles
+}
+
+async fn release(
+    response_tx: &mpsc::Sender<MockInspectResponse>,
+    handles: Vec<JoinHandle<Result<(), String>>>,
+) {
+    for _ in 0..handles.len() {
+        response_tx
+            .send(MockInspectResponse::default())
+            .await
+            .expect("failed to send response");
+    }
+    for handle in handles {
+        handle
+            .await
+            .expect("failed to join inspect")
+            .expect("inspect failed");
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_inspect_blocking_waits_for_space_in_queue() {
+    let (server_manager, client, response_tx) =
+        setup(Duration::from_secs(5)).await;
+    let mut handles = fill_queue(&client).await;
+    let waiting = spawn_inspect(&client);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!waiting.is_finished());
+    handles.push(waiting);
+    release(&response_tx, handles).await;
+    assert_eq!(
+        server_manager.received_requests().len(),
+        CONCURRENCY + QUEUE_SIZE + 1
+    );
+    server_manager.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_inspect_blocking_fails_after_enqueue_timeout() {
+    let (server_manager, client, response_tx) =
+        setup(Duration::from_millis(100)).await;
+    let handles = fill_queue(&client).await;
+    let err = spawn_inspect(&client)
+        .await
+        .expect("failed to join inspect")
+        .expect_err("queue should be full");
+    assert_eq!(
+        err,
+        format!("Inspect queue is full (capacity: {})", QUEUE_SIZE)
+    );
+    release(&response_tx, handles).await;
+    server_manager.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_inspect_fails_immediately_when_queue_is_full() {
+    let (server_manager, client, response_tx) =
+        setup(Duration::from_secs(5)).await;
+    let handles = fill_queue(&client).await;
+    let err = tokio::time::timeout(
+        Duration::from_millis(100),
+        client.inspect(b"hello".to_vec()),
+    )
+    .await
+    .expect("inspect should not wait for the queue")
+    .expect_err("queue should be full");
+    assert_eq!(
+        err.to_string(),
+        format!("Inspect queue is full (capacity: {})", QUEUE_SIZE)
+    );
+    release(&response_tx, handles).await;
+    server_manager.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_inspect_blocking_gives_up_when_cancelled() {
+    let (server_manager, client, response_tx) =
+        setup(Duration::from_secs(5)).await;
+    let handles = fill_queue(&client).await;
+    let waiting = spawn_inspect(&client);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    waiting.abort();
+    assert!(waiting
+        .await
+        .expect_err("inspect was aborted")
+        .is_cancelled());
+    release(&response_tx, handles).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(
+        server_manager.received_requests().len(),
+        CONCURRENCY + QUEUE_SIZE
+    );
+    server_manager.stop().await;
+}
@@ -0,0 +1,71 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use tracing_test::traced_test;
+
+const TRANSPORT: &str = "error.kind=\"transport\"";
+const APPLICATION: &str = "error.kind=\"application\"";
+
+/// Fail every request with the given status
+struct FailingInspect {
+    status: Status,
+}
+
+#[tonic::async_trait]
+impl MockInspect for FailingInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Err(self.status.clone())
+    }
+}
+
+async fn inspect(client: &InspectClient) {
+    client
+        .inspect(b"hello".to_vec())
+        .await
+        .expect_err("inspect should fail");
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[traced_test]
+async fn test_it_logs_the_status_of_the_server_manager_as_application() {
+    let server_manager = MockServerManagerWrapper::start(FailingInspect {
+        status: Status::failed_precondition("machine is busy"),
+    })
+    .await;
+    let client = InspectClient::new(&test_config());
+    inspect(&client).await;
+    logs_assert(|lines: &[&str]| {
+        let failures: Vec<&&str> = lines
+            .iter()
+            .filter(|line| line.contains("error.kind="))
+            .collect();
+        match failures.as_slice() {
+            [failure] => {
+                assert!(failure.contains(APPLICATION));
+                assert!(failure.contains("error.code=FailedPrecondition"));
+                assert!(failure.contains("error.message=\"machine is busy\""));
+                Ok(())
+            }
+            _ => Err(format!("expected one failure, got {:?}", failures)),
+        }
+    });
+    server_manager.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[traced_test]
+async fn test_it_logs_the_connect_failures_as_transport() {
+    // There is no server manager listening
+    let client = InspectClient::new(&test_config());
+    inspect(&client).await;
+    assert!(logs_contain(TRANSPORT));
+    assert!(!logs_contain(APPLICATION));
+}
@@ -0,0 +1,187 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use inspect_server::config::InspectServerConfig;
+use inspect_server::error::InspectError;
+use inspect_server::inspect::InspectStateResponse;
+
+struct FixedResponseInspect {
+    response: MockInspectResponse,
+}
+
+#[tonic::async_trait]
+impl MockInspect for FixedResponseInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(self.response.clone())
+    }
+}
+
+async fn inspect(
+    response: MockInspectResponse,
+) -> Result<InspectStateResponse, InspectError> {
+    inspect_with_config(response, test_config()).await
+}
+
+async fn inspect_with_config(
+    response: MockInspectResponse,
+    config: InspectServerConfig,
+) -> Result<InspectStateResponse, InspectError> {
+    let server_manager =
+        MockServerManagerWrapper::start(FixedResponseInspect { response })
+            .await;
+    let client = InspectClient::new(&config);
+    let result = client.inspect(b"hello".to_vec()).await;
+    server_manager.stop().await;
+    result
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_inspect_returns_accepted_response() {
+    let response = inspect(MockInspectResponse {
+        reports: vec![Report {
+            payload: vec![1, 2, 3],
+        }],
+        exception: None,
+        completion_status: CompletionStatus::Accepted,
+        processed_input_count: None,
+    })
+    .await
+    .expect("inspect should succeed");
+    assert_eq!(response.status, CompletionStatus::Accepted as i32);
+    assert_eq!(response.exception_data, None);
+    assert_eq!(response.reports.len(), 1);
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_inspect_fails_with_rejected_status() {
+    let err = inspect(MockInspectResponse {
+        reports: vec![Report {
+            payload: vec![1, 2, 3],
+        }],
+        exception: None,
+        completion_status: CompletionStatus::Rejected,
+        processed_input_count: None,
+    })
+    .await
+    .expect_err("rejected inspect should fail");
+    match err {
+        InspectError::MachineException {
+            status,
+            payload,
+            message,
+            reports,
+            ..
+        } => {
+            assert_eq!(status, CompletionStatus::Rejected);
+            assert!(payload.is_empty());
+            assert_eq!(message, None);
+            assert_eq!(reports.len(), 1);
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_inspect_fails_with_machine_exception() {
+    let err = inspect(MockInspectResponse {
+        reports: vec![Report {
+            payload: vec![1, 2, 3],
+        }],
+        exception: Some(vec![4, 5, 6]),
+        completion_status: CompletionStatus::Exception,
+        processed_input_count: None,
+    })
+    .await
+    .expect_err("inspect should fail");
+    match err {
+        InspectError::MachineException {
+            status,
+            payload,
+            message,
+            reports,
+            processed_input_count,
+        } => {
+            assert_eq!(status, CompletionStatus::Exception);
+            assert_eq!(payload, vec![4, 5, 6]);
+            assert_eq!(message.as_deref(), Some("\u{4}\u{5}\u{6}"));
+            assert_eq!(
+                reports,
+                vec![Report {
+                    payload: vec![1, 2, 3]
+                }]
+            );
+            assert_eq!(processed_input_count, PROCESSED_INPUT_COUNT);
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+}
+
+fn exception_response(payload: &[u8]) -> MockInspectResponse {
+    MockInspectResponse {
+        reports: vec![],
+        exception: Some(payload.to_vec()),
+        completion_status: CompletionStatus::Exception,
+        processed_input_count: None,
+    }
+}
+
+/// Return the payload and the message of the MachineException.
+fn exception(
+    result: Result<InspectStateResponse, InspectError>,
+) -> (Vec<u8>, Option<String>) {
+    match result.expect_err("inspect should fail") {
+        InspectError::MachineException {
+            payload, message, ..
+        } => (payload, message),
+        err => panic!("unexpected error: {}", err),
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_machine_exception_decodes_utf8_message() {
+    let sent = "execution reverted: insufficient funds";
+    let result = inspect(exception_response(sent.as_bytes())).await;
+    let err = result
+        .as_ref()
+        .expect_err("inspect should fail")
+        .to_string();
+    assert_eq!(
+        err,
+        format!("Inspect failed with status Exception: {}", sent)
+    );
+    let (payload, message) = exception(result);
+    assert_eq!(payload, sent.as_bytes());
+    assert_eq!(message.as_deref(), Some(sent));
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_machine_exception_keeps_binary_payload() {
+    let sent = vec![0xff, 0x00, 0xfe, 0x80];
+    let (payload, message) =
+        exception(inspect(exception_response(&sent)).await);
+    assert_eq!(payload, sent);
+    assert_eq!(message, None);
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_machine_exception_message_decoding_can_be_disabled() {
+    let mut config = test_config();
+    config.decode_exception_messages = false;
+    let result =
+        inspect_with_config(exception_response(b"reverted"), config).await;
+    let (payload, message) = exception(result);
+    assert_eq!(payload, b"reverted");
+    assert_eq!(message, None);
+}
@@ -0,0 +1,71 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use inspect_server::error::InspectError;
+
+struct EchoInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for EchoInspect {
+    async fn inspect_state(
+        &self,
+        payload: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse {
+            reports: vec![Report { payload }],
+            exception: None,
+            completion_status: CompletionStatus::Accepted,
+            processed_input_count: None,
+        })
+    }
+}
+
+/// Address where nothing is listening, so the connections are refused.
+fn unreachable_address() -> String {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().to_string()
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_fails_over_to_the_next_endpoint() {
+    let server_manager = MockServerManagerWrapper::start(EchoInspect {}).await;
+    let mut config = test_config();
+    config.server_manager_address =
+        format!("{},{}", unreachable_address(), SERVER_MANAGER_ADDRESS);
+    let client = InspectClient::new(&config);
+    client
+        .check_ready()
+        .await
+        .expect("server manager should be ready");
+    for payload in [b"hello".to_vec(), b"world".to_vec()] {
+        let response = client
+            .inspect(payload.clone())
+            .await
+            .expect("failed to inspect");
+        assert_eq!(response.reports[0].payload, payload);
+    }
+    assert_eq!(server_manager.received_requests().len(), 2);
+    server_manager.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_fails_when_all_endpoints_are_unreachable() {
+    let mut config = test_config();
+    config.server_manager_address =
+        format!("{},{}", unreachable_address(), unreachable_address());
+    let client = InspectClient::new(&config);
+    let err = client
+        .inspect(b"hello".to_vec())
+        .await
+        .expect_err("inspect should fail");
+    assert!(
+        matches!(err, InspectError::FailedToConnect { .. }),
+        "unexpected error {:?}",
+        err
+    );
+}
@@ -0,0 +1,103 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+/// Fail every request with the given status
+struct FailingInspect {
+    status: Status,
+}
+
+#[tonic::async_trait]
+impl MockInspect for FailingInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Err(self.status.clone())
+    }
+}
+
+async fn test_get_status(
+    grpc_status: Status,
+    expected_status: StatusCode,
+    expected_message: &str,
+) {
+    let test_state = TestState::setup(FailingInspect {
+        status: grpc_status,
+    })
+    .await;
+    let (status, message) = send_get_request("hello")
+        .await
+        .expect_err("inspect should fail");
+    assert_eq!(status, expected_status);
+    assert_eq!(message, expected_message);
+    test_state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_maps_not_found_to_session_not_found() {
+    test_get_status(
+        Status::not_found("session not found"),
+        StatusCode::NOT_FOUND,
+        "Session \"default session\" not found in server manager",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_maps_invalid_argument() {
+    test_get_status(
+        Status::invalid_argument("bad payload"),
+        StatusCode::BAD_REQUEST,
+        "Invalid argument: bad payload",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_maps_internal_to_server_manager_internal() {
+    test_get_status(
+        Status::internal("machine crashed"),
+        StatusCode::BAD_GATEWAY,
+        "Server manager internal error: machine crashed",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_maps_unknown_codes_to_inspect_failed() {
+    test_get_status(
+        Status::permission_denied("denied"),
+        StatusCode::BAD_REQUEST,
+        "Failed to inspect state: denied",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_inspect_keeps_the_grpc_code_and_details() {
+    let server_manager = MockServerManagerWrapper::start(FailingInspect {
+        status: Status::with_details(
+            tonic::Code::FailedPrecondition,
+            "machine is busy",
+            b"structured details".to_vec().into(),
+        ),
+    })
+    .await;
+    let client = InspectClient::new(&test_config());
+    let err = client
+        .inspect(b"hello".to_vec())
+        .await
+        .expect_err("inspect should fail");
+    assert_eq!(err.to_string(), "Failed to inspect state: machine is busy");
+    assert_eq!(err.grpc_code(), Some(tonic::Code::FailedPrecondition));
+    assert_eq!(err.grpc_details(), Some(&b"structured details"[..]));
+    server_manager.stop().await;
+}
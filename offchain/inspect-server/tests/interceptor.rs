@@ -0,0 +1,59 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+struct AcceptInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for AcceptInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse::default())
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_applies_the_interceptor_to_inspect_requests() {
+    let server_manager =
+        MockServerManagerWrapper::start(AcceptInspect {}).await;
+    let client = InspectClient::with_interceptor(&test_config(), |request| {
+        request
+            .metadata_mut()
+            .insert("authorization", "Bearer token".parse().unwrap());
+    });
+    client
+        .inspect_with(
+            b"hello".to_vec(),
+            InspectOptions::default().request_id("id"),
+        )
+        .await
+        .expect("failed to inspect");
+    let received = server_manager.received_requests();
+    assert_eq!(received.len(), 1);
+    assert_eq!(
+        received[0].metadata.get("authorization").unwrap(),
+        "Bearer token"
+    );
+    assert_eq!(received[0].metadata.get("request-id").unwrap(), "id");
+    server_manager.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_sends_no_extra_metadata_without_interceptor() {
+    let server_manager =
+        MockServerManagerWrapper::start(AcceptInspect {}).await;
+    let client = InspectClient::new(&test_config());
+    client
+        .inspect(b"hello".to_vec())
+        .await
+        .expect("failed to inspect");
+    let received = server_manager.received_requests();
+    assert!(received[0].metadata.get("authorization").is_none());
+    server_manager.stop().await;
+}
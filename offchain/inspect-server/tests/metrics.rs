@@ -0,0 +1,184 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+#![cfg(feature = "metrics")]
+
+mod common;
+use crate::common::*;
+
+struct AcceptInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for AcceptInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse {
+            reports: vec![],
+            exception: None,
+            completion_status: CompletionStatus::Accepted,
+            processed_input_count: None,
+        })
+    }
+}
+
+async fn get_metrics() -> String {
+    let url = format!("http://{}/metrics", INSPECT_SERVER_ADDRESS);
+    let response = reqwest::get(url).await.expect("failed to get metrics");
+    assert_eq!(response.status(), StatusCode::OK);
+    response.text().await.expect("failed to read metrics")
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_counts_accepted_inspects() {
+    let test_state = TestState::setup(AcceptInspect {}).await;
+    for _ in 0..2 {
+        send_get_request("hello")
+            .await
+            .expect("failed to obtain response");
+    }
+    let metrics = get_metrics().await;
+    assert!(
+        metrics.contains(
+            "cartesi_rollups_inspect_server_inspect_outcomes_total{outcome=\"Accepted\"} 2"
+        ),
+        "{}",
+        metrics
+    );
+    assert!(
+        metrics.contains(
+            "cartesi_rollups_inspect_server_inspect_latency_seconds_count 2"
+        ),
+        "{}",
+        metrics
+    );
+    assert!(
+        metrics
+            .contains("cartesi_rollups_inspect_server_inspect_queue_depth 0"),
+        "{}",
+        metrics
+    );
+    test_state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_counts_inspect_errors() {
+    let inspect_server = InspectServerWrapper::start().await;
+    send_get_request("hello")
+        .await
+        .expect_err("server manager should be down");
+    let metrics = get_metrics().await;
+    assert!(
+        metrics.contains(
+            "cartesi_rollups_inspect_server_inspect_outcomes_total{outcome=\"FailedToConnect\"} 1"
+        ),
+        "{}",
+        metrics
+    );
+    inspect_server.stop().await;
+}
+
+/// Value of the metric in the Prometheus text format.
+fn metric_value(metrics: &str, name: &str) -> u64 {
+    metrics
+        .lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+        .unwrap_or_else(|| panic!("metric {} not found in {}", name, metrics))
+        .parse()
+        .expect("metric should be an integer")
+}
+
+const CONNECTS: &str =
+    "cartesi_rollups_inspect_server_server_manager_connects_total";
+const CONNECT_FAILURES: &str =
+    "cartesi_rollups_inspect_server_server_manager_connect_failures_total";
+const ACCEPTED: &str =
+    "cartesi_rollups_inspect_server_inspect_outcomes_total{outcome=\"Accepted\"}";
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_counts_connects_apart_from_reused_connections() {
+    let test_state = TestState::setup(AcceptInspect {}).await;
+    send_get_request("hello")
+        .await
+        .expect("failed to obtain response");
+    let metrics = get_metrics().await;
+    let connects = metric_value(&metrics, CONNECTS);
+    assert!(connects >= 1, "{}", metrics);
+    assert_eq!(metric_value(&metrics, ACCEPTED), 1);
+    for _ in 0..3 {
+        send_get_request("hello")
+            .await
+            .expect("failed to obtain response");
+    }
+    let metrics = get_metrics().await;
+    assert_eq!(metric_value(&metrics, ACCEPTED), 4);
+    assert_eq!(metric_value(&metrics, CONNECTS), connects);
+    assert_eq!(metric_value(&metrics, CONNECT_FAILURES), 0);
+    test_state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_counts_connect_failures() {
+    let inspect_server = InspectServerWrapper::start().await;
+    send_get_request("hello")
+        .await
+        .expect_err("server manager should be down");
+    let metrics = get_metrics().await;
+    assert!(metric_value(&metrics, CONNECT_FAILURES) >= 1, "{}", metrics);
+    assert_eq!(metric_value(&metrics, CONNECTS), 0);
+    inspect_server.stop().await;
+}
+
+/// Fail the payloads that start with "fail"
+struct HalfFailingInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for HalfFailingInspect {
+    async fn inspect_state(
+        &self,
+        payload: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        if payload.starts_with(b"fail") {
+            return Err(Status::invalid_argument("bad payload"));
+        }
+        Ok(MockInspectResponse::default())
+    }
+}
+
+fn encode_metrics(client: &InspectClient) -> String {
+    let registry =
+        prometheus_client::registry::Registry::from(client.metrics().clone());
+    let mut metrics = String::new();
+    prometheus_client::encoding::text::encode(&mut metrics, &registry)
+        .expect("failed to encode metrics");
+    metrics
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_records_the_success_ratio_of_a_batch() {
+    let server_manager =
+        MockServerManagerWrapper::start(HalfFailingInspect {}).await;
+    let client = InspectClient::new(&test_config());
+    let payloads = ["ok", "fail", "ok", "fail"]
+        .iter()
+        .map(|payload| payload.as_bytes().to_vec())
+        .collect();
+    let results = client.inspect_batch(payloads).await;
+    assert_eq!(results.iter().filter(|result| result.is_err()).count(), 2);
+    let metrics = encode_metrics(&client);
+    for expected in [
+        "cartesi_rollups_inspect_server_inspect_batch_success_ratio_sum 0.5\n",
+        "cartesi_rollups_inspect_server_inspect_batch_success_ratio_count 1\n",
+        "cartesi_rollups_inspect_server_inspect_batch_items_total{dominant_error=\"InvalidArgument\"} 4\n",
+        "cartesi_rollups_inspect_server_inspect_batch_failed_items_total{dominant_error=\"InvalidArgument\"} 2\n",
+    ] {
+        assert!(metrics.contains(expected), "{}", metrics);
+    }
+    server_manager.stop().await;
+}
@@ -0,0 +1,58 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+struct DefaultInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for DefaultInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse::default())
+    }
+}
+
+/// Send an inspect and return the metadata received by the server manager
+async fn received_metadata(
+    node_id: Option<&str>,
+) -> tonic::metadata::MetadataMap {
+    let mut config = test_config();
+    config.node_id = node_id.map(String::from);
+    let state = TestState::setup_with_config(DefaultInspect {}, config).await;
+    send_get_request("hello")
+        .await
+        .expect("failed to obtain response");
+    let requests = state.received_requests();
+    state.teardown().await;
+    assert_eq!(requests.len(), 1);
+    requests[0].metadata.clone()
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_sends_the_node_version() {
+    let metadata = received_metadata(None).await;
+    let user_agent = metadata
+        .get("user-agent")
+        .expect("missing user-agent")
+        .to_str()
+        .expect("invalid user-agent");
+    // Tonic appends its own user agent
+    let version = format!("rollups-node/{} ", env!("CARGO_PKG_VERSION"));
+    assert!(user_agent.starts_with(&version));
+    assert!(metadata.get("x-node-id").is_none());
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_sends_the_node_id() {
+    let metadata = received_metadata(Some("node-1")).await;
+    assert_eq!(
+        metadata.get("x-node-id").expect("missing x-node-id"),
+        "node-1"
+    );
+}
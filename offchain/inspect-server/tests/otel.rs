@@ -0,0 +1,109 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+#![cfg(feature = "otel")]
+
+mod common;
+use crate::common::*;
+
+use opentelemetry::sdk::propagation::TraceContextPropagator;
+use opentelemetry::sdk::trace::TracerProvider;
+use opentelemetry::trace::{TraceContextExt, TracerProvider as _};
+use std::sync::Once;
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::layer::SubscriberExt;
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+const PARENT_SPAN_ID: &str = "00f067aa0ba902b7";
+
+struct DefaultInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for DefaultInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse::default())
+    }
+}
+
+/// Trace the spans without exporting them, for all the threads of the test.
+fn setup_tracing() {
+    static SETUP: Once = Once::new();
+    SETUP.call_once(|| {
+        opentelemetry::global::set_text_map_propagator(
+            TraceContextPropagator::new(),
+        );
+        // The tracer only keeps a weak reference to its provider
+        let provider = TracerProvider::builder().build();
+        let tracer = provider.tracer("test");
+        opentelemetry::global::set_tracer_provider(provider);
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(tracer));
+        tracing::subscriber::set_global_default(subscriber)
+            .expect("failed to set the subscriber");
+    });
+}
+
+fn received_traceparent(state: &TestState) -> String {
+    let requests = state.received_requests();
+    assert_eq!(requests.len(), 1);
+    requests[0]
+        .metadata
+        .get("traceparent")
+        .expect("missing traceparent")
+        .to_str()
+        .expect("invalid traceparent")
+        .to_owned()
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_continues_the_trace_of_the_http_request() {
+    setup_tracing();
+    let state = TestState::setup(DefaultInspect {}).await;
+    let url = format!("http://{}/inspect/hello", INSPECT_SERVER_ADDRESS);
+    let status = reqwest::Client::new()
+        .get(url)
+        .header(
+            "traceparent",
+            format!("00-{}-{}-01", TRACE_ID, PARENT_SPAN_ID),
+        )
+        .send()
+        .await
+        .expect("failed to send inspect")
+        .status();
+    assert_eq!(status, StatusCode::OK);
+    let traceparent = received_traceparent(&state);
+    let parts: Vec<&str> = traceparent.split('-').collect();
+    assert_eq!(parts.len(), 4, "invalid traceparent {}", traceparent);
+    assert_eq!(parts[1], TRACE_ID);
+    // The parent of the server manager span is the inspect_state span
+    assert_ne!(parts[2], PARENT_SPAN_ID);
+    state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_propagates_the_trace_of_the_caller() {
+    setup_tracing();
+    let state = TestState::setup(DefaultInspect {}).await;
+    let client = InspectClient::new(&test_config());
+    let span = tracing::info_span!("caller");
+    let trace_id = span.context().span().span_context().trace_id();
+    client
+        .inspect(b"hello".to_vec())
+        .instrument(span)
+        .await
+        .expect("inspect should succeed");
+    let traceparent = received_traceparent(&state);
+    assert!(
+        traceparent.starts_with(&format!("00-{}-", trace_id)),
+        "traceparent {} is not part of trace {}",
+        traceparent,
+        trace_id
+    );
+    state.teardown().await;
+}
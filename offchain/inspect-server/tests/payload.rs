@@ -3,18 +3,23 @@
 
 mod common;
 use crate::common::*;
+use inspect_server::config::InspectServerConfig;
 use inspect_server::server::CARTESI_MACHINE_RX_BUFFER_LIMIT;
 
 struct EchoInspect {}
 
 #[tonic::async_trait]
 impl MockInspect for EchoInspect {
-    async fn inspect_state(&self, payload: Vec<u8>) -> MockInspectResponse {
-        MockInspectResponse {
+    async fn inspect_state(
+        &self,
+        payload: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse {
             reports: vec![Report { payload }],
             exception: None,
             completion_status: CompletionStatus::Accepted,
-        }
+            processed_input_count: None,
+        })
     }
 }
 
@@ -133,3 +138,127 @@ async fn test_post_fails_when_payload_over_limit() {
         .expect_err("Payload reached size limit");
     test_state.teardown().await;
 }
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_fails_when_payload_over_configured_limit() {
+    let mut config = test_config();
+    config.max_payload_size = 5;
+    let test_state = TestState::setup_with_config(EchoInspect {}, config).await;
+    let (status, message) = send_get_request("hello world")
+        .await
+        .expect_err("payload should be too large");
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(
+        message,
+        "Inspect payload is too large (11 bytes, limit: 5 bytes)"
+    );
+    assert!(test_state.received_requests().is_empty());
+    test_state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_payload_on_configured_limit() {
+    let mut config = test_config();
+    config.max_payload_size = 5;
+    let test_state = TestState::setup_with_config(EchoInspect {}, config).await;
+    send_get_request("hello")
+        .await
+        .expect("failed to obtain response");
+    assert_eq!(test_state.received_requests().len(), 1);
+    test_state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_post_fails_when_body_over_configured_limit() {
+    let mut config = test_config();
+    config.max_http_body_bytes = 5;
+    let test_state = TestState::setup_with_config(EchoInspect {}, config).await;
+    let url = format!("http://{}/inspect", INSPECT_SERVER_ADDRESS);
+    let response = reqwest::Client::new()
+        .post(url)
+        .body("hello world")
+        .send()
+        .await
+        .expect("failed to send inspect");
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(test_state.received_requests().is_empty());
+    send_post_request("hello")
+        .await
+        .expect("failed to obtain response");
+    assert_eq!(test_state.received_requests().len(), 1);
+    test_state.teardown().await;
+}
+
+async fn test_get_and_post_payloads(
+    config: InspectServerConfig,
+    get_payload: &str,
+    post_payload: &str,
+) {
+    let test_state = TestState::setup_with_config(EchoInspect {}, config).await;
+    let get_response = send_get_request(get_payload)
+        .await
+        .expect("failed to obtain response");
+    let post_response = send_post_request(post_payload)
+        .await
+        .expect("failed to obtain response");
+    assert_eq!(get_response.status, post_response.status);
+    assert_eq!(
+        get_response.reports[0].payload,
+        post_response.reports[0].payload
+    );
+    let requests = test_state.received_requests();
+    assert_eq!(requests[0].payload, requests[1].payload);
+    test_state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_url_encoded_payload_matches_post() {
+    test_get_and_post_payloads(test_config(), "hello%20world", "hello world")
+        .await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_hex_payload_matches_post() {
+    let mut config = test_config();
+    config.get_payload_encoding = GetPayloadEncoding::Hex;
+    for payload in ["0x68656c6c6f", "68656c6c6f", "0x68656C6C6F"] {
+        test_get_and_post_payloads(config.clone(), payload, "hello").await;
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_fails_with_invalid_hex_payload() {
+    let mut config = test_config();
+    config.get_payload_encoding = GetPayloadEncoding::Hex;
+    let test_state = TestState::setup_with_config(EchoInspect {}, config).await;
+    for payload in ["hello", "0x123", "0x00?key=value"] {
+        let (status, _) = send_get_request(payload)
+            .await
+            .expect_err("payload should be invalid");
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+    assert!(test_state.received_requests().is_empty());
+    test_state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_fails_with_hex_payload_and_query() {
+    let mut config = test_config();
+    config.get_payload_encoding = GetPayloadEncoding::Hex;
+    let test_state = TestState::setup_with_config(EchoInspect {}, config).await;
+    let (status, message) = send_get_request("0xdead?x=1")
+        .await
+        .expect_err("query should be rejected");
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(message.contains("query string is not allowed"));
+    assert!(!message.contains("invalid hex payload"));
+    assert!(test_state.received_requests().is_empty());
+    test_state.teardown().await;
+}
@@ -0,0 +1,126 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use inspect_server::inspect::Priority;
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+
+/// Answer the inspects only when the test sends a response
+struct SyncInspect {
+    response_rx: Mutex<mpsc::Receiver<MockInspectResponse>>,
+}
+
+#[tonic::async_trait]
+impl MockInspect for SyncInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(self.response_rx.lock().await.recv().await.unwrap())
+    }
+}
+
+fn spawn_inspect(
+    client: &InspectClient,
+    payload: &str,
+    priority: Priority,
+) -> JoinHandle<()> {
+    let client = client.clone();
+    let payload = payload.as_bytes().to_vec();
+    tokio::spawn(async move {
+        client
+            .inspect_with(payload, InspectOptions::default().priority(priority))
+            .await
+            .expect("failed to inspect");
+    })
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_serves_the_high_priority_requests_first() {
+    let (response_tx, response_rx) = mpsc::channel(1000);
+    let mock = SyncInspect {
+        response_rx: Mutex::new(response_rx),
+    };
+    let server_manager = MockServerManagerWrapper::start(mock).await;
+    let mut config = test_config();
+    config.concurrency = 1;
+    config.queue_size = 10;
+    let client = InspectClient::new(&config);
+    // Occupy the only task, so the next requests wait in the queue
+    let mut handles = vec![spawn_inspect(&client, "busy", Priority::Normal)];
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    for payload in ["low 1", "low 2", "low 3", "high"] {
+        let priority = match payload {
+            "high" => Priority::High,
+            _ => Priority::Low,
+        };
+        handles.push(spawn_inspect(&client, payload, priority));
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    for _ in 0..handles.len() {
+        response_tx
+            .send(MockInspectResponse::default())
+            .await
+            .expect("failed to send response");
+    }
+    for handle in handles {
+        handle.await.expect("failed to wait for inspect");
+    }
+    let payloads: Vec<Vec<u8>> = server_manager
+        .received_requests()
+        .into_iter()
+        .map(|request| request.payload)
+        .collect();
+    let expected: Vec<Vec<u8>> = ["busy", "high", "low 1", "low 2", "low 3"]
+        .iter()
+        .map(|payload| payload.as_bytes().to_vec())
+        .collect();
+    assert_eq!(payloads, expected);
+    server_manager.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_promotes_the_requests_that_waited() {
+    let (response_tx, response_rx) = mpsc::channel(1000);
+    let mock = SyncInspect {
+        response_rx: Mutex::new(response_rx),
+    };
+    let server_manager = MockServerManagerWrapper::start(mock).await;
+    let mut config = test_config();
+    config.concurrency = 1;
+    config.queue_size = 10;
+    config.priority_aging = Duration::from_millis(100);
+    let client = InspectClient::new(&config);
+    let mut handles = vec![spawn_inspect(&client, "busy", Priority::Normal)];
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    handles.push(spawn_inspect(&client, "low", Priority::Low));
+    // The low priority request waits long enough to become a high priority one
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    handles.push(spawn_inspect(&client, "high", Priority::High));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    for _ in 0..handles.len() {
+        response_tx
+            .send(MockInspectResponse::default())
+            .await
+            .expect("failed to send response");
+    }
+    for handle in handles {
+        handle.await.expect("failed to wait for inspect");
+    }
+    let payloads: Vec<Vec<u8>> = server_manager
+        .received_requests()
+        .into_iter()
+        .map(|request| request.payload)
+        .collect();
+    assert_eq!(
+        payloads,
+        vec![b"busy".to_vec(), b"low".to_vec(), b"high".to_vec()]
+    );
+    server_manager.stop().await;
+}
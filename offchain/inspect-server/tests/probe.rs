@@ -0,0 +1,70 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use inspect_server::error::InspectError;
+use inspect_server::inspect::probe_server_manager;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tonic::transport::Endpoint;
+
+const PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+
+struct AcceptInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for AcceptInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse::default())
+    }
+}
+
+fn server_manager_endpoint() -> Endpoint {
+    Endpoint::from_shared(format!("http://{}", SERVER_MANAGER_ADDRESS))
+        .expect("address should be valid")
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_probe_succeeds_when_server_manager_is_reachable() {
+    let server_manager =
+        MockServerManagerWrapper::start(AcceptInspect {}).await;
+    probe_server_manager(server_manager_endpoint(), PROBE_TIMEOUT)
+        .await
+        .expect("server manager should be reachable");
+    server_manager.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_probe_fails_when_server_manager_is_unreachable() {
+    let err = probe_server_manager(server_manager_endpoint(), PROBE_TIMEOUT)
+        .await
+        .expect_err("server manager should be unreachable");
+    assert!(
+        matches!(err, InspectError::FailedToConnect { .. }),
+        "{:?}",
+        err
+    );
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_probe_times_out_when_server_manager_does_not_answer() {
+    // The connections are queued by the listener, but never answered
+    let _listener = TcpListener::bind(SERVER_MANAGER_ADDRESS)
+        .await
+        .expect("failed to bind listener");
+    let err = probe_server_manager(server_manager_endpoint(), PROBE_TIMEOUT)
+        .await
+        .expect_err("probe should time out");
+    match err {
+        InspectError::Timeout { elapsed } => assert_eq!(elapsed, PROBE_TIMEOUT),
+        err => panic!("unexpected error {:?}", err),
+    }
+}
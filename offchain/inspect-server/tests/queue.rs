@@ -6,7 +6,8 @@ use crate::common::*;
 
 use futures::stream::FuturesUnordered;
 use futures::StreamExt;
-use tokio::sync::{mpsc, Mutex};
+use inspect_server::server::{QUEUE_CAPACITY_HEADER, QUEUE_DEPTH_HEADER};
+use tokio::sync::{mpsc, Barrier, Mutex};
 
 struct SyncInspect {
     response_rx: Mutex<mpsc::Receiver<MockInspectResponse>>,
@@ -14,8 +15,11 @@ struct SyncInspect {
 
 #[tonic::async_trait]
 impl MockInspect for SyncInspect {
-    async fn inspect_state(&self, _: Vec<u8>) -> MockInspectResponse {
-        self.response_rx.lock().await.recv().await.unwrap()
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(self.response_rx.lock().await.recv().await.unwrap())
     }
 }
 
@@ -66,6 +70,36 @@ async fn test_get_succeeds_after_server_manager_starts() {
     inspect_server.stop().await;
 }
 
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_reconnects_after_server_manager_restarts() {
+    let inspect_server = InspectServerWrapper::start().await;
+    let (mock, response_tx) = SyncInspect::setup();
+    let server_manager = MockServerManagerWrapper::start(mock).await;
+    response_tx
+        .send(MockInspectResponse::default())
+        .await
+        .expect("failed to send response");
+    send_get_request("hello")
+        .await
+        .expect("failed to obtain response");
+    server_manager.stop().await;
+    send_get_request("hello")
+        .await
+        .expect_err("request should fail while server manager is down");
+    let (mock, response_tx) = SyncInspect::setup();
+    let server_manager = MockServerManagerWrapper::start(mock).await;
+    response_tx
+        .send(MockInspectResponse::default())
+        .await
+        .expect("failed to send response");
+    send_get_request("hello")
+        .await
+        .expect("failed to obtain response");
+    server_manager.stop().await;
+    inspect_server.stop().await;
+}
+
 #[tokio::test]
 #[serial_test::serial]
 async fn test_get_it_handle_concurrent_inspect_requests() {
@@ -97,16 +131,77 @@ async fn test_get_it_handle_concurrent_inspect_requests() {
     state.teardown().await;
 }
 
+/// Wait until the expected number of inspect requests arrive at the same time
+struct BarrierInspect {
+    barrier: Barrier,
+}
+
+#[tonic::async_trait]
+impl MockInspect for BarrierInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        self.barrier.wait().await;
+        Ok(MockInspectResponse::default())
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_it_processes_inspect_requests_concurrently() {
+    let mock = BarrierInspect {
+        barrier: Barrier::new(CONCURRENCY),
+    };
+    let state = TestState::setup(mock).await;
+    // The requests only finish if all of them reach the server manager at the same time
+    let handlers: Vec<_> = (0..CONCURRENCY)
+        .map(|_| tokio::spawn(send_get_request("hello")))
+        .collect();
+    for handler in handlers {
+        tokio::time::timeout(std::time::Duration::from_secs(5), handler)
+            .await
+            .expect("requests were not processed concurrently")
+            .expect("failed to wait handler")
+            .expect("failed to obtain response");
+    }
+    state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_it_releases_workers_after_errors() {
+    let inspect_server = InspectServerWrapper::start().await;
+    // Fail more requests than the number of workers
+    for _ in 0..(CONCURRENCY + 1) {
+        let (status, _) = send_get_request("hello")
+            .await
+            .expect_err("failed to obtain response");
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+    }
+    let (mock, response_tx) = SyncInspect::setup();
+    let server_manager = MockServerManagerWrapper::start(mock).await;
+    response_tx
+        .send(MockInspectResponse::default())
+        .await
+        .expect("failed to send response");
+    send_get_request("hello")
+        .await
+        .expect("failed to obtain response");
+    server_manager.stop().await;
+    inspect_server.stop().await;
+}
+
 #[tokio::test]
 #[serial_test::serial]
 async fn test_get_it_returns_error_when_queue_is_full() {
     let (mock, response_tx) = SyncInspect::setup();
     let state = TestState::setup(mock).await;
     // Send concurrent requests to overflow the queue.
-    // We need to 2 extra requests to overflow the queue because the first message will be
-    // imediatelly consumed and removed from the queue.
+    // We need CONCURRENCY + 1 extra requests to overflow the queue because the first messages
+    // will be imediatelly consumed and removed from the queue.
     let mut handlers = FuturesUnordered::new();
-    for _ in 0..(QUEUE_SIZE + 2) {
+    for _ in 0..(QUEUE_SIZE + CONCURRENCY + 1) {
         handlers.push(tokio::spawn(send_get_request("hello")));
     }
     // Poll the handlers to find the overflow error
@@ -116,13 +211,13 @@ async fn test_get_it_returns_error_when_queue_is_full() {
         .expect("failed to poll")
         .expect("failed to join handler")
         .expect_err("failed to receive error");
-    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
     assert_eq!(
         message,
-        String::from("Failed to inspect state: no available capacity")
+        format!("Inspect queue is full (capacity: {})", QUEUE_SIZE)
     );
     // Add the responses to the queue
-    for _ in 0..(QUEUE_SIZE + 1) {
+    for _ in 0..(QUEUE_SIZE + CONCURRENCY) {
         response_tx
             .send(MockInspectResponse::default())
             .await
@@ -135,6 +230,98 @@ async fn test_get_it_returns_error_when_queue_is_full() {
     state.teardown().await;
 }
 
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_it_queues_requests_beyond_concurrency() {
+    let (mock, response_tx) = SyncInspect::setup();
+    let state = TestState::setup(mock).await;
+    // Occupy all the tasks and then fill the queue
+    let mut handlers: Vec<_> = (0..CONCURRENCY)
+        .map(|_| tokio::spawn(send_get_request("hello")))
+        .collect();
+    tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while state.received_requests().len() < CONCURRENCY {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("requests did not reach the server manager");
+    for _ in 0..QUEUE_SIZE {
+        handlers.push(tokio::spawn(send_get_request("hello")));
+    }
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    // Only the requests in flight reach the server manager; the others wait in the queue
+    assert_eq!(state.received_requests().len(), CONCURRENCY);
+    assert!(handlers.iter().all(|handler| !handler.is_finished()));
+    for _ in 0..(QUEUE_SIZE + CONCURRENCY) {
+        response_tx
+            .send(MockInspectResponse::default())
+            .await
+            .expect("failed to send response");
+    }
+    for handler in handlers {
+        handler
+            .await
+            .expect("failed to join handler")
+            .expect("failed to obtain response");
+    }
+    assert_eq!(state.received_requests().len(), QUEUE_SIZE + CONCURRENCY);
+    state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_it_returns_backpressure_headers_when_queue_is_full() {
+    let (mock, response_tx) = SyncInspect::setup();
+    let state = TestState::setup(mock).await;
+    // Fill the workers first and then the queue
+    let mut handlers = vec![];
+    for size in [CONCURRENCY, QUEUE_SIZE] {
+        for _ in 0..size {
+            handlers.push(tokio::spawn(send_get_request("hello")));
+        }
+        // Wait until the requests arrive in the inspect-server
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    let url = format!("http://{}/inspect/hello", INSPECT_SERVER_ADDRESS);
+    let response = reqwest::get(url).await.expect("failed to send inspect");
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(
+        response
+            .headers()
+            .get("retry-after")
+            .expect("missing retry-after header"),
+        "1"
+    );
+    assert_eq!(queue_header(&response, QUEUE_DEPTH_HEADER), QUEUE_SIZE);
+    assert_eq!(queue_header(&response, QUEUE_CAPACITY_HEADER), QUEUE_SIZE);
+    // Add the responses to the queue
+    for _ in 0..(QUEUE_SIZE + CONCURRENCY) {
+        response_tx
+            .send(MockInspectResponse::default())
+            .await
+            .expect("failed to send response");
+    }
+    for handler in handlers {
+        handler
+            .await
+            .expect("failed to wait handler")
+            .expect("failed to obtain response");
+    }
+    state.teardown().await;
+}
+
+fn queue_header(response: &reqwest::Response, name: &str) -> usize {
+    response
+        .headers()
+        .get(name)
+        .expect("missing queue header")
+        .to_str()
+        .expect("invalid queue header")
+        .parse()
+        .expect("queue header should be a number")
+}
+
 #[tokio::test]
 #[serial_test::serial]
 async fn test_post_error_when_server_manager_is_down() {
@@ -209,10 +396,10 @@ async fn test_post_it_returns_error_when_queue_is_full() {
     let (mock, response_tx) = SyncInspect::setup();
     let state = TestState::setup(mock).await;
     // Send concurrent requests to overflow the queue.
-    // We need to 2 extra requests to overflow the queue because the first message will be
-    // imediatelly consumed and removed from the queue.
+    // We need CONCURRENCY + 1 extra requests to overflow the queue because the first messages
+    // will be imediatelly consumed and removed from the queue.
     let mut handlers = FuturesUnordered::new();
-    for _ in 0..(QUEUE_SIZE + 2) {
+    for _ in 0..(QUEUE_SIZE + CONCURRENCY + 1) {
         handlers.push(tokio::spawn(send_post_request("hello")));
     }
     // Poll the handlers to find the overflow error
@@ -222,13 +409,13 @@ async fn test_post_it_returns_error_when_queue_is_full() {
         .expect("failed to poll")
         .expect("failed to join handler")
         .expect_err("failed to receive error");
-    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
     assert_eq!(
         message,
-        String::from("Failed to inspect state: no available capacity")
+        format!("Inspect queue is full (capacity: {})", QUEUE_SIZE)
     );
     // Add the responses to the queue
-    for _ in 0..(QUEUE_SIZE + 1) {
+    for _ in 0..(QUEUE_SIZE + CONCURRENCY) {
         response_tx
             .send(MockInspectResponse::default())
             .await
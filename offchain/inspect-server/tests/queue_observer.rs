@@ -0,0 +1,74 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use inspect_server::metrics::QueueObserver;
+use std::sync::{Arc, Mutex};
+
+struct AcceptInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for AcceptInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse::default())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Event {
+    Enqueued { len: usize, capacity: usize },
+    Dequeued { len: usize, capacity: usize },
+}
+
+#[derive(Default)]
+struct RecordingObserver {
+    events: Mutex<Vec<Event>>,
+}
+
+impl QueueObserver for RecordingObserver {
+    fn enqueued(&self, len: usize, capacity: usize) {
+        self.events
+            .lock()
+            .unwrap()
+            .push(Event::Enqueued { len, capacity });
+    }
+
+    fn dequeued(&self, len: usize, capacity: usize) {
+        self.events
+            .lock()
+            .unwrap()
+            .push(Event::Dequeued { len, capacity });
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_notifies_the_queue_observer() {
+    let server_manager =
+        MockServerManagerWrapper::start(AcceptInspect {}).await;
+    let observer = Arc::new(RecordingObserver::default());
+    let client =
+        InspectClient::with_queue_observer(&test_config(), observer.clone());
+    for _ in 0..2 {
+        client
+            .inspect(b"hello".to_vec())
+            .await
+            .expect("failed to inspect");
+    }
+    let capacity = QUEUE_SIZE;
+    assert_eq!(
+        *observer.events.lock().unwrap(),
+        vec![
+            Event::Enqueued { len: 1, capacity },
+            Event::Dequeued { len: 0, capacity },
+            Event::Enqueued { len: 1, capacity },
+            Event::Dequeued { len: 0, capacity },
+        ]
+    );
+    server_manager.stop().await;
+}
@@ -0,0 +1,88 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use inspect_server::config::RateLimitConfig;
+use inspect_server::server::{QUEUE_CAPACITY_HEADER, QUEUE_DEPTH_HEADER};
+
+const BURST: u32 = 2;
+const API_KEY_HEADER: &str = "x-api-key";
+
+struct AcceptInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for AcceptInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse::default())
+    }
+}
+
+async fn setup(header: Option<&str>) -> TestState {
+    let mut config = test_config();
+    config.rate_limit_config = Some(RateLimitConfig {
+        rate: 1.0,
+        burst: BURST,
+        header: header.map(String::from),
+    });
+    TestState::setup_with_config(AcceptInspect {}, config).await
+}
+
+async fn send_request_as(api_key: &str) -> reqwest::Response {
+    let url = format!("http://{}/inspect/hello", INSPECT_SERVER_ADDRESS);
+    reqwest::Client::new()
+        .get(url)
+        .header(API_KEY_HEADER, api_key)
+        .send()
+        .await
+        .expect("failed to send inspect")
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_rate_limits_client_by_header() {
+    let test_state = setup(Some(API_KEY_HEADER)).await;
+    for _ in 0..BURST {
+        let response = send_request_as("abusive").await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let response = send_request_as("abusive").await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(
+        response
+            .headers()
+            .get("retry-after")
+            .expect("missing retry-after header"),
+        "1"
+    );
+    // The queue is empty, since the request is rejected before reaching it
+    assert_eq!(response.headers()[QUEUE_DEPTH_HEADER], "0");
+    assert_eq!(
+        response.headers()[QUEUE_CAPACITY_HEADER],
+        QUEUE_SIZE.to_string().as_str()
+    );
+    // The other clients are not affected
+    let response = send_request_as("other").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(test_state.received_requests().len(), BURST as usize + 1);
+    test_state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_rate_limits_client_by_ip() {
+    let test_state = setup(None).await;
+    for _ in 0..BURST {
+        send_get_request("hello")
+            .await
+            .expect("failed to obtain response");
+    }
+    // The header is ignored when it is not configured
+    let response = send_request_as("other").await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    test_state.teardown().await;
+}
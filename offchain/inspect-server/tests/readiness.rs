@@ -0,0 +1,110 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use std::time::Duration;
+
+struct AcceptInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for AcceptInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse::default())
+    }
+}
+
+async fn send_readyz_request() -> (StatusCode, String) {
+    send_probe_request("readyz").await
+}
+
+async fn send_probe_request(probe: &str) -> (StatusCode, String) {
+    let url = format!("http://{}/{}", INSPECT_SERVER_ADDRESS, probe);
+    let response = reqwest::get(url)
+        .await
+        .expect("failed to send probe request");
+    let status = response.status();
+    let message = response
+        .text()
+        .await
+        .expect("failed to obtain response body");
+    (status, message)
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_readyz_when_server_manager_is_down() {
+    let inspect_server = InspectServerWrapper::start().await;
+    let (status, message) = send_readyz_request().await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(
+        message,
+        "Failed to connect to server manager: transport error"
+    );
+    inspect_server.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_readyz_when_server_manager_is_up() {
+    let test_state = TestState::setup(AcceptInspect {}).await;
+    let (status, _) = send_readyz_request().await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(test_state.received_requests().is_empty());
+    test_state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_wait_until_ready_when_server_manager_starts_later() {
+    let inspect_client = InspectClient::new(&test_config());
+    let wait = tokio::spawn(async move {
+        inspect_client
+            .wait_until_ready(Duration::from_millis(50), Duration::from_secs(5))
+            .await
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let server_manager =
+        MockServerManagerWrapper::start(AcceptInspect {}).await;
+    assert!(wait.await.expect("failed to wait for readiness"));
+    server_manager.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_wait_until_ready_gives_up_after_max_wait() {
+    let inspect_client = InspectClient::new(&test_config());
+    let ready = inspect_client
+        .wait_until_ready(Duration::from_millis(50), Duration::from_millis(200))
+        .await;
+    assert!(!ready);
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_probes_across_lifecycle() {
+    let inspect_server = InspectServerWrapper::start().await;
+    // The server is up but the server manager is not
+    assert_eq!(send_probe_request("healthz").await.0, StatusCode::OK);
+    assert_eq!(
+        send_readyz_request().await.0,
+        StatusCode::SERVICE_UNAVAILABLE
+    );
+    let server_manager =
+        MockServerManagerWrapper::start(AcceptInspect {}).await;
+    assert_eq!(send_probe_request("healthz").await.0, StatusCode::OK);
+    assert_eq!(send_readyz_request().await.0, StatusCode::OK);
+    inspect_server.inspect_client().shutdown();
+    // Give some time to the inspect client to close the queue
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(send_probe_request("healthz").await.0, StatusCode::OK);
+    let (status, message) = send_readyz_request().await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(message, "Inspect service is unavailable");
+    server_manager.stop().await;
+    inspect_server.stop().await;
+}
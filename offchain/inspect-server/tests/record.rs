@@ -0,0 +1,72 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use inspect_server::recorder::RecordedInspect;
+use std::time::Duration;
+
+struct EchoInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for EchoInspect {
+    async fn inspect_state(
+        &self,
+        payload: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse {
+            reports: vec![Report { payload }],
+            exception: None,
+            completion_status: CompletionStatus::Accepted,
+            processed_input_count: None,
+        })
+    }
+}
+
+/// Wait until the recording has the number of lines, since it is written in the background.
+async fn read_recording(path: &std::path::Path, count: usize) -> Vec<String> {
+    for _ in 0..100 {
+        let content = std::fs::read_to_string(path).unwrap_or_default();
+        let lines: Vec<String> = content.lines().map(String::from).collect();
+        if lines.len() >= count {
+            return lines;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("recording should have {} lines", count);
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_records_the_inspects() {
+    let path = std::env::temp_dir()
+        .join(format!("inspect-server-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let server_manager = MockServerManagerWrapper::start(EchoInspect {}).await;
+    let mut config = test_config();
+    config.record_path = Some(path.display().to_string());
+    let client = InspectClient::new(&config);
+    for payload in [b"hello".to_vec(), b"world".to_vec()] {
+        client
+            .inspect_with(payload, InspectOptions::default().request_id("id"))
+            .await
+            .expect("failed to inspect");
+    }
+    let lines = read_recording(&path, 2).await;
+    assert_eq!(lines.len(), 2);
+    let entries: Vec<RecordedInspect> = lines
+        .iter()
+        .map(|line| serde_json::from_str(line).expect("invalid JSON line"))
+        .collect();
+    assert_eq!(entries[0].payload, "0x68656c6c6f");
+    assert_eq!(entries[1].payload, "0x776f726c64");
+    for entry in &entries {
+        assert_eq!(entry.session_id, SESSION_ID);
+        assert_eq!(entry.request_id, "id");
+        assert_eq!(entry.outcome, "Accepted");
+        assert_eq!(entry.report_count, 1);
+    }
+    server_manager.stop().await;
+    let _ = std::fs::remove_file(&path);
+}
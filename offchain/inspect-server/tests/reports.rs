@@ -0,0 +1,77 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use inspect_server::error::InspectError;
+use std::time::Duration;
+
+struct ManyReportsInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for ManyReportsInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse {
+            reports: (0..5).map(|i| Report { payload: vec![i] }).collect(),
+            exception: None,
+            completion_status: CompletionStatus::Accepted,
+            processed_input_count: None,
+        })
+    }
+}
+
+fn payloads(reports: &[inspect_server::inspect::Report]) -> Vec<u8> {
+    reports.iter().map(|report| report.payload[0]).collect()
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_returns_the_reports_in_pages() {
+    let server_manager =
+        MockServerManagerWrapper::start(ManyReportsInspect {}).await;
+    let client = InspectClient::new(&test_config());
+    let (response, token) = client
+        .inspect_paged(b"hello".to_vec(), None, None)
+        .await
+        .expect("failed to inspect");
+    assert!(response.reports.is_empty());
+    let page = client.get_reports(&token, 0, 2).expect("missing reports");
+    assert_eq!(payloads(&page.reports), vec![0, 1]);
+    assert_eq!(page.total, 5);
+    let page = client.get_reports(&token, 2, 2).expect("missing reports");
+    assert_eq!(payloads(&page.reports), vec![2, 3]);
+    let page = client.get_reports(&token, 4, 2).expect("missing reports");
+    assert_eq!(payloads(&page.reports), vec![4]);
+    let page = client.get_reports(&token, 6, 2).expect("missing reports");
+    assert!(page.reports.is_empty());
+    assert_eq!(server_manager.received_requests().len(), 1);
+    server_manager.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_expires_the_reports() {
+    let server_manager =
+        MockServerManagerWrapper::start(ManyReportsInspect {}).await;
+    let mut config = test_config();
+    config.reports_ttl = Duration::from_millis(50);
+    let client = InspectClient::new(&config);
+    let (_, token) = client
+        .inspect_paged(b"hello".to_vec(), None, None)
+        .await
+        .expect("failed to inspect");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let err = client
+        .get_reports(&token, 0, 2)
+        .expect_err("reports should expire");
+    assert!(matches!(err, InspectError::ReportsNotFound { .. }));
+    assert!(matches!(
+        client.get_reports("unknown", 0, 2),
+        Err(InspectError::ReportsNotFound { .. })
+    ));
+    server_manager.stop().await;
+}
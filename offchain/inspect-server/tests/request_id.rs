@@ -0,0 +1,110 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use inspect_server::error::InspectError;
+use inspect_server::server::REQUEST_ID_HEADER;
+
+struct DefaultInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for DefaultInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse::default())
+    }
+}
+
+async fn send_request_with_id(request_id: &[u8]) -> StatusCode {
+    let url = format!("http://{}/inspect/hello", INSPECT_SERVER_ADDRESS);
+    let header = reqwest::header::HeaderValue::from_bytes(request_id)
+        .expect("invalid header value");
+    reqwest::Client::new()
+        .get(url)
+        .header(REQUEST_ID_HEADER, header)
+        .send()
+        .await
+        .expect("failed to send inspect")
+        .status()
+}
+
+fn received_request_id(state: &TestState) -> String {
+    let requests = state.received_requests();
+    assert_eq!(requests.len(), 1);
+    requests[0]
+        .metadata
+        .get("request-id")
+        .expect("missing request-id")
+        .to_str()
+        .expect("invalid request-id")
+        .to_owned()
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_forwards_the_request_id_from_the_caller() {
+    let state = TestState::setup(DefaultInspect {}).await;
+    let status = send_request_with_id(b"my-request-id").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(received_request_id(&state), "my-request-id");
+    state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_generates_a_request_id_when_absent() {
+    let state = TestState::setup(DefaultInspect {}).await;
+    send_get_request("hello")
+        .await
+        .expect("failed to obtain response");
+    let request_id = received_request_id(&state);
+    uuid::Uuid::parse_str(&request_id).expect("request id is not an uuid");
+    state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_rejects_invalid_request_id() {
+    let state = TestState::setup(DefaultInspect {}).await;
+    let status = send_request_with_id(b"invalid \xff id").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(state.received_requests().is_empty());
+    state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_rejects_request_ids_that_are_invalid_metadata() {
+    let server_manager =
+        MockServerManagerWrapper::start(DefaultInspect {}).await;
+    let client = InspectClient::new(&test_config());
+    for request_id in ["line\nbreak", "tab\tid", "del\u{7f}", "ação", ""] {
+        let err = client
+            .inspect_with(
+                b"hello".to_vec(),
+                InspectOptions::default().request_id(request_id),
+            )
+            .await
+            .expect_err("request id should be rejected");
+        assert!(
+            matches!(err, InspectError::InvalidArgument { .. }),
+            "unexpected error {:?} for {:?}",
+            err,
+            request_id
+        );
+    }
+    // The worker is still running
+    client
+        .inspect_with(
+            b"hello".to_vec(),
+            InspectOptions::default().request_id("valid-id"),
+        )
+        .await
+        .expect("failed to inspect");
+    assert_eq!(server_manager.received_requests().len(), 1);
+    server_manager.stop().await;
+}
@@ -3,6 +3,9 @@
 
 mod common;
 use crate::common::*;
+use inspect_server::error::InspectError;
+use inspect_server::inspect::ReportFilter;
+use inspect_server::server::HttpInspectResponse;
 
 struct FixedResponseInspect {
     response: MockInspectResponse,
@@ -10,8 +13,11 @@ struct FixedResponseInspect {
 
 #[tonic::async_trait]
 impl MockInspect for FixedResponseInspect {
-    async fn inspect_state(&self, _: Vec<u8>) -> MockInspectResponse {
-        self.response.clone()
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(self.response.clone())
     }
 }
 
@@ -43,6 +49,7 @@ async fn test_get_response_with_no_reports() {
         reports: vec![],
         exception: None,
         completion_status: CompletionStatus::Accepted,
+        processed_input_count: None,
     };
     test_get_response(response, "Accepted").await;
 }
@@ -56,6 +63,7 @@ async fn test_get_response_with_single_report() {
         }],
         exception: None,
         completion_status: CompletionStatus::Accepted,
+        processed_input_count: None,
     };
     test_get_response(response, "Accepted").await;
 }
@@ -78,6 +86,7 @@ async fn test_get_response_with_multiple_reports() {
         reports,
         exception: None,
         completion_status: CompletionStatus::Accepted,
+        processed_input_count: None,
     };
     test_get_response(response, "Accepted").await;
 }
@@ -91,10 +100,49 @@ async fn test_get_response_with_reports_and_exception() {
         }],
         exception: Some(vec![4, 5, 6]),
         completion_status: CompletionStatus::Exception,
+        processed_input_count: None,
     };
     test_get_response(response, "Exception").await;
 }
 
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_response_with_rejected_status() {
+    let response = MockInspectResponse {
+        reports: vec![Report {
+            payload: vec![1, 2, 3],
+        }],
+        exception: None,
+        completion_status: CompletionStatus::Rejected,
+        processed_input_count: None,
+    };
+    test_get_response(response, "Rejected").await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_response_with_exception_message() {
+    let mock = FixedResponseInspect {
+        response: MockInspectResponse {
+            reports: vec![],
+            exception: Some(b"reverted".to_vec()),
+            completion_status: CompletionStatus::Exception,
+            processed_input_count: None,
+        },
+    };
+    let state = TestState::setup(mock).await;
+    let response = send_get_request("")
+        .await
+        .expect("failed to obtain response");
+    assert_eq!(response.status, "Exception");
+    assert_eq!(response.exception_message.as_deref(), Some("reverted"));
+    assert_eq!(
+        response.exception_payload,
+        Some(hex_to_bin(&b"reverted".to_vec()))
+    );
+    state.teardown().await;
+}
+
 async fn test_post_response(sent: MockInspectResponse, expected_status: &str) {
     let mock = FixedResponseInspect {
         response: sent.clone(),
@@ -123,6 +171,7 @@ async fn test_post_response_with_no_reports() {
         reports: vec![],
         exception: None,
         completion_status: CompletionStatus::Accepted,
+        processed_input_count: None,
     };
     test_post_response(response, "Accepted").await;
 }
@@ -136,6 +185,7 @@ async fn test_post_response_with_single_report() {
         }],
         exception: None,
         completion_status: CompletionStatus::Accepted,
+        processed_input_count: None,
     };
     test_post_response(response, "Accepted").await;
 }
@@ -158,6 +208,7 @@ async fn test_post_response_with_multiple_reports() {
         reports,
         exception: None,
         completion_status: CompletionStatus::Accepted,
+        processed_input_count: None,
     };
     test_post_response(response, "Accepted").await;
 }
@@ -171,6 +222,155 @@ async fn test_post_response_with_reports_and_exception() {
         }],
         exception: Some(vec![4, 5, 6]),
         completion_status: CompletionStatus::Exception,
+        processed_input_count: None,
     };
     test_post_response(response, "Exception").await;
 }
+
+fn reports_response(count: usize, size: usize) -> MockInspectResponse {
+    MockInspectResponse {
+        reports: (0..count)
+            .map(|_| Report {
+                payload: vec![0; size],
+            })
+            .collect(),
+        exception: None,
+        completion_status: CompletionStatus::Accepted,
+        processed_input_count: None,
+    }
+}
+
+async fn test_report_limits(
+    response: MockInspectResponse,
+    max_reports: usize,
+    max_total_report_bytes: usize,
+) -> Result<HttpInspectResponse, (StatusCode, String)> {
+    let mut config = test_config();
+    config.max_reports = max_reports;
+    config.max_total_report_bytes = max_total_report_bytes;
+    let state =
+        TestState::setup_with_config(FixedResponseInspect { response }, config)
+            .await;
+    let response = send_get_request("").await;
+    state.teardown().await;
+    response
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_fails_when_reports_exceed_max_reports() {
+    let (status, message) = test_report_limits(reports_response(4, 1), 3, 0)
+        .await
+        .expect_err("response should be too large");
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(
+        message,
+        "Inspect response is too large (4 reports, 4 bytes)"
+    );
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_fails_when_reports_exceed_max_total_report_bytes() {
+    let (status, message) = test_report_limits(reports_response(2, 6), 0, 10)
+        .await
+        .expect_err("response should be too large");
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(
+        message,
+        "Inspect response is too large (2 reports, 12 bytes)"
+    );
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_reports_on_limits() {
+    let response = test_report_limits(reports_response(3, 4), 3, 12)
+        .await
+        .expect("failed to obtain response");
+    assert_eq!(response.reports.len(), 3);
+}
+
+async fn inspect_filtered(filter: ReportFilter) -> Vec<Vec<u8>> {
+    let mock = FixedResponseInspect {
+        response: MockInspectResponse {
+            reports: (0..5).map(|i| Report { payload: vec![i] }).collect(),
+            exception: None,
+            completion_status: CompletionStatus::Accepted,
+            processed_input_count: None,
+        },
+    };
+    let server_manager = MockServerManagerWrapper::start(mock).await;
+    let client = InspectClient::new(&test_config());
+    let response = client
+        .inspect_with(
+            b"hello".to_vec(),
+            InspectOptions::default().report_filter(filter),
+        )
+        .await
+        .expect("failed to inspect");
+    server_manager.stop().await;
+    response
+        .reports
+        .into_iter()
+        .map(|report| report.payload)
+        .collect()
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_inspect_filters_reports_by_range() {
+    let filter = ReportFilter {
+        start_index: 1,
+        count: 2,
+    };
+    assert_eq!(inspect_filtered(filter).await, vec![vec![1], vec![2]]);
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_inspect_clamps_report_filter_to_reports() {
+    let filter = ReportFilter {
+        start_index: 3,
+        count: 10,
+    };
+    assert_eq!(inspect_filtered(filter).await, vec![vec![3], vec![4]]);
+    let filter = ReportFilter {
+        start_index: 10,
+        count: 1,
+    };
+    assert!(inspect_filtered(filter).await.is_empty());
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_inspect_fails_when_response_exceeds_max_response_bytes() {
+    let mock = FixedResponseInspect {
+        response: MockInspectResponse {
+            reports: vec![Report {
+                payload: vec![0; 1000],
+            }],
+            exception: None,
+            completion_status: CompletionStatus::Accepted,
+            processed_input_count: None,
+        },
+    };
+    let server_manager = MockServerManagerWrapper::start(mock).await;
+    let mut config = test_config();
+    config.max_response_bytes = 100;
+    let client = InspectClient::new(&config);
+    let err = client
+        .inspect(b"hello".to_vec())
+        .await
+        .expect_err("response should be too large");
+    assert!(
+        matches!(err, InspectError::ResponseTooLarge { .. }),
+        "unexpected error: {}",
+        err
+    );
+    assert_eq!(
+        err.to_string(),
+        "Inspect response is too large (limit: 100 bytes)"
+    );
+    server_manager.stop().await;
+}
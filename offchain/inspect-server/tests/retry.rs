@@ -0,0 +1,140 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Fail the first requests with the given status
+struct FailingInspect {
+    calls: Arc<AtomicUsize>,
+    failures: usize,
+    status: Status,
+}
+
+#[tonic::async_trait]
+impl MockInspect for FailingInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        let call = self.calls.fetch_add(1, Ordering::SeqCst);
+        if call < self.failures {
+            Err(self.status.clone())
+        } else {
+            Ok(MockInspectResponse::default())
+        }
+    }
+}
+
+async fn setup(
+    failures: usize,
+    status: Status,
+    max_retries: u32,
+) -> (TestState, Arc<AtomicUsize>) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mock = FailingInspect {
+        calls: calls.clone(),
+        failures,
+        status,
+    };
+    let mut config = test_config();
+    config.max_retries = max_retries;
+    let state = TestState::setup_with_config(mock, config).await;
+    (state, calls)
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_retries_when_server_manager_is_unavailable() {
+    let (state, calls) = setup(2, Status::unavailable("unavailable"), 3).await;
+    send_get_request("hello")
+        .await
+        .expect("failed to obtain response");
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+    state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_fails_after_max_retries() {
+    let (state, calls) = setup(10, Status::unavailable("unavailable"), 2).await;
+    let (status, message) = send_get_request("hello")
+        .await
+        .expect_err("failed to obtain response");
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(&message, "Failed to inspect state: unavailable");
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+    state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_does_not_retry_application_errors() {
+    let (state, calls) =
+        setup(10, Status::invalid_argument("invalid argument"), 3).await;
+    let (status, message) = send_get_request("hello")
+        .await
+        .expect_err("failed to obtain response");
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(&message, "Invalid argument: invalid argument");
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_retries_until_server_manager_starts() {
+    let mut config = test_config();
+    config.max_retries = 10;
+    config.initial_backoff = Duration::from_millis(50);
+    let inspect_server = InspectServerWrapper::start_with_config(config).await;
+    let request = tokio::spawn(send_get_request("hello"));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let mock = FailingInspect {
+        calls: Arc::new(AtomicUsize::new(0)),
+        failures: 0,
+        status: Status::unavailable("unavailable"),
+    };
+    let server_manager = MockServerManagerWrapper::start(mock).await;
+    request
+        .await
+        .expect("failed to join handler")
+        .expect("failed to obtain response");
+    server_manager.stop().await;
+    inspect_server.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_retries_within_the_retry_budget() {
+    const REQUESTS: usize = 10;
+    const BUDGET: u32 = 3;
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mock = FailingInspect {
+        calls: calls.clone(),
+        failures: usize::MAX,
+        status: Status::unavailable("unavailable"),
+    };
+    let mut config = test_config();
+    config.queue_size = REQUESTS;
+    config.max_retries = 3;
+    config.retry_budget = BUDGET;
+    config.retry_budget_refill = 0.001;
+    let state = TestState::setup_with_config(mock, config).await;
+    let requests: Vec<_> = (0..REQUESTS)
+        .map(|_| tokio::spawn(send_get_request("hello")))
+        .collect();
+    for request in requests {
+        request
+            .await
+            .expect("failed to join handler")
+            .expect_err("failed to obtain response");
+    }
+    // Each request is sent once, and only the budget is retried
+    assert_eq!(calls.load(Ordering::SeqCst), REQUESTS + BUDGET as usize);
+    state.teardown().await;
+}
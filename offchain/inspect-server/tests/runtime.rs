@@ -0,0 +1,38 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+struct EchoInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for EchoInspect {
+    async fn inspect_state(
+        &self,
+        payload: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse {
+            reports: vec![Report { payload }],
+            exception: None,
+            completion_status: CompletionStatus::Accepted,
+            processed_input_count: None,
+        })
+    }
+}
+
+#[test]
+#[serial_test::serial]
+fn test_it_creates_the_client_outside_of_a_runtime() {
+    let runtime =
+        tokio::runtime::Runtime::new().expect("failed to build runtime");
+    let server_manager =
+        runtime.block_on(MockServerManagerWrapper::start(EchoInspect {}));
+    let client = InspectClient::new_on(runtime.handle(), &test_config());
+    let response = runtime
+        .block_on(client.inspect(b"hello".to_vec()))
+        .expect("failed to inspect");
+    assert_eq!(response.reports.len(), 1);
+    assert_eq!(response.reports[0].payload, b"hello");
+    runtime.block_on(server_manager.stop());
+}
@@ -0,0 +1,170 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use inspect_server::error::InspectError;
+
+struct AcceptInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for AcceptInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse::default())
+    }
+}
+
+async fn send_session_requests(first: &str, second: &str) {
+    let get_url = format!(
+        "http://{}/sessions/{}/inspect/hello",
+        INSPECT_SERVER_ADDRESS, first
+    );
+    let response = reqwest::get(get_url)
+        .await
+        .expect("failed to send inspect via GET");
+    assert_eq!(response.status(), StatusCode::OK);
+    let post_url = format!(
+        "http://{}/sessions/{}/inspect",
+        INSPECT_SERVER_ADDRESS, second
+    );
+    let response = reqwest::Client::new()
+        .post(post_url)
+        .body("hello")
+        .send()
+        .await
+        .expect("failed to send inspect via POST");
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_inspect_routes_requests_to_sessions() {
+    let test_state = TestState::setup(AcceptInspect {}).await;
+    send_session_requests("first", "second").await;
+    send_get_request("hello")
+        .await
+        .expect("failed to obtain response");
+    let session_ids: Vec<String> = test_state
+        .received_requests()
+        .into_iter()
+        .map(|request| request.session_id)
+        .collect();
+    assert_eq!(session_ids, vec!["first", "second", SESSION_ID]);
+    test_state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_inspect_fails_with_empty_session_id() {
+    let test_state = TestState::setup(AcceptInspect {}).await;
+    let inspect_client = InspectClient::new(&test_config());
+    let err = inspect_client
+        .inspect_with(
+            b"hello".to_vec(),
+            InspectOptions::default().session_id(""),
+        )
+        .await
+        .expect_err("empty session id should be rejected");
+    assert_eq!(err.to_string(), "Invalid argument: empty session id");
+    assert!(test_state.received_requests().is_empty());
+    test_state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_check_session_when_session_exists() {
+    let test_state = TestState::setup(AcceptInspect {}).await;
+    let inspect_client = InspectClient::new(&test_config());
+    inspect_client
+        .check_session()
+        .await
+        .expect("session should exist");
+    test_state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_check_session_when_session_is_missing() {
+    let test_state = TestState::setup(AcceptInspect {}).await;
+    let mut config = test_config();
+    config.session_id = String::from("missing session");
+    let inspect_client = InspectClient::new(&config);
+    let err = inspect_client
+        .check_session()
+        .await
+        .expect_err("session should be missing");
+    match err {
+        InspectError::SessionNotFound { session_id } => {
+            assert_eq!(session_id, "missing session");
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+    let err = inspect_client
+        .check_ready()
+        .await
+        .expect_err("server manager should not be ready");
+    assert_eq!(
+        err.to_string(),
+        "Session \"missing session\" not found in server manager"
+    );
+    test_state.teardown().await;
+}
+
+/// Answer the inspects, except the pending ones, which never finish
+struct PendingInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for PendingInspect {
+    async fn inspect_state(
+        &self,
+        payload: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        if payload == b"pending" {
+            std::future::pending::<()>().await;
+        }
+        Ok(MockInspectResponse::default())
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_inspect_is_cancelled_when_the_session_changes() {
+    let server_manager =
+        MockServerManagerWrapper::start(PendingInspect {}).await;
+    let inspect_client = InspectClient::new(&test_config());
+    let pending = {
+        let inspect_client = inspect_client.clone();
+        tokio::spawn(async move {
+            inspect_client.inspect(b"pending".to_vec()).await
+        })
+    };
+    // Wait until the inspect reaches the server manager
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert_eq!(server_manager.received_requests().len(), 1);
+    inspect_client.set_session(String::from("other"));
+    let err = pending
+        .await
+        .expect("failed to join inspect")
+        .expect_err("inspect should be cancelled");
+    assert!(matches!(
+        err,
+        InspectError::SessionChanged { session_id } if session_id == SESSION_ID
+    ));
+    // The new requests use the new session
+    assert_eq!(inspect_client.session_id(), "other");
+    inspect_client
+        .inspect(b"hello".to_vec())
+        .await
+        .expect("inspect should succeed");
+    let session_ids: Vec<String> = server_manager
+        .received_requests()
+        .into_iter()
+        .map(|request| request.session_id)
+        .collect();
+    assert_eq!(session_ids, vec![SESSION_ID, "other"]);
+    server_manager.stop().await;
+}
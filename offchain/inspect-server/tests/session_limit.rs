@@ -0,0 +1,82 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+/// Inspect that holds the slow payloads until they are released.
+struct SlowInspect {
+    release: Arc<Semaphore>,
+}
+
+#[tonic::async_trait]
+impl MockInspect for SlowInspect {
+    async fn inspect_state(
+        &self,
+        payload: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        if payload == b"slow" {
+            self.release.acquire().await.unwrap().forget();
+        }
+        Ok(MockInspectResponse {
+            reports: vec![Report { payload }],
+            exception: None,
+            completion_status: CompletionStatus::Accepted,
+            processed_input_count: None,
+        })
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_a_busy_session_does_not_starve_the_others() {
+    let release = Arc::new(Semaphore::new(0));
+    let server_manager = MockServerManagerWrapper::start(SlowInspect {
+        release: release.clone(),
+    })
+    .await;
+    let mut config = test_config();
+    config.session_concurrency = 1;
+    let client = InspectClient::new(&config);
+    // Without the session limit, these requests would take all the workers
+    let busy: Vec<_> = (0..CONCURRENCY)
+        .map(|_| {
+            let client = client.clone();
+            tokio::spawn(async move {
+                client
+                    .inspect_with(
+                        b"slow".to_vec(),
+                        InspectOptions::default().session_id("busy"),
+                    )
+                    .await
+            })
+        })
+        .collect();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let response = tokio::time::timeout(
+        Duration::from_secs(1),
+        client.inspect_with(
+            b"fast".to_vec(),
+            InspectOptions::default().session_id("other"),
+        ),
+    )
+    .await
+    .expect("the other session should not wait for the busy one")
+    .expect("failed to inspect");
+    assert_eq!(response.reports[0].payload, b"fast");
+    let busy_requests = server_manager
+        .received_requests()
+        .into_iter()
+        .filter(|request| request.session_id == "busy")
+        .count();
+    assert_eq!(busy_requests, 1);
+    release.add_permits(CONCURRENCY);
+    for handle in busy {
+        handle.await.unwrap().expect("failed to inspect");
+    }
+    server_manager.stop().await;
+}
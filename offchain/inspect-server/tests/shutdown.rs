@@ -0,0 +1,122 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use std::time::Duration;
+
+use inspect_server::inspect::InspectClient;
+
+struct SleepInspect {
+    duration: Duration,
+}
+
+#[tonic::async_trait]
+impl MockInspect for SleepInspect {
+    async fn inspect_state(
+        &self,
+        payload: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        tokio::time::sleep(self.duration).await;
+        Ok(MockInspectResponse {
+            reports: vec![Report { payload }],
+            exception: None,
+            completion_status: CompletionStatus::Accepted,
+            processed_input_count: None,
+        })
+    }
+}
+
+fn spawn_inspect(
+    client: &InspectClient,
+) -> tokio::task::JoinHandle<Result<(), String>> {
+    let client = client.clone();
+    tokio::spawn(async move {
+        client
+            .inspect(b"hello".to_vec())
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    })
+}
+
+/// Send enough inspect requests to occupy all the tasks and fill the queue
+async fn fill_queue(
+    client: &InspectClient,
+) -> Vec<tokio::task::JoinHandle<Result<(), String>>> {
+    let mut handles = vec![];
+    // Wait for the running requests to leave the queue before filling it
+    for count in [CONCURRENCY, QUEUE_SIZE] {
+        for _ in 0..count {
+            handles.push(spawn_inspect(client));
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    handles
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_drains_queued_requests_on_shutdown() {
+    let server_manager = MockServerManagerWrapper::start(SleepInspect {
+        duration: Duration::from_millis(300),
+    })
+    .await;
+    let mut config = test_config();
+    config.shutdown_timeout = Duration::from_secs(5);
+    let client = InspectClient::new(&config);
+    let handles = fill_queue(&client).await;
+    client.shutdown();
+    for handle in handles {
+        assert_eq!(handle.await.unwrap(), Ok(()));
+    }
+    server_manager.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_rejects_new_requests_after_shutdown() {
+    let server_manager = MockServerManagerWrapper::start(SleepInspect {
+        duration: Duration::ZERO,
+    })
+    .await;
+    let client = InspectClient::new(&test_config());
+    client.shutdown();
+    // Give some time to the inspect client to close the queue
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let err = client
+        .inspect(b"hello".to_vec())
+        .await
+        .expect_err("inspect should fail");
+    assert_eq!(err.to_string(), "Inspect service is unavailable");
+    assert!(server_manager.received_requests().is_empty());
+    server_manager.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_answers_pending_requests_after_shutdown_timeout() {
+    let server_manager = MockServerManagerWrapper::start(SleepInspect {
+        duration: Duration::from_secs(5),
+    })
+    .await;
+    let mut config = test_config();
+    config.shutdown_timeout = Duration::from_millis(200);
+    let client = InspectClient::new(&config);
+    let handles = fill_queue(&client).await;
+    client.shutdown();
+    let results = tokio::time::timeout(
+        Duration::from_secs(2),
+        futures::future::join_all(handles),
+    )
+    .await
+    .expect("pending requests were not answered");
+    for result in results {
+        assert_eq!(
+            result.unwrap(),
+            Err("Inspect service is unavailable".to_string())
+        );
+    }
+    server_manager.stop().await;
+}
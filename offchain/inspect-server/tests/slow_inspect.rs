@@ -0,0 +1,83 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use std::time::Duration;
+use tracing_test::traced_test;
+
+const SLOW_THRESHOLD: Duration = Duration::from_millis(100);
+const SLOW_WARNING: &str = "inspect slower than the threshold";
+
+/// Takes twice the threshold to answer the payloads that start with "slow".
+struct SlowInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for SlowInspect {
+    async fn inspect_state(
+        &self,
+        payload: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        if payload.starts_with(b"slow") {
+            tokio::time::sleep(SLOW_THRESHOLD * 2).await;
+        }
+        Ok(MockInspectResponse::default())
+    }
+}
+
+async fn inspect(client: &InspectClient, payload: &[u8], request_id: &str) {
+    client
+        .inspect_with(
+            payload.to_vec(),
+            InspectOptions::default().request_id(request_id.to_string()),
+        )
+        .await
+        .expect("failed to inspect");
+}
+
+fn slow_warnings<'a>(lines: &[&'a str]) -> Vec<&'a str> {
+    lines
+        .iter()
+        .copied()
+        .filter(|line| line.contains(SLOW_WARNING))
+        .collect()
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[traced_test]
+async fn test_it_warns_once_about_slow_inspects() {
+    let server_manager = MockServerManagerWrapper::start(SlowInspect {}).await;
+    let mut config = test_config();
+    config.slow_inspect_threshold = SLOW_THRESHOLD;
+    let client = InspectClient::new(&config);
+    inspect(&client, b"fast", "fast-request").await;
+    assert!(!logs_contain(SLOW_WARNING));
+    inspect(&client, b"slow", "slow-request").await;
+    inspect(&client, b"slower", "slower-request").await;
+    logs_assert(|lines: &[&str]| {
+        let warnings = slow_warnings(lines);
+        match warnings.as_slice() {
+            [warning] => {
+                assert!(warning.contains("request_id=slow-request"));
+                assert!(warning.contains("payload_len=4"));
+                assert!(warning.contains("elapsed_ms="));
+                Ok(())
+            }
+            _ => Err(format!("expected one slow warning, got {:?}", warnings)),
+        }
+    });
+    server_manager.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[traced_test]
+async fn test_it_does_not_warn_when_the_threshold_is_disabled() {
+    let server_manager = MockServerManagerWrapper::start(SlowInspect {}).await;
+    let client = InspectClient::new(&test_config());
+    inspect(&client, b"slow", "slow-request").await;
+    assert!(!logs_contain(SLOW_WARNING));
+    server_manager.stop().await;
+}
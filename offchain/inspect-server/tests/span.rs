@@ -0,0 +1,68 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use tracing_test::traced_test;
+
+struct EchoInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for EchoInspect {
+    async fn inspect_state(
+        &self,
+        payload: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse {
+            reports: vec![Report { payload }],
+            exception: None,
+            completion_status: CompletionStatus::Accepted,
+            processed_input_count: None,
+        })
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[traced_test]
+async fn test_it_records_the_inspect_outcome_in_the_span() {
+    let server_manager = MockServerManagerWrapper::start(EchoInspect {}).await;
+    let client = InspectClient::new(&test_config());
+    client
+        .inspect_with(
+            b"hello".to_vec(),
+            InspectOptions::default().request_id("span-test"),
+        )
+        .await
+        .expect("failed to inspect");
+    assert!(logs_contain("request_id=span-test"));
+    assert!(logs_contain(&format!("session_id={}", SESSION_ID)));
+    assert!(logs_contain("payload_len=5"));
+    assert!(logs_contain("status=\"Accepted\""));
+    assert!(logs_contain("report_count=1"));
+    assert!(logs_contain("elapsed_ms="));
+    assert!(logs_contain("inspect finished"));
+    server_manager.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[traced_test]
+async fn test_it_records_the_inspect_outcome_on_errors() {
+    let mut config = test_config();
+    config.max_payload_size = 1;
+    let client = InspectClient::new(&config);
+    client
+        .inspect_with(
+            b"hello".to_vec(),
+            InspectOptions::default().request_id("span-error"),
+        )
+        .await
+        .expect_err("payload should be too large");
+    assert!(logs_contain("request_id=span-error"));
+    assert!(logs_contain("status=\"PayloadTooLarge\""));
+    assert!(logs_contain("report_count=0"));
+    assert!(logs_contain("elapsed_ms="));
+    assert!(logs_contain("inspect finished"));
+}
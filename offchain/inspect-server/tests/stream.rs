@@ -0,0 +1,67 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use futures::StreamExt;
+use inspect_server::inspect::InspectClient;
+
+struct ReportsInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for ReportsInspect {
+    async fn inspect_state(
+        &self,
+        payload: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        let reports = (0..3)
+            .map(|i| Report {
+                payload: [payload.clone(), vec![i]].concat(),
+            })
+            .collect();
+        Ok(MockInspectResponse {
+            reports,
+            exception: None,
+            completion_status: CompletionStatus::Accepted,
+            processed_input_count: None,
+        })
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_stream_yields_every_report_in_order() {
+    let server_manager =
+        MockServerManagerWrapper::start(ReportsInspect {}).await;
+    let client = InspectClient::new(&test_config());
+    let reports: Vec<_> = client
+        .inspect_stream(b"report".to_vec(), None)
+        .map(|report| report.expect("failed to inspect").payload)
+        .collect()
+        .await;
+    assert_eq!(
+        reports,
+        vec![
+            b"report\0".to_vec(),
+            b"report\x01".to_vec(),
+            b"report\x02".to_vec()
+        ]
+    );
+    server_manager.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_stream_yields_error_when_inspect_fails() {
+    let client = InspectClient::new(&test_config());
+    let results: Vec<_> = client
+        .inspect_stream(b"report".to_vec(), None)
+        .collect()
+        .await;
+    assert_eq!(results.len(), 1);
+    let err = results[0].as_ref().expect_err("server manager is down");
+    assert!(err
+        .to_string()
+        .starts_with("Failed to connect to server manager"));
+}
@@ -0,0 +1,70 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+#![cfg(feature = "test-utils")]
+
+mod common;
+use crate::common::*;
+
+use inspect_server::testing::{MockServerManager, MOCK_SESSION_ID};
+use inspect_server::InspectServerConfig;
+use std::time::Duration;
+
+fn mock_config(mock_address: std::net::SocketAddr) -> InspectServerConfig {
+    let mut config = test_config();
+    config.server_manager_address = mock_address.to_string();
+    config.session_id = MOCK_SESSION_ID.to_string();
+    config
+}
+
+#[tokio::test]
+async fn test_client_inspects_mock_server_manager() {
+    let mock = MockServerManager::new().start().await;
+    let client = InspectClient::new(&mock_config(mock.address()));
+    let response = client
+        .inspect(b"hello".to_vec())
+        .await
+        .expect("failed to inspect");
+    assert_eq!(response.status, CompletionStatus::Accepted as i32);
+    let received = mock.received_requests();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].session_id, MOCK_SESSION_ID);
+    assert_eq!(received[0].query_payload, b"hello");
+    client.check_ready().await.expect("mock should be ready");
+    mock.stop().await;
+}
+
+#[tokio::test]
+async fn test_client_maps_mock_status() {
+    let mock = MockServerManager::new()
+        .with_status(Status::not_found("no session"))
+        .start()
+        .await;
+    let client = InspectClient::new(&mock_config(mock.address()));
+    let err = client
+        .inspect(b"hello".to_vec())
+        .await
+        .expect_err("inspect should fail");
+    assert_eq!(
+        err.to_string(),
+        "Session \"default\" not found in server manager"
+    );
+    mock.stop().await;
+}
+
+#[tokio::test]
+async fn test_client_times_out_when_mock_is_slow() {
+    let mock = MockServerManager::new()
+        .with_delay(Duration::from_millis(500))
+        .start()
+        .await;
+    let mut config = mock_config(mock.address());
+    config.request_timeout = Duration::from_millis(100);
+    let client = InspectClient::new(&config);
+    let err = client
+        .inspect(b"hello".to_vec())
+        .await
+        .expect_err("inspect should time out");
+    assert_eq!(err.to_string(), "Inspect request timed out after 100ms");
+    mock.stop().await;
+}
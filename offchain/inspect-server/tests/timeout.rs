@@ -0,0 +1,244 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use inspect_server::error::InspectError;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_millis(100);
+
+struct SleepInspect {
+    duration: Duration,
+}
+
+#[tonic::async_trait]
+impl MockInspect for SleepInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        tokio::time::sleep(self.duration).await;
+        Ok(MockInspectResponse::default())
+    }
+}
+
+async fn setup(sleep_duration: Duration) -> TestState {
+    let mock = SleepInspect {
+        duration: sleep_duration,
+    };
+    let mut config = test_config();
+    config.request_timeout = REQUEST_TIMEOUT;
+    TestState::setup_with_config(mock, config).await
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_fails_when_server_manager_exceeds_timeout() {
+    let state = setup(REQUEST_TIMEOUT * 5).await;
+    let (status, message) = send_get_request("hello")
+        .await
+        .expect_err("failed to obtain response");
+    assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(&message, "Inspect request timed out after 100ms");
+    state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_succeeds_within_timeout() {
+    let state = setup(Duration::ZERO).await;
+    send_get_request("hello")
+        .await
+        .expect("failed to obtain response");
+    state.teardown().await;
+}
+
+/// Sleep only in the first request
+struct SleepOnceInspect {
+    slept: AtomicBool,
+}
+
+#[tonic::async_trait]
+impl MockInspect for SleepOnceInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        if !self.slept.swap(true, Ordering::SeqCst) {
+            tokio::time::sleep(REQUEST_TIMEOUT * 2).await;
+        }
+        Ok(MockInspectResponse::default())
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_succeeds_after_previous_request_timed_out() {
+    let mock = SleepOnceInspect {
+        slept: AtomicBool::new(false),
+    };
+    let mut config = test_config();
+    config.request_timeout = REQUEST_TIMEOUT;
+    let state = TestState::setup_with_config(mock, config).await;
+    let (status, _) = send_get_request("hello")
+        .await
+        .expect_err("failed to obtain response");
+    assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+    send_get_request("hello")
+        .await
+        .expect("failed to obtain response");
+    state.teardown().await;
+}
+
+/// Fail with Cancelled after sleeping
+struct CancelledInspect {
+    duration: Duration,
+}
+
+#[tonic::async_trait]
+impl MockInspect for CancelledInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        tokio::time::sleep(self.duration).await;
+        Err(Status::cancelled("inspect aborted"))
+    }
+}
+
+async fn inspect_cancelled_after(duration: Duration) -> InspectError {
+    let server_manager =
+        MockServerManagerWrapper::start(CancelledInspect { duration }).await;
+    let mut config = test_config();
+    config.request_timeout = REQUEST_TIMEOUT;
+    let client = InspectClient::new(&config);
+    let err = client
+        .inspect(b"hello".to_vec())
+        .await
+        .expect_err("inspect should fail");
+    server_manager.stop().await;
+    err
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_inspect_reports_an_early_cancellation_as_is() {
+    let err = inspect_cancelled_after(Duration::ZERO).await;
+    assert!(
+        matches!(
+            err,
+            InspectError::InspectFailed {
+                code: tonic::Code::Cancelled,
+                ..
+            }
+        ),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_inspect_reports_a_cancellation_at_the_deadline_as_timeout() {
+    // The server manager gives up at 9/10 of the request timeout
+    let err = inspect_cancelled_after(REQUEST_TIMEOUT * 95 / 100).await;
+    assert!(
+        matches!(err, InspectError::Timeout { .. }),
+        "unexpected error: {}",
+        err
+    );
+}
+
+/// Parse the grpc-timeout header into a duration
+fn parse_grpc_timeout(value: &str) -> Duration {
+    let (amount, unit) = value.split_at(value.len() - 1);
+    let amount: u64 = amount.parse().expect("invalid grpc-timeout");
+    match unit {
+        "H" => Duration::from_secs(amount * 3600),
+        "M" => Duration::from_secs(amount * 60),
+        "S" => Duration::from_secs(amount),
+        "m" => Duration::from_millis(amount),
+        "u" => Duration::from_micros(amount),
+        "n" => Duration::from_nanos(amount),
+        _ => panic!("invalid grpc-timeout unit"),
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_sends_grpc_timeout_to_server_manager() {
+    let state = setup(Duration::ZERO).await;
+    send_get_request("hello")
+        .await
+        .expect("failed to obtain response");
+    let requests = state.received_requests();
+    let grpc_timeout = requests[0]
+        .metadata
+        .get("grpc-timeout")
+        .expect("missing grpc-timeout")
+        .to_str()
+        .unwrap();
+    let grpc_timeout = parse_grpc_timeout(grpc_timeout);
+    assert!(grpc_timeout > Duration::ZERO);
+    assert!(grpc_timeout < REQUEST_TIMEOUT);
+    state.teardown().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_get_does_not_send_grpc_timeout_without_timeout() {
+    let state = TestState::setup(SleepInspect {
+        duration: Duration::ZERO,
+    })
+    .await;
+    send_get_request("hello")
+        .await
+        .expect("failed to obtain response");
+    let requests = state.received_requests();
+    assert!(requests[0].metadata.get("grpc-timeout").is_none());
+    state.teardown().await;
+}
+
+/// Listen without accepting and fill the accept queue, so the connections to the returned address
+/// hang like the ones to an unroutable address.
+async fn hanging_listener() -> (tokio::net::TcpListener, Vec<TcpStream>) {
+    let socket = tokio::net::TcpSocket::new_v4().unwrap();
+    socket.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let listener = socket.listen(0).unwrap();
+    let address = listener.local_addr().unwrap();
+    let mut streams = vec![];
+    while let Ok(Ok(stream)) = tokio::time::timeout(
+        Duration::from_millis(100),
+        TcpStream::connect(address),
+    )
+    .await
+    {
+        streams.push(stream);
+    }
+    (listener, streams)
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_inspect_fails_when_connect_exceeds_timeout() {
+    let (listener, _streams) = hanging_listener().await;
+    let mut config = test_config();
+    config.server_manager_address = listener.local_addr().unwrap().to_string();
+    config.connect_timeout = Duration::from_millis(200);
+    let client = InspectClient::new(&config);
+    let start = std::time::Instant::now();
+    let err = client
+        .inspect(b"hello".to_vec())
+        .await
+        .expect_err("inspect should fail");
+    assert!(
+        matches!(err, InspectError::FailedToConnect { .. }),
+        "unexpected error: {}",
+        err
+    );
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
@@ -0,0 +1,112 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use inspect_server::config::{InspectServerConfig, TlsConfig};
+use inspect_server::inspect::InspectClient;
+
+const TLS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/tls");
+
+struct EchoInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for EchoInspect {
+    async fn inspect_state(
+        &self,
+        payload: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse {
+            reports: vec![Report { payload }],
+            exception: None,
+            completion_status: CompletionStatus::Accepted,
+            processed_input_count: None,
+        })
+    }
+}
+
+fn tls_path(name: &str) -> String {
+    format!("{}/{}", TLS_DIR, name)
+}
+
+fn server_tls_config() -> ServerTlsConfig {
+    let cert = std::fs::read(tls_path("server.pem")).unwrap();
+    let key = std::fs::read(tls_path("server.key")).unwrap();
+    ServerTlsConfig::new().identity(Identity::from_pem(cert, key))
+}
+
+fn tls_test_config(ca_cert_path: String) -> InspectServerConfig {
+    let mut config = test_config();
+    config.tls_config = Some(TlsConfig {
+        ca_cert_path,
+        client_identity: None,
+        domain_name: Some("localhost".to_string()),
+    });
+    config
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_inspects_over_tls() {
+    let server_manager = MockServerManagerWrapper::start_with_tls(
+        EchoInspect {},
+        Some(server_tls_config()),
+    )
+    .await;
+    let client = InspectClient::new(&tls_test_config(tls_path("ca.pem")));
+    let response = client
+        .inspect(b"hello".to_vec())
+        .await
+        .expect("failed to inspect over tls");
+    assert_eq!(
+        response.reports,
+        vec![Report {
+            payload: b"hello".to_vec()
+        }]
+    );
+    server_manager.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_fails_to_connect_when_handshake_fails() {
+    // The server manager doesn't serve TLS, so the handshake fails
+    let server_manager = MockServerManagerWrapper::start(EchoInspect {}).await;
+    let client = InspectClient::new(&tls_test_config(tls_path("ca.pem")));
+    let err = client
+        .inspect(b"hello".to_vec())
+        .await
+        .expect_err("inspect should fail");
+    assert!(
+        err.to_string()
+            .starts_with("Failed to connect to server manager"),
+        "unexpected error: {}",
+        err
+    );
+    assert!(server_manager.received_requests().is_empty());
+    server_manager.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_fails_to_connect_when_ca_cert_is_missing() {
+    let server_manager = MockServerManagerWrapper::start_with_tls(
+        EchoInspect {},
+        Some(server_tls_config()),
+    )
+    .await;
+    let client = InspectClient::new(&tls_test_config(tls_path("missing.pem")));
+    let err = client
+        .inspect(b"hello".to_vec())
+        .await
+        .expect_err("inspect should fail");
+    let message = err.to_string();
+    assert!(
+        message.starts_with("Failed to connect to server manager")
+            && message.contains("missing.pem"),
+        "unexpected error: {}",
+        message
+    );
+    server_manager.stop().await;
+}
@@ -0,0 +1,65 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+struct EchoInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for EchoInspect {
+    async fn inspect_state(
+        &self,
+        payload: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse {
+            reports: vec![Report { payload }],
+            exception: None,
+            completion_status: CompletionStatus::Accepted,
+            processed_input_count: None,
+        })
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_inspects_over_a_unix_socket() {
+    let path = std::env::temp_dir()
+        .join(format!("inspect-server-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let server_manager =
+        MockServerManagerWrapper::start_with_unix_socket(EchoInspect {}, &path)
+            .await;
+    let mut config = test_config();
+    config.server_manager_address = format!("unix:{}", path.display());
+    let client = InspectClient::new(&config);
+    client
+        .check_ready()
+        .await
+        .expect("server manager should be ready");
+    let response = client
+        .inspect(b"hello".to_vec())
+        .await
+        .expect("failed to inspect over unix socket");
+    assert_eq!(response.reports.len(), 1);
+    assert_eq!(response.reports[0].payload, b"hello");
+    assert_eq!(server_manager.received_requests().len(), 1);
+    server_manager.stop().await;
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_fails_to_connect_to_a_missing_unix_socket() {
+    let mut config = test_config();
+    config.server_manager_address =
+        String::from("unix:/nonexistent/inspect-server.sock");
+    let client = InspectClient::new(&config);
+    let err = client
+        .inspect(b"hello".to_vec())
+        .await
+        .expect_err("inspect should fail");
+    assert!(err
+        .to_string()
+        .starts_with("Failed to connect to server manager"));
+}
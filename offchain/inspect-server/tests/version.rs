@@ -0,0 +1,35 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use inspect_server::server::HttpVersionResponse;
+
+struct AcceptInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for AcceptInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse::default())
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_version_returns_the_compiled_in_version() {
+    let state = TestState::setup(AcceptInspect {}).await;
+    let url = format!("http://{}/version", INSPECT_SERVER_ADDRESS);
+    let response = reqwest::get(url).await.expect("failed to send request");
+    assert_eq!(response.status(), StatusCode::OK);
+    let version: HttpVersionResponse =
+        response.json().await.expect("failed to decode response");
+    assert_eq!(version.version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(version.proto_schema_hash, grpc_interfaces::SCHEMA_HASH);
+    assert_eq!(version.proto_schema_hash.len(), 64);
+    assert_eq!(version, HttpVersionResponse::current());
+    state.teardown().await;
+}
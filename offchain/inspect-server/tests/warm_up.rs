@@ -0,0 +1,76 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use inspect_server::config::InspectServerConfig;
+use std::time::Duration;
+
+// Address of the inspect servers started with run, which are not stopped by the tests
+const WARM_UP_SERVER_ADDRESS: &str = "127.0.0.1:50012";
+
+struct AcceptInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for AcceptInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse::default())
+    }
+}
+
+fn warm_up_config() -> InspectServerConfig {
+    let mut config = test_config();
+    config.inspect_server_address = WARM_UP_SERVER_ADDRESS.to_string();
+    config.readiness_timeout = Duration::from_secs(1);
+    config.warm_up = true;
+    config.warm_up_payload = b"warm up".to_vec();
+    config
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_warm_up_is_sent_before_the_server_is_ready() {
+    let server_manager =
+        MockServerManagerWrapper::start(AcceptInspect {}).await;
+    let server = tokio::spawn(inspect_server::run(warm_up_config()));
+    let url = format!("http://{}/readyz", WARM_UP_SERVER_ADDRESS);
+    let ready = async {
+        loop {
+            if let Ok(response) = reqwest::get(&url).await {
+                return response.status();
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    let status = tokio::time::timeout(Duration::from_secs(5), ready)
+        .await
+        .expect("inspect server should start");
+    assert_eq!(status, StatusCode::OK);
+    let received = server_manager.received_requests();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].payload, b"warm up");
+    server.abort();
+    server_manager.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_required_warm_up_failure_stops_the_startup() {
+    let mut config = warm_up_config();
+    config.readiness_timeout = Duration::ZERO;
+    config.warm_up_required = true;
+    let err = tokio::time::timeout(
+        Duration::from_secs(5),
+        inspect_server::run(config),
+    )
+    .await
+    .expect("run should fail promptly")
+    .expect_err("run should fail without server manager");
+    assert!(err
+        .to_string()
+        .starts_with("Failed to connect to server manager"));
+}
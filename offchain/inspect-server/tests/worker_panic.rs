@@ -0,0 +1,88 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+mod common;
+use crate::common::*;
+
+use inspect_server::error::InspectError;
+use inspect_server::inspect::InspectStateResponse;
+use inspect_server::metrics::QueueObserver;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+struct AcceptInspect {}
+
+#[tonic::async_trait]
+impl MockInspect for AcceptInspect {
+    async fn inspect_state(
+        &self,
+        _: Vec<u8>,
+    ) -> Result<MockInspectResponse, Status> {
+        Ok(MockInspectResponse::default())
+    }
+}
+
+/// Observer that panics in the worker when it dequeues the first requests.
+struct PanickingObserver {
+    panics: AtomicUsize,
+}
+
+impl QueueObserver for PanickingObserver {
+    fn dequeued(&self, _: usize, _: usize) {
+        let remaining = self.panics.load(Ordering::SeqCst);
+        if remaining > 0 {
+            self.panics.store(remaining - 1, Ordering::SeqCst);
+            panic!("observer failed");
+        }
+    }
+}
+
+async fn inspect(
+    client: &InspectClient,
+) -> Result<InspectStateResponse, InspectError> {
+    tokio::time::timeout(
+        Duration::from_secs(1),
+        client.inspect(b"hello".to_vec()),
+    )
+    .await
+    .expect("inspect should not hang")
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_fails_the_requests_dropped_by_a_panic() {
+    let server_manager =
+        MockServerManagerWrapper::start(AcceptInspect {}).await;
+    let observer = Arc::new(PanickingObserver {
+        panics: AtomicUsize::new(usize::MAX),
+    });
+    let client = InspectClient::with_queue_observer(&test_config(), observer);
+    for _ in 0..3 {
+        let err = inspect(&client).await.expect_err("inspect should fail");
+        assert!(
+            matches!(err, InspectError::ServiceUnavailable),
+            "unexpected error {:?}",
+            err
+        );
+    }
+    server_manager.stop().await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_it_restarts_the_worker_after_a_panic() {
+    let server_manager =
+        MockServerManagerWrapper::start(AcceptInspect {}).await;
+    let observer = Arc::new(PanickingObserver {
+        panics: AtomicUsize::new(1),
+    });
+    let client = InspectClient::with_queue_observer(&test_config(), observer);
+    inspect(&client)
+        .await
+        .expect_err("the first inspect should fail");
+    inspect(&client)
+        .await
+        .expect("the worker should handle the next inspect");
+    server_manager.stop().await;
+}
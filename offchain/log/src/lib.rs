@@ -1,10 +1,14 @@
 // (c) Cartesi and individual authors (see AUTHORS)
 // SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+use std::collections::HashMap;
 use std::fmt::Debug;
 
-use clap::Parser;
+use clap::{parser::ValueSource, ArgMatches, Parser, ValueEnum};
 use tracing::info;
 use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{Layer, Registry};
 
 pub mod built_info {
     include!(concat!(env!("OUT_DIR"), "/built.rs"));
@@ -18,12 +22,25 @@ pub struct LogEnvCliConfig {
 
     #[arg(long, env, default_value = "false")]
     pub log_enable_color: bool,
+
+    #[arg(long, env, value_enum, default_value_t = LogFormat::Pretty)]
+    pub log_format: LogFormat,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable logs
+    #[default]
+    Pretty,
+    /// Line-delimited JSON logs, with the span fields as structured data
+    Json,
 }
 
 #[derive(Clone, Debug, Default)]
 pub struct LogConfig {
     pub enable_timestamp: bool,
     pub enable_color: bool,
+    pub format: LogFormat,
 }
 
 impl LogConfig {
@@ -32,9 +49,12 @@ impl LogConfig {
 
         let enable_color = env_cli_config.log_enable_color;
 
+        let format = env_cli_config.log_format;
+
         LogConfig {
             enable_timestamp,
             enable_color,
+            format,
         }
     }
 }
@@ -45,21 +65,44 @@ impl From<LogEnvCliConfig> for LogConfig {
     }
 }
 
+/// Layer added to the logs by configure_with_layers, e.g. to export the spans.
+pub type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
 pub fn configure(config: &LogConfig) {
+    configure_with_layers(config, Vec::new());
+}
+
+/// Same as configure, with extra layers that get the same filter as the logs.
+pub fn configure_with_layers(config: &LogConfig, mut layers: Vec<BoxedLayer>) {
     let filter = EnvFilter::builder()
         .with_default_directive(LevelFilter::INFO.into())
         .from_env_lossy();
 
-    let subscribe_builder = tracing_subscriber::fmt()
-        .compact()
-        .with_env_filter(filter)
-        .with_ansi(config.enable_color);
+    let fmt_layer = tracing_subscriber::fmt::layer();
 
-    if !config.enable_timestamp {
-        subscribe_builder.without_time().init();
-    } else {
-        subscribe_builder.init();
-    }
+    let fmt_layer = match config.format {
+        LogFormat::Pretty => {
+            let fmt_layer = fmt_layer.compact().with_ansi(config.enable_color);
+            if !config.enable_timestamp {
+                fmt_layer.without_time().boxed()
+            } else {
+                fmt_layer.boxed()
+            }
+        }
+        LogFormat::Json => {
+            let fmt_layer = fmt_layer.json().with_ansi(false);
+            if !config.enable_timestamp {
+                fmt_layer.without_time().boxed()
+            } else {
+                fmt_layer.boxed()
+            }
+        }
+    };
+    layers.insert(0, fmt_layer);
+
+    tracing_subscriber::registry()
+        .with(layers.with_filter(filter))
+        .init();
 }
 
 pub fn log_service_start<C: Debug>(config: &C, service_name: &str) {
@@ -69,3 +112,73 @@ pub fn log_service_start<C: Debug>(config: &C, service_name: &str) {
     let message = format!("Starting {service} (version={version}, git ref={git_ref}, git hash={git_hash}) with config {:?}",config, service = service_name, version = built_info::PKG_VERSION, git_ref = git_ref, git_hash = git_hash);
     info!(message);
 }
+
+/// Where the value of a setting of the effective configuration came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigSource {
+    CommandLine,
+    Environment,
+    File,
+    Default,
+}
+
+/// Sources of the settings, by the id of their command-line argument.
+/// The settings that are not in it have their default value.
+#[derive(Clone, Debug, Default)]
+pub struct ConfigSources {
+    sources: HashMap<String, ConfigSource>,
+}
+
+impl ConfigSources {
+    /// Sources of the settings given in the command line or in the environment.
+    pub fn from_matches(matches: &ArgMatches) -> Self {
+        let sources = matches
+            .ids()
+            .filter_map(|id| {
+                let source = match matches.value_source(id.as_str())? {
+                    ValueSource::CommandLine => ConfigSource::CommandLine,
+                    ValueSource::EnvVariable => ConfigSource::Environment,
+                    _ => return None,
+                };
+                Some((id.to_string(), source))
+            })
+            .collect();
+        Self { sources }
+    }
+
+    /// Mark the settings given in the config file, unless the command line or the environment
+    /// already set them.
+    pub fn with_file<'a>(
+        mut self,
+        ids: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        for id in ids {
+            self.sources
+                .entry(id.to_string())
+                .or_insert(ConfigSource::File);
+        }
+        self
+    }
+
+    pub fn get(&self, id: &str) -> ConfigSource {
+        self.sources
+            .get(id)
+            .copied()
+            .unwrap_or(ConfigSource::Default)
+    }
+}
+
+/// Value of a sensitive setting, which is logged instead of the real one.
+pub struct Redacted;
+
+impl Debug for Redacted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+/// Log a setting of the effective configuration and where it came from.
+/// Pass Redacted as the value of the sensitive settings.
+pub fn log_setting(name: &str, value: &dyn Debug, source: ConfigSource) {
+    info!(setting = name, value = ?value, source = ?source, "effective configuration");
+}
@@ -1,8 +1,12 @@
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches, Parser};
 use eth_state_server_lib::config::{
     Result, StateServerConfig, StateServerEnvCLIConfig,
 };
-use log::{LogConfig, LogEnvCliConfig};
+use log::{ConfigSources, LogConfig, LogEnvCliConfig};
+use state_server::KeepaliveConfig;
+use std::fmt::Debug;
+use std::time::Duration;
+use url::Url;
 
 #[derive(Parser)]
 #[command(name = "state_server_config")]
@@ -13,16 +17,43 @@ pub struct EnvCLIConfig {
 
     #[command(flatten)]
     pub log_config: LogEnvCliConfig,
+
+    /// Time given to the fold requests in flight to finish when shutting down (in millis)
+    #[arg(long, env, default_value_t = 30000)]
+    pub ss_shutdown_timeout: u64,
+
+    /// Interval between the HTTP/2 pings sent to idle gRPC connections (in millis)
+    #[arg(long, env, default_value_t = 60000)]
+    pub ss_http2_keepalive_interval: u64,
+
+    /// Time to wait for the HTTP/2 ping response before closing the connection (in millis)
+    #[arg(long, env, default_value_t = 20000)]
+    pub ss_http2_keepalive_timeout: u64,
+
+    /// Interval of the TCP keepalive probes of the gRPC connections (in millis)
+    #[arg(long, env, default_value_t = 60000)]
+    pub ss_tcp_keepalive: u64,
+
+    /// Maximum number of concurrent gRPC streams of each connection
+    #[arg(long, env, default_value_t = 128)]
+    pub ss_max_concurrent_streams: u32,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Config {
     pub state_server_config: StateServerConfig,
     pub log_config: LogConfig,
+    pub shutdown_timeout: Duration,
+    pub keepalive: KeepaliveConfig,
+    /// Where the value of each setting came from, by the id of its command-line argument
+    pub sources: ConfigSources,
 }
 
 impl Config {
-    pub fn initialize(env_cli_config: EnvCLIConfig) -> Result<Self> {
+    pub fn initialize(
+        env_cli_config: EnvCLIConfig,
+        sources: ConfigSources,
+    ) -> Result<Self> {
         let state_server_config =
             StateServerConfig::initialize(env_cli_config.state_server_config);
         let log_config = LogConfig::initialize(env_cli_config.log_config);
@@ -30,11 +61,127 @@ impl Config {
         Ok(Self {
             state_server_config: state_server_config?,
             log_config,
+            shutdown_timeout: Duration::from_millis(
+                env_cli_config.ss_shutdown_timeout,
+            ),
+            keepalive: KeepaliveConfig {
+                http2_keepalive_interval: Duration::from_millis(
+                    env_cli_config.ss_http2_keepalive_interval,
+                ),
+                http2_keepalive_timeout: Duration::from_millis(
+                    env_cli_config.ss_http2_keepalive_timeout,
+                ),
+                tcp_keepalive: Duration::from_millis(
+                    env_cli_config.ss_tcp_keepalive,
+                ),
+                max_concurrent_streams: env_cli_config
+                    .ss_max_concurrent_streams,
+            },
+            sources,
         })
     }
 
     pub fn initialize_from_args() -> Result<Self> {
-        let env_cli_config = EnvCLIConfig::parse();
-        Self::initialize(env_cli_config)
+        let matches = EnvCLIConfig::command().get_matches();
+        let env_cli_config = EnvCLIConfig::from_arg_matches(&matches)
+            .unwrap_or_else(|e| e.exit());
+        Self::initialize(env_cli_config, ConfigSources::from_matches(&matches))
+    }
+
+    /// Log each setting of the effective configuration and where its value came from.
+    /// Only the scheme, host and port of the blockchain endpoints are logged, since their paths
+    /// and credentials often carry API keys.
+    pub fn log_effective(&self) {
+        let state_fold = &self.state_server_config.state_fold;
+        let block_history = &self.state_server_config.block_history;
+        let settings: [(&str, &dyn Debug); 18] = [
+            (
+                "ss_server_address",
+                &self.state_server_config.server_address,
+            ),
+            (
+                "ss_max_decoding_message_size",
+                &self.state_server_config.max_decoding_message_size,
+            ),
+            ("bh_ws_endpoint", &redact_url(&block_history.ws_endpoint)),
+            (
+                "bh_http_endpoint",
+                &redact_url(&block_history.http_endpoint),
+            ),
+            ("bh_block_timeout", &block_history.block_timeout),
+            ("bh_max_depth", &block_history.max_depth),
+            (
+                "sf_concurrent_events_fetch",
+                &state_fold.concurrent_events_fetch,
+            ),
+            ("sf_genesis_block", &state_fold.genesis_block),
+            (
+                "sf_query_limit_error_codes",
+                &state_fold.query_limit_error_codes,
+            ),
+            ("sf_safety_margin", &state_fold.safety_margin),
+            ("log_enable_timestamp", &self.log_config.enable_timestamp),
+            ("log_enable_color", &self.log_config.enable_color),
+            ("log_format", &self.log_config.format),
+            ("ss_shutdown_timeout", &self.shutdown_timeout),
+            (
+                "ss_http2_keepalive_interval",
+                &self.keepalive.http2_keepalive_interval,
+            ),
+            (
+                "ss_http2_keepalive_timeout",
+                &self.keepalive.http2_keepalive_timeout,
+            ),
+            ("ss_tcp_keepalive", &self.keepalive.tcp_keepalive),
+            (
+                "ss_max_concurrent_streams",
+                &self.keepalive.max_concurrent_streams,
+            ),
+        ];
+        for (id, value) in settings {
+            log::log_setting(id, value, self.sources.get(id));
+        }
+    }
+}
+
+impl Debug for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let block_history = &self.state_server_config.block_history;
+        f.debug_struct("Config")
+            .field("state_fold", &self.state_server_config.state_fold)
+            .field("ws_endpoint", &redact_url(&block_history.ws_endpoint))
+            .field("http_endpoint", &redact_url(&block_history.http_endpoint))
+            .field("block_timeout", &block_history.block_timeout)
+            .field("max_depth", &block_history.max_depth)
+            .field("server_address", &self.state_server_config.server_address)
+            .field(
+                "max_decoding_message_size",
+                &self.state_server_config.max_decoding_message_size,
+            )
+            .field("log_config", &self.log_config)
+            .field("shutdown_timeout", &self.shutdown_timeout)
+            .field("keepalive", &self.keepalive)
+            .finish()
+    }
+}
+
+/// Scheme, host and port of an endpoint URL, without the path, query and credentials.
+fn redact_url(url: &str) -> String {
+    let url = match Url::parse(url) {
+        Ok(url) => url,
+        Err(_) => return String::from("<redacted>"),
+    };
+    let host = url.host_str().unwrap_or_default();
+    let mut redacted = match url.port() {
+        Some(port) => format!("{}://{}:{}", url.scheme(), host, port),
+        None => format!("{}://{}", url.scheme(), host),
+    };
+    if url.path() != "/"
+        || url.query().is_some()
+        || !url.username().is_empty()
+        || url.password().is_some()
+    {
+        redacted.push_str("/<redacted>");
     }
+    redacted
 }
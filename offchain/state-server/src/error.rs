@@ -4,6 +4,7 @@
 use eth_block_history::BlockArchiveError;
 use eth_state_fold_types::ethers::providers::{Http, RetryClient};
 use snafu::Snafu;
+use std::net::SocketAddr;
 use tonic::transport::Error as TonicError;
 use url::ParseError;
 
@@ -11,6 +12,12 @@ use url::ParseError;
 #[snafu(visibility(pub(crate)))]
 #[allow(clippy::enum_variant_names)]
 pub enum StateServerError {
+    #[snafu(display("failed to bind {}", address))]
+    BindError {
+        address: SocketAddr,
+        source: std::io::Error,
+    },
+
     #[snafu(display("tonic error"))]
     TonicError { source: TonicError },
 
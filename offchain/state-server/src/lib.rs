@@ -5,29 +5,37 @@ use eth_state_fold::{Foldable, StateFoldEnvironment};
 use eth_state_fold_types::ethers::providers::{
     Http, HttpRateLimitRetryPolicy, Provider, RetryClient,
 };
-use eth_state_server_lib::{
-    config,
-    grpc_server::StateServer,
-    utils::{start_server, wait_for_signal},
-};
+use eth_state_server_lib::{config, grpc_server::StateServer};
 use snafu::ResultExt;
+use std::future::Future;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
 use tokio::sync::oneshot;
 use types::UserData;
 use url::Url;
 
 use crate::error::{
-    BlockArchiveSnafu, ParserSnafu, StateServerError, TonicSnafu,
+    BindSnafu, BlockArchiveSnafu, ParserSnafu, StateServerError, TonicSnafu,
 };
+use crate::server::{start_server, InFlightLayer};
+
+pub use crate::server::KeepaliveConfig;
 
 mod error;
+mod server;
 
 const MAX_RETRIES: u32 = 10;
 const INITIAL_BACKOFF: u64 = 1000;
 
+/// Run the state server until SIGINT or SIGTERM is received.
+/// After the signal, the fold requests in flight have up to shutdown_timeout to finish; the
+/// remaining ones are dropped when it elapses.
 #[tracing::instrument(level = "trace")]
 pub async fn run_server<F: Foldable<UserData = Mutex<UserData>> + 'static>(
     config: config::StateServerConfig,
+    shutdown_timeout: Duration,
+    keepalive: KeepaliveConfig,
 ) -> Result<(), StateServerError>
 where
     <F as Foldable>::InitialState: serde::de::DeserializeOwned,
@@ -44,15 +52,58 @@ where
 
     let server = StateServer::<_, _, F>::new(block_subscriber, env);
 
+    let (signal_tx, signal_rx) = oneshot::channel();
     let (shutdown_tx, shutdown_rx) = oneshot::channel();
 
-    tokio::spawn(async { wait_for_signal(shutdown_tx).await });
+    tokio::spawn(async { wait_for_signal(signal_tx).await });
 
-    start_server(&config, server, shutdown_rx)
+    let listener =
+        TcpListener::bind(config.server_address)
+            .await
+            .context(BindSnafu {
+                address: config.server_address,
+            })?;
+    let in_flight = InFlightLayer::default();
+    let server = start_server(
+        &config,
+        listener,
+        &keepalive,
+        in_flight.clone(),
+        server,
+        shutdown_rx,
+    );
+    tokio::pin!(server);
+    tokio::select! {
+        result = &mut server => return result.context(TonicSnafu),
+        _ = signal_rx => {
+            let _ = shutdown_tx.send(());
+        }
+    }
+
+    wait_for_shutdown(server, shutdown_timeout, &in_flight)
         .await
         .context(TonicSnafu)
 }
 
+/// Wait for the server to finish the requests in flight, up to shutdown_timeout.
+async fn wait_for_shutdown<E>(
+    server: impl Future<Output = Result<(), E>>,
+    shutdown_timeout: Duration,
+    in_flight: &InFlightLayer,
+) -> Result<(), E> {
+    match tokio::time::timeout(shutdown_timeout, server).await {
+        Ok(result) => result,
+        Err(_) => {
+            tracing::warn!(
+                in_flight = in_flight.count(),
+                "fold requests still in flight after {:?}; closing them",
+                shutdown_timeout
+            );
+            Ok(())
+        }
+    }
+}
+
 type ServerProvider = Provider<RetryClient<Http>>;
 
 fn create_provider(
@@ -114,3 +165,55 @@ async fn create_block_subscriber(
 
     Ok(Arc::new(block_subscriber))
 }
+
+/// Send to tx when SIGINT or SIGTERM is received, so the server shuts down gracefully.
+/// Container runtimes send SIGTERM when stopping the service.
+async fn wait_for_signal(tx: oneshot::Sender<()>) {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut sigterm = signal(SignalKind::terminate())
+            .expect("failed to listen for SIGTERM");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {
+                tracing::info!("SIGINT received: shutting down");
+            }
+            _ = sigterm.recv() => {
+                tracing::info!("SIGTERM received: shutting down");
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        tracing::info!("SIGINT received: shutting down");
+    }
+    let _ = tx.send(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::testing::TestServer;
+
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn it_stops_waiting_for_the_requests_in_flight_at_the_timeout() {
+        let server = TestServer::start().await;
+        let in_flight = server.in_flight.clone();
+        let _request = server.spawn_blocking_call().await;
+        // Wait until the server is handling the request
+        while in_flight.count() == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let shutdown_timeout = Duration::from_millis(100);
+        let result = tokio::time::timeout(
+            2 * shutdown_timeout,
+            wait_for_shutdown(server.shutdown(), shutdown_timeout, &in_flight),
+        )
+        .await
+        .expect("shutdown should finish within the timeout");
+        assert!(result.is_ok());
+        assert!(logs_contain("in_flight=1"));
+    }
+}
@@ -11,8 +11,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     log::configure(&config.log_config);
 
     log::log_service_start(&config, "State Server");
+    config.log_effective();
 
-    state_server::run_server::<RollupsState>(config.state_server_config)
-        .await
-        .map_err(|e| e.into())
+    state_server::run_server::<RollupsState>(
+        config.state_server_config,
+        config.shutdown_timeout,
+        config.keepalive,
+    )
+    .await
+    .map_err(|e| e.into())
 }
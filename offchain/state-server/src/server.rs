@@ -0,0 +1,424 @@
+// (c) Cartesi and individual authors (see AUTHORS)
+// SPDX-License-Identifier: Apache-2.0 (see LICENSE)
+
+use eth_state_fold::Foldable;
+use eth_state_fold_types::ethers::providers::Middleware;
+use eth_state_server_common::state_fold_server::state_fold_server::StateFoldServer;
+use eth_state_server_lib::{
+    config::StateServerConfig, grpc_server::StateServer,
+};
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::body::BoxBody;
+use tonic::codegen::http;
+use tonic::server::NamedService;
+use tonic::transport::{Body, Server};
+use tower::{Layer, Service};
+
+/// Keepalive and connection limits of the gRPC server.
+#[derive(Debug, Clone)]
+pub struct KeepaliveConfig {
+    /// Interval between the HTTP/2 pings sent to idle connections.
+    /// The default of 60s is below the idle timeout of common NATs and load balancers, so the
+    /// connections of the dispatcher and the authority claimer are not dropped silently.
+    pub http2_keepalive_interval: Duration,
+    /// Time to wait for the ping response before closing the connection.
+    /// The default of 20s is the tonic default.
+    pub http2_keepalive_timeout: Duration,
+    /// Interval of the TCP keepalive probes sent by the operating system; 60s by default.
+    pub tcp_keepalive: Duration,
+    /// Maximum number of concurrent streams of each connection.
+    /// Each fold query is a stream, so the default of 128 is well above what the node services
+    /// send at the same time, while bounding a misbehaving client.
+    pub max_concurrent_streams: u32,
+}
+
+impl Default for KeepaliveConfig {
+    fn default() -> Self {
+        Self {
+            http2_keepalive_interval: Duration::from_secs(60),
+            http2_keepalive_timeout: Duration::from_secs(20),
+            tcp_keepalive: Duration::from_secs(60),
+            max_concurrent_streams: 128,
+        }
+    }
+}
+
+/// Layer that counts the requests being served, so the shutdown can report the ones it drops.
+/// A request is in flight from the call of the service until its response future is dropped.
+#[derive(Debug, Clone, Default)]
+pub struct InFlightLayer {
+    count: Arc<AtomicUsize>,
+}
+
+impl InFlightLayer {
+    /// Number of requests in flight.
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::Acquire)
+    }
+
+    fn track(&self) -> InFlightGuard {
+        self.count.fetch_add(1, Ordering::AcqRel);
+        InFlightGuard {
+            count: Arc::clone(&self.count),
+        }
+    }
+}
+
+impl<S> Layer<S> for InFlightLayer {
+    type Service = InFlightService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        InFlightService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InFlightService<S> {
+    inner: S,
+    layer: InFlightLayer,
+}
+
+impl<S, R> Service<R> for InFlightService<S>
+where
+    S: Service<R>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        let guard = self.layer.track();
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let _guard = guard;
+            response.await
+        })
+    }
+}
+
+/// Decrement the count of requests in flight when dropped.
+struct InFlightGuard {
+    count: Arc<AtomicUsize>,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.count.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Serve the state fold gRPC service and its health service on the listener until the kill
+/// switch is triggered or the block subscriber exits.
+/// This is the same as eth_state_server_lib::utils::start_server, which doesn't allow to configure
+/// the transport, with the keepalive settings. The listener is bound by the caller, which can read
+/// its address, e.g. when binding port 0.
+pub async fn start_server<
+    M: Middleware + 'static,
+    UD: Send + Sync + 'static,
+    F: Foldable<UserData = UD> + 'static,
+>(
+    config: &StateServerConfig,
+    listener: TcpListener,
+    keepalive: &KeepaliveConfig,
+    in_flight: InFlightLayer,
+    state_server: StateServer<M, UD, F>,
+    kill_switch: oneshot::Receiver<()>,
+) -> Result<(), tonic::transport::Error>
+where
+    F::InitialState: serde::de::DeserializeOwned + 'static,
+    F: serde::Serialize,
+{
+    let block_subscriber = Arc::clone(&state_server.block_subscriber);
+    let state_fold_server = StateFoldServer::new(state_server)
+        .max_decoding_message_size(config.max_decoding_message_size);
+    let shutdown = async move {
+        tokio::select! {
+            r = block_subscriber.wait_for_completion() => {
+                tracing::error!("`block_subscriber` has exited: {:?}", r);
+                tracing::error!("Shutting down...");
+            }
+            r = kill_switch => {
+                tracing::info!("Graceful context shutdown: {:?}", r);
+            }
+        }
+    };
+    serve(listener, keepalive, in_flight, state_fold_server, shutdown).await
+}
+
+/// Serve the gRPC service and its health service on the listener until shutdown completes.
+/// The health service reports the service as not serving once the shutdown starts.
+async fn serve<S>(
+    listener: TcpListener,
+    keepalive: &KeepaliveConfig,
+    in_flight: InFlightLayer,
+    service: S,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), tonic::transport::Error>
+where
+    S: Service<
+            http::Request<Body>,
+            Response = http::Response<BoxBody>,
+            Error = Infallible,
+        > + NamedService
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    let (mut health_reporter, health_server) =
+        tonic_health::server::health_reporter();
+    health_reporter.set_serving::<S>().await;
+
+    match listener.local_addr() {
+        Ok(address) => tracing::info!(
+            ?keepalive,
+            service = S::NAME,
+            "gRPC server listening on {}",
+            address
+        ),
+        Err(e) => tracing::warn!("failed to get the server address: {}", e),
+    }
+
+    Server::builder()
+        .trace_fn(|_| tracing::trace_span!("state_fold_server"))
+        .http2_keepalive_interval(Some(keepalive.http2_keepalive_interval))
+        .http2_keepalive_timeout(Some(keepalive.http2_keepalive_timeout))
+        .tcp_keepalive(Some(keepalive.tcp_keepalive))
+        .max_concurrent_streams(keepalive.max_concurrent_streams)
+        .layer(in_flight)
+        .add_service(health_server)
+        .add_service(service)
+        .serve_with_incoming_shutdown(TcpListenerStream::new(listener), async {
+            shutdown.await;
+            // The clients watching the health see the server going away
+            health_reporter.set_not_serving::<S>().await;
+        })
+        .await
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+    use std::net::SocketAddr;
+    use tokio::task::JoinHandle;
+    use tonic::codec::ProstCodec;
+    use tonic::codegen::http::uri::PathAndQuery;
+    use tonic::transport::Channel;
+    use tonic::{Status, Streaming};
+    use tonic_health::pb::health_check_response::ServingStatus;
+    use tonic_health::pb::health_client::HealthClient;
+    use tonic_health::pb::{HealthCheckRequest, HealthCheckResponse};
+
+    /// Wait for the next status of a health watch stream.
+    pub async fn next_status(
+        statuses: &mut Streaming<HealthCheckResponse>,
+    ) -> ServingStatus {
+        let response =
+            tokio::time::timeout(Duration::from_secs(5), statuses.message())
+                .await
+                .expect("timed out waiting for the health status")
+                .expect("failed to read health")
+                .expect("health stream closed");
+        ServingStatus::from_i32(response.status)
+            .expect("invalid serving status")
+    }
+
+    /// gRPC service whose calls never finish.
+    #[derive(Debug, Clone)]
+    pub struct BlockingService;
+
+    impl NamedService for BlockingService {
+        const NAME: &'static str = "test.Blocking";
+    }
+
+    impl Service<http::Request<Body>> for BlockingService {
+        type Response = http::Response<BoxBody>;
+        type Error = Infallible;
+        type Future = std::future::Pending<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(
+            &mut self,
+            _: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _: http::Request<Body>) -> Self::Future {
+            std::future::pending()
+        }
+    }
+
+    /// Server of the blocking service running on an ephemeral port of localhost.
+    pub struct TestServer {
+        pub address: SocketAddr,
+        pub in_flight: InFlightLayer,
+        shutdown: Option<oneshot::Sender<()>>,
+        join_handle: JoinHandle<Result<(), tonic::transport::Error>>,
+    }
+
+    impl TestServer {
+        pub async fn start() -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0")
+                .await
+                .expect("failed to bind test server");
+            let address = listener
+                .local_addr()
+                .expect("failed to get test server address");
+            let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+            let in_flight = InFlightLayer::default();
+            let join_handle = {
+                let in_flight = in_flight.clone();
+                tokio::spawn(async move {
+                    let shutdown = async {
+                        let _ = shutdown_rx.await;
+                    };
+                    serve(
+                        listener,
+                        &KeepaliveConfig::default(),
+                        in_flight,
+                        BlockingService,
+                        shutdown,
+                    )
+                    .await
+                })
+            };
+            Self {
+                address,
+                in_flight,
+                shutdown: Some(shutdown_tx),
+                join_handle,
+            }
+        }
+
+        pub async fn connect(&self) -> Channel {
+            Channel::from_shared(format!("http://{}", self.address))
+                .expect("invalid test server address")
+                .connect()
+                .await
+                .expect("failed to connect to test server")
+        }
+
+        pub async fn health_client(&self) -> HealthClient<Channel> {
+            HealthClient::new(self.connect().await)
+        }
+
+        pub async fn check_health(&self) -> ServingStatus {
+            let status = self
+                .health_client()
+                .await
+                .check(HealthCheckRequest {
+                    service: BlockingService::NAME.to_string(),
+                })
+                .await
+                .expect("failed to check health")
+                .into_inner()
+                .status;
+            ServingStatus::from_i32(status).expect("invalid serving status")
+        }
+
+        /// Call the blocking service from a new task, which only finishes when the server closes
+        /// the connection.
+        pub async fn spawn_blocking_call(
+            &self,
+        ) -> JoinHandle<Result<(), Status>> {
+            let mut client = tonic::client::Grpc::new(self.connect().await);
+            tokio::spawn(async move {
+                client.ready().await.expect("test server is not ready");
+                client
+                    .unary(
+                        tonic::Request::new(()),
+                        PathAndQuery::from_static("/test.Blocking/Call"),
+                        ProstCodec::<(), ()>::default(),
+                    )
+                    .await
+                    .map(|response| response.into_inner())
+            })
+        }
+
+        /// Trigger the shutdown and return the future of the server.
+        pub fn shutdown(
+            mut self,
+        ) -> impl Future<Output = Result<(), tonic::transport::Error>> {
+            if let Some(shutdown) = self.shutdown.take() {
+                let _ = shutdown.send(());
+            }
+            async move { self.join_handle.await.expect("test server panicked") }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{next_status, BlockingService, TestServer};
+    use super::*;
+    use tonic_health::pb::health_check_response::ServingStatus;
+    use tonic_health::pb::HealthCheckRequest;
+    use tower::service_fn;
+
+    #[tokio::test]
+    async fn it_counts_the_requests_in_flight() {
+        let layer = InFlightLayer::default();
+        let (response_tx, response_rx) = oneshot::channel::<()>();
+        let mut response_rx = Some(response_rx);
+        let mut service =
+            layer.layer(service_fn(move |_: ()| response_rx.take().unwrap()));
+        let response = tokio::spawn(service.call(()));
+        assert_eq!(layer.count(), 1);
+        response_tx.send(()).unwrap();
+        response.await.unwrap().unwrap();
+        assert_eq!(layer.count(), 0);
+    }
+
+    #[tokio::test]
+    async fn it_serves_on_the_port_bound_by_the_caller() {
+        let first = TestServer::start().await;
+        let second = TestServer::start().await;
+        assert_ne!(first.address, second.address);
+        assert_eq!(first.check_health().await, ServingStatus::Serving);
+        assert_eq!(second.check_health().await, ServingStatus::Serving);
+        first.shutdown().await.expect("first server failed");
+        second.shutdown().await.expect("second server failed");
+    }
+
+    #[tokio::test]
+    async fn it_reports_not_serving_after_the_shutdown() {
+        let server = TestServer::start().await;
+        assert_eq!(server.check_health().await, ServingStatus::Serving);
+        let mut statuses = server
+            .health_client()
+            .await
+            .watch(HealthCheckRequest {
+                service: BlockingService::NAME.to_string(),
+            })
+            .await
+            .expect("failed to watch health")
+            .into_inner();
+        assert_eq!(next_status(&mut statuses).await, ServingStatus::Serving);
+        let shutdown = server.shutdown();
+        assert_eq!(next_status(&mut statuses).await, ServingStatus::NotServing);
+        // The server waits for the watch stream to close
+        drop(statuses);
+        shutdown.await.expect("server failed");
+    }
+}